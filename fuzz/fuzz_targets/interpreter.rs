@@ -0,0 +1,306 @@
+//! Runs generated programs, which should only ever fail with an error.
+//! Programs are generated from a small grammar rather than from bytes, so
+//! most of them parse and get to run. A budget on the statements run stops
+//! the ones that loop forever.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use rutox::interpreter::{Hook, Interpreter};
+use rutox::parser::{ast::Stmt as AstStmt, Parser};
+use rutox::rutox_error::RutoxError;
+use rutox::scanner::{Scanner, SourceMap};
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::rc::Rc;
+
+/// How many statements a program can run before it is stopped.
+const BUDGET: usize = 10_000;
+
+/// How deeply generated code nests. Deeper parts are cut short.
+const MAX_DEPTH: usize = 8;
+
+#[derive(Arbitrary, Debug)]
+enum Stmt {
+    Print(Expr),
+    Expr(Expr),
+    Var(Name, Option<Expr>),
+    Block(Vec<Stmt>),
+    If(Expr, Box<Stmt>, Option<Box<Stmt>>),
+    While(Expr, Box<Stmt>),
+    Break,
+    Continue,
+    Fun(Name, Vec<Name>, Vec<Stmt>),
+    Return(Option<Expr>),
+    Throw(Expr),
+    Try(Vec<Stmt>, Option<(Name, Vec<Stmt>)>, Option<Vec<Stmt>>),
+}
+
+#[derive(Arbitrary, Debug)]
+enum Expr {
+    Number(i8),
+    String(Name),
+    Bool(bool),
+    Nil,
+    Variable(Name),
+    Assign(Name, Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(Box<Expr>, BinaryOp, Box<Expr>),
+    Logical(Box<Expr>, bool, Box<Expr>),
+    Grouping(Box<Expr>),
+    Call(Box<Expr>, Vec<Expr>),
+    Lambda(Vec<Name>, Vec<Stmt>),
+}
+
+#[derive(Arbitrary, Debug)]
+enum UnaryOp {
+    Bang,
+    Minus,
+}
+
+#[derive(Arbitrary, Debug)]
+enum BinaryOp {
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    EqualEqual,
+    BangEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+/// A few names, so declarations and references line up.
+#[derive(Arbitrary, Debug)]
+enum Name {
+    A,
+    B,
+    C,
+    F,
+}
+
+impl Name {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Name::A => "a",
+            Name::B => "b",
+            Name::C => "c",
+            Name::F => "f",
+        }
+    }
+}
+
+fn names(names: &[Name]) -> String {
+    names
+        .iter()
+        .map(Name::as_str)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn write_stmts(out: &mut String, stmts: &[Stmt], depth: usize) {
+    out.push_str("{\n");
+    for stmt in stmts {
+        write_stmt(out, stmt, depth + 1);
+    }
+    out.push_str("}\n");
+}
+
+fn write_stmt(out: &mut String, stmt: &Stmt, depth: usize) {
+    if depth > MAX_DEPTH {
+        out.push_str("{}\n");
+        return;
+    }
+
+    match stmt {
+        Stmt::Print(expr) => {
+            out.push_str("print ");
+            write_expr(out, expr, depth + 1);
+            out.push_str(";\n");
+        }
+        Stmt::Expr(expr) => {
+            write_expr(out, expr, depth + 1);
+            out.push_str(";\n");
+        }
+        Stmt::Var(name, initializer) => {
+            let _ = write!(out, "var {}", name.as_str());
+            if let Some(initializer) = initializer {
+                out.push_str(" = ");
+                write_expr(out, initializer, depth + 1);
+            }
+            out.push_str(";\n");
+        }
+        Stmt::Block(stmts) => write_stmts(out, stmts, depth),
+        Stmt::If(cond, then_branch, else_branch) => {
+            out.push_str("if (");
+            write_expr(out, cond, depth + 1);
+            out.push_str(") ");
+            write_stmt(out, then_branch, depth + 1);
+            if let Some(else_branch) = else_branch {
+                out.push_str("else ");
+                write_stmt(out, else_branch, depth + 1);
+            }
+        }
+        Stmt::While(cond, body) => {
+            out.push_str("while (");
+            write_expr(out, cond, depth + 1);
+            out.push_str(") ");
+            write_stmt(out, body, depth + 1);
+        }
+        Stmt::Break => out.push_str("break;\n"),
+        Stmt::Continue => out.push_str("continue;\n"),
+        Stmt::Fun(name, params, body) => {
+            let _ = write!(out, "fun {}({}) ", name.as_str(), names(params));
+            write_stmts(out, body, depth);
+        }
+        Stmt::Return(value) => {
+            out.push_str("return");
+            if let Some(value) = value {
+                out.push(' ');
+                write_expr(out, value, depth + 1);
+            }
+            out.push_str(";\n");
+        }
+        Stmt::Throw(value) => {
+            out.push_str("throw ");
+            write_expr(out, value, depth + 1);
+            out.push_str(";\n");
+        }
+        Stmt::Try(body, catch, finally) => {
+            out.push_str("try ");
+            write_stmts(out, body, depth);
+            if let Some((name, body)) = catch {
+                let _ = write!(out, "catch ({}) ", name.as_str());
+                write_stmts(out, body, depth);
+            }
+            if let Some(finally) = finally {
+                out.push_str("finally ");
+                write_stmts(out, finally, depth);
+            }
+        }
+    }
+}
+
+fn write_expr(out: &mut String, expr: &Expr, depth: usize) {
+    if depth > MAX_DEPTH {
+        out.push_str("nil");
+        return;
+    }
+
+    match expr {
+        Expr::Number(n) => {
+            let _ = write!(out, "{}", n.unsigned_abs());
+        }
+        Expr::String(name) => {
+            let _ = write!(out, "\"{}\"", name.as_str());
+        }
+        Expr::Bool(b) => {
+            let _ = write!(out, "{b}");
+        }
+        Expr::Nil => out.push_str("nil"),
+        Expr::Variable(name) => out.push_str(name.as_str()),
+        Expr::Assign(name, value) => {
+            let _ = write!(out, "{} = ", name.as_str());
+            write_expr(out, value, depth + 1);
+        }
+        Expr::Unary(op, expr) => {
+            out.push_str(match op {
+                UnaryOp::Bang => "!",
+                UnaryOp::Minus => "-",
+            });
+            write_expr(out, expr, depth + 1);
+        }
+        Expr::Binary(left, op, right) => {
+            let op = match op {
+                BinaryOp::Plus => "+",
+                BinaryOp::Minus => "-",
+                BinaryOp::Star => "*",
+                BinaryOp::Slash => "/",
+                BinaryOp::EqualEqual => "==",
+                BinaryOp::BangEqual => "!=",
+                BinaryOp::Less => "<",
+                BinaryOp::LessEqual => "<=",
+                BinaryOp::Greater => ">",
+                BinaryOp::GreaterEqual => ">=",
+            };
+            out.push('(');
+            write_expr(out, left, depth + 1);
+            let _ = write!(out, " {op} ");
+            write_expr(out, right, depth + 1);
+            out.push(')');
+        }
+        Expr::Logical(left, is_or, right) => {
+            out.push('(');
+            write_expr(out, left, depth + 1);
+            out.push_str(if *is_or { " or " } else { " and " });
+            write_expr(out, right, depth + 1);
+            out.push(')');
+        }
+        Expr::Grouping(expr) => {
+            out.push('(');
+            write_expr(out, expr, depth + 1);
+            out.push(')');
+        }
+        Expr::Call(callee, args) => {
+            write_expr(out, callee, depth + 1);
+            out.push('(');
+            for (i, arg) in args.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_expr(out, arg, depth + 1);
+            }
+            out.push(')');
+        }
+        Expr::Lambda(params, body) => {
+            let _ = write!(out, "fun ({}) ", names(params));
+            write_stmts(out, body, depth);
+        }
+    }
+}
+
+/// Stops the program once it has run `BUDGET` statements.
+struct Budget {
+    spent: usize,
+}
+
+impl Hook for Budget {
+    fn before_stmt(
+        &mut self,
+        _interpreter: &mut Interpreter,
+        stmt: &AstStmt,
+    ) -> Result<(), RutoxError> {
+        self.spent += 1;
+        if self.spent > BUDGET {
+            return Err(RutoxError::Programmer(
+                "Instruction budget exhausted".to_string(),
+                stmt.location(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+fuzz_target!(|program: Vec<Stmt>| {
+    let mut source = String::new();
+    for stmt in &program {
+        write_stmt(&mut source, stmt, 0);
+    }
+
+    let source_map = Rc::new(RefCell::new(SourceMap::new()));
+    let file = source_map.borrow_mut().add("fuzz", &source);
+    let Ok(stmts) = Scanner::new(source, file)
+        .scan_tokens()
+        .and_then(|tokens| Parser::new(tokens).parse())
+    else {
+        return;
+    };
+
+    let mut interpreter = Interpreter::new(source_map);
+    interpreter.set_hook(Box::new(Budget { spent: 0 }));
+    let _ = interpreter.interpret(stmts);
+});
@@ -0,0 +1,155 @@
+//! Parses arbitrary token streams, which should only ever fail with an error.
+//! Tokens are generated directly rather than scanned, so the parser sees
+//! sequences no scanner would produce too, e.g. an `Eof` in the middle.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use rutox::parser::Parser;
+use rutox::scanner::{
+    token::{Token, TokenKind},
+    SourceMap, SrcLocation,
+};
+
+#[derive(Arbitrary, Debug)]
+enum Kind {
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    Comma,
+    Dot,
+    Minus,
+    Plus,
+    Semicolon,
+    Slash,
+    Star,
+    Bang,
+    BangEqual,
+    Equal,
+    EqualEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Identifier(Name),
+    String(Name),
+    Number(f64),
+    And,
+    Break,
+    Catch,
+    Class,
+    Continue,
+    Else,
+    False,
+    Finally,
+    Fun,
+    For,
+    If,
+    Import,
+    Nil,
+    Or,
+    Print,
+    Return,
+    Super,
+    This,
+    Throw,
+    True,
+    Try,
+    Var,
+    While,
+    Eof,
+}
+
+/// A few names, so declarations and references line up. `test` and `from`
+/// are only special in some places.
+#[derive(Arbitrary, Debug)]
+enum Name {
+    A,
+    B,
+    Test,
+    From,
+}
+
+impl Name {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Name::A => "a",
+            Name::B => "b",
+            Name::Test => "test",
+            Name::From => "from",
+        }
+    }
+}
+
+impl Kind {
+    fn token_kind(&self) -> TokenKind {
+        match self {
+            Kind::LParen => TokenKind::LParen,
+            Kind::RParen => TokenKind::RParen,
+            Kind::LBrace => TokenKind::LBrace,
+            Kind::RBrace => TokenKind::RBrace,
+            Kind::Comma => TokenKind::Comma,
+            Kind::Dot => TokenKind::Dot,
+            Kind::Minus => TokenKind::Minus,
+            Kind::Plus => TokenKind::Plus,
+            Kind::Semicolon => TokenKind::Semicolon,
+            Kind::Slash => TokenKind::Slash,
+            Kind::Star => TokenKind::Star,
+            Kind::Bang => TokenKind::Bang,
+            Kind::BangEqual => TokenKind::BangEqual,
+            Kind::Equal => TokenKind::Equal,
+            Kind::EqualEqual => TokenKind::EqualEqual,
+            Kind::Greater => TokenKind::Greater,
+            Kind::GreaterEqual => TokenKind::GreaterEqual,
+            Kind::Less => TokenKind::Less,
+            Kind::LessEqual => TokenKind::LessEqual,
+            Kind::Identifier(name) => TokenKind::Identifier(name.as_str().to_string()),
+            Kind::String(name) => TokenKind::String(name.as_str().to_string()),
+            Kind::Number(n) => TokenKind::Number(*n),
+            Kind::And => TokenKind::And,
+            Kind::Break => TokenKind::Break,
+            Kind::Catch => TokenKind::Catch,
+            Kind::Class => TokenKind::Class,
+            Kind::Continue => TokenKind::Continue,
+            Kind::Else => TokenKind::Else,
+            Kind::False => TokenKind::False,
+            Kind::Finally => TokenKind::Finally,
+            Kind::Fun => TokenKind::Fun,
+            Kind::For => TokenKind::For,
+            Kind::If => TokenKind::If,
+            Kind::Import => TokenKind::Import,
+            Kind::Nil => TokenKind::Nil,
+            Kind::Or => TokenKind::Or,
+            Kind::Print => TokenKind::Print,
+            Kind::Return => TokenKind::Return,
+            Kind::Super => TokenKind::Super,
+            Kind::This => TokenKind::This,
+            Kind::Throw => TokenKind::Throw,
+            Kind::True => TokenKind::True,
+            Kind::Try => TokenKind::Try,
+            Kind::Var => TokenKind::Var,
+            Kind::While => TokenKind::While,
+            Kind::Eof => TokenKind::Eof,
+        }
+    }
+}
+
+fuzz_target!(|kinds: Vec<Kind>| {
+    let file = SourceMap::new().add("fuzz", "");
+    let tokens = kinds
+        .iter()
+        .enumerate()
+        .map(|(i, kind)| {
+            let kind = kind.token_kind();
+            Token {
+                lexeme: kind.to_string(),
+                kind,
+                location: SrcLocation::new(1, i + 1, file),
+            }
+        })
+        .collect();
+
+    let _ = Parser::new(tokens).parse();
+});
@@ -0,0 +1,15 @@
+//! Scans arbitrary bytes, which should only ever fail with an error.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rutox::scanner::{Scanner, SourceMap};
+
+fuzz_target!(|data: &[u8]| {
+    // Scripts are read as UTF-8 text, so invalid sequences never reach the
+    // scanner. Replacing them keeps the rest of the input interesting.
+    let source = String::from_utf8_lossy(data);
+    let file = SourceMap::new().add("fuzz", &source);
+
+    let _ = Scanner::new(source.into_owned(), file).scan_tokens();
+});
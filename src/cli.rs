@@ -0,0 +1,239 @@
+use super::color::ColorChoice;
+use super::error_format::ErrorFormat;
+use super::parser::ast_printer::AstFormat;
+use super::vm::Backend;
+
+pub const USAGE: &str = "Usage: rutox [run] [options] [script | -e code]
+       rutox explain <code>
+       rutox ast [--format=sexpr|json] [script | -e code]
+       rutox fmt [--check] [script | -e code]
+       rutox lint [--config=path] [script | -e code]
+       rutox lsp
+       rutox debug [--dap | script | -e code]
+       rutox profile [--folded=path] [script | -e code]
+       rutox test [dir | script | -e code]
+
+Commands:
+  run                              Run the script, or start a REPL without
+                                   one. This is the default.
+  explain <code>                   Describe an error code, e.g. `E0201`.
+  ast                              Print the parse tree instead of running it,
+                                   as S-expressions (the default) or JSON.
+  fmt                              Format the script in place, or print the
+                                   formatted snippet. With `--check`, only
+                                   report whether it is formatted.
+  lint                             Report likely mistakes without running the
+                                   script. Rules are configured in
+                                   `.rutoxlint.json`, or the file given with
+                                   `--config`, and silenced for a line with
+                                   `// rutox-ignore [rule, ...]`.
+  lsp                              Run a Language Server Protocol server over
+                                   stdin and stdout, for editors.
+  debug                            Run the script in a step debugger, with
+                                   breakpoints and watch expressions. Type
+                                   `help` when paused for its commands. With
+                                   `--dap`, serve the Debug Adapter Protocol
+                                   over stdin and stdout instead, for
+                                   editors, debugging the `program` given
+                                   when it is launched.
+  profile                          Run the script and report the time spent
+                                   on each line to stderr. Folded stacks for
+                                   flame graphs are written to `--folded`,
+                                   `<script>.folded` by default.
+  test                             Run the `*_test.lox` files in `dir`, the
+                                   current directory by default. Each
+                                   `test \"name\" { ... }` block in a file runs
+                                   in a fresh interpreter, after the rest of
+                                   the file; files without any run as a single
+                                   test. `assert(value)` and
+                                   `assertEqual(actual, expected)` are defined
+                                   in tests.
+
+Options:
+  --error-format=human|json|sarif  How errors are reported. `json` and `sarif`
+                                   are written to stderr.
+  --color=auto|always|never        Whether output is colored. `auto` colors it
+                                   unless NO_COLOR is set or stdout is not a
+                                   terminal.
+  -W error                         Treat warnings as errors.
+  --trace                          Log each statement and expression value
+                                   to stderr while the script runs.
+  --coverage[=path]                Record which lines, branches and functions
+                                   run, and write them as LCOV to `path`,
+                                   `lcov.info` by default.
+  --backend=tree|vm                Whether to walk the parse tree (the
+                                   default) or compile it to bytecode and run
+                                   that. Only `tree` supports `--trace` and
+                                   `--coverage`.";
+
+pub enum Command {
+    Run,
+    Explain(String),
+    Ast(AstFormat),
+    Fmt { check: bool },
+    Lint { config: Option<String> },
+    Lsp,
+    Debug { dap: bool },
+    Profile { folded: Option<String> },
+    Test,
+}
+
+pub enum Input {
+    Repl,
+    File(String),
+    Snippet(String),
+}
+
+pub struct Options {
+    pub command: Command,
+    pub input: Input,
+    pub error_format: ErrorFormat,
+    pub color: ColorChoice,
+    pub warnings_as_errors: bool,
+    pub trace: bool,
+    /// Where to write coverage, if it is recorded.
+    pub coverage: Option<String>,
+    pub backend: Backend,
+}
+
+impl Options {
+    pub fn parse(args: &[String]) -> Result<Options, String> {
+        let mut command = Command::Run;
+        let mut input = Input::Repl;
+        let mut error_format = ErrorFormat::Human;
+        let mut color = ColorChoice::Auto;
+        let mut warnings_as_errors = false;
+        let mut trace = false;
+        let mut coverage = None;
+        let mut backend = Backend::Tree;
+        let mut args = args.iter().peekable();
+
+        if args.next_if(|arg| *arg == "run").is_some() {
+            command = Command::Run;
+        } else if args.next_if(|arg| *arg == "explain").is_some() {
+            let code = args
+                .next()
+                .ok_or("Expected an error code after `explain`")?;
+            command = Command::Explain(code.clone());
+        } else if args.next_if(|arg| *arg == "ast").is_some() {
+            command = Command::Ast(AstFormat::Sexpr);
+        } else if args.next_if(|arg| *arg == "fmt").is_some() {
+            command = Command::Fmt { check: false };
+        } else if args.next_if(|arg| *arg == "lint").is_some() {
+            command = Command::Lint { config: None };
+        } else if args.next_if(|arg| *arg == "lsp").is_some() {
+            command = Command::Lsp;
+        } else if args.next_if(|arg| *arg == "debug").is_some() {
+            command = Command::Debug { dap: false };
+        } else if args.next_if(|arg| *arg == "profile").is_some() {
+            command = Command::Profile { folded: None };
+        } else if args.next_if(|arg| *arg == "test").is_some() {
+            command = Command::Test;
+        }
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-e" => {
+                    let code = args.next().ok_or("Expected code after `-e`")?;
+                    input = Self::set_input(input, Input::Snippet(code.clone()))?;
+                }
+                "-W" => match args.next().map(String::as_str) {
+                    Some("error") => warnings_as_errors = true,
+                    _ => return Err("Expected `error` after `-W`".to_string()),
+                },
+                flag if flag.starts_with("--error-format=") => {
+                    error_format = flag["--error-format=".len()..].parse()?;
+                }
+                flag if flag.starts_with("--color=") => {
+                    color = flag["--color=".len()..].parse()?;
+                }
+                "--trace" => match command {
+                    Command::Run => trace = true,
+                    _ => return Err("Unknown option `--trace`".to_string()),
+                },
+                flag if flag == "--coverage" || flag.starts_with("--coverage=") => match command {
+                    Command::Run => {
+                        let path = flag.strip_prefix("--coverage=").unwrap_or("lcov.info");
+                        coverage = Some(path.to_string());
+                    }
+                    _ => return Err(format!("Unknown option `{flag}`")),
+                },
+                flag if flag.starts_with("--backend=") => match command {
+                    Command::Run => backend = flag["--backend=".len()..].parse()?,
+                    _ => return Err(format!("Unknown option `{flag}`")),
+                },
+                "--dap" => match &mut command {
+                    Command::Debug { dap } => *dap = true,
+                    _ => return Err("Unknown option `--dap`".to_string()),
+                },
+                "--check" => match &mut command {
+                    Command::Fmt { check } => *check = true,
+                    _ => return Err("Unknown option `--check`".to_string()),
+                },
+                flag if flag.starts_with("--config=") => match &mut command {
+                    Command::Lint { config } => {
+                        *config = Some(flag["--config=".len()..].to_string())
+                    }
+                    _ => return Err(format!("Unknown option `{flag}`")),
+                },
+                flag if flag.starts_with("--folded=") => match &mut command {
+                    Command::Profile { folded } => {
+                        *folded = Some(flag["--folded=".len()..].to_string())
+                    }
+                    _ => return Err(format!("Unknown option `{flag}`")),
+                },
+                flag if flag.starts_with("--format=") => match &mut command {
+                    Command::Ast(format) => *format = flag["--format=".len()..].parse()?,
+                    _ => return Err(format!("Unknown option `{flag}`")),
+                },
+                flag if flag.starts_with('-') => return Err(format!("Unknown option `{flag}`")),
+                path if !matches!(command, Command::Explain(_) | Command::Lsp) => {
+                    input = Self::set_input(input, Input::File(path.to_string()))?
+                }
+                arg => return Err(format!("Unexpected argument `{arg}`")),
+            }
+        }
+
+        if coverage.is_some() {
+            if trace {
+                return Err("`--coverage` can't be combined with `--trace`".to_string());
+            }
+            if let Input::Repl = input {
+                return Err("Expected a script or `-e` snippet to record coverage for".to_string());
+            }
+        }
+
+        if let (Command::Debug { dap: true }, Input::File(_) | Input::Snippet(_)) =
+            (&command, &input)
+        {
+            return Err("`--dap` debugs the `program` it is launched with instead".to_string());
+        }
+
+        if backend == Backend::Vm {
+            if trace {
+                return Err("`--backend=vm` can't be combined with `--trace`".to_string());
+            }
+            if coverage.is_some() {
+                return Err("`--backend=vm` can't be combined with `--coverage`".to_string());
+            }
+        }
+
+        Ok(Options {
+            command,
+            input,
+            error_format,
+            color,
+            warnings_as_errors,
+            trace,
+            coverage,
+            backend,
+        })
+    }
+
+    fn set_input(current: Input, new: Input) -> Result<Input, String> {
+        match current {
+            Input::Repl => Ok(new),
+            _ => Err("Expected a single script or `-e` snippet".to_string()),
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use std::io::IsTerminal;
+use std::str::FromStr;
+
+/// Whether output is colored, chosen with `--color`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorChoice {
+    /// Color unless `NO_COLOR` is set or stdout is not a terminal.
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(choice: &str) -> Result<Self, Self::Err> {
+        match choice {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            other => Err(format!(
+                "Unknown color choice `{other}`, expected `auto`, `always` or `never`"
+            )),
+        }
+    }
+}
+
+impl ColorChoice {
+    /// Turns styling on or off for everything printed with `colored`. This is
+    /// the only place that decides it, so the rest of rutox can style output
+    /// freely.
+    pub fn apply(&self) {
+        colored::control::set_override(self.enabled());
+    }
+
+    fn enabled(&self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+
+                !no_color && std::io::stdout().is_terminal()
+            }
+        }
+    }
+}
@@ -0,0 +1,506 @@
+use crate::interpreter::{Hook, Interpreter, LoxFunction, LoxObj};
+use crate::parser::{
+    ast::{
+        BinaryData, CallData, Expr, FunctionData, ImportData, LiteralData, LogicalOp, Stmt,
+        TestData, TryData, UnaryData,
+    },
+    visitors::{ExprVisitor, StmtVisitor},
+};
+use crate::rutox_error::RutoxError;
+use crate::scanner::{token::Token, FileId, SourceMap, SrcLocation};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+/// Records which lines, branches and functions run, for `--coverage`.
+/// Clones share their records, so one can be given to the interpreter and
+/// the other kept to write the report.
+#[derive(Clone, Default)]
+pub struct Coverage {
+    data: Rc<RefCell<CoverageData>>,
+}
+
+#[derive(Default)]
+struct CoverageData {
+    /// In the order they were loaded.
+    files: Vec<FileCoverage>,
+    /// The first statement on each line, which counts how often the line
+    /// runs, by its location.
+    line_starts: HashMap<SrcLocation, usize>,
+    /// `if` conditions and the left side of `and`/`or`, by their span.
+    branch_points: HashMap<SrcLocation, BranchPoint>,
+    /// Where each function is declared, by the location of its declaration.
+    functions: HashMap<SrcLocation, (usize, usize)>,
+}
+
+struct FileCoverage {
+    file: FileId,
+    /// How often each line with a statement ran.
+    lines: BTreeMap<usize, usize>,
+    branches: Vec<Branches>,
+    functions: Vec<Function>,
+}
+
+/// The two ways an `if` or a logical operator can go: the `then` and
+/// `else` branches, or short-circuiting and evaluating the right side.
+struct Branches {
+    line: usize,
+    taken: [usize; 2],
+}
+
+struct Function {
+    line: usize,
+    name: String,
+    calls: usize,
+}
+
+#[derive(Clone, Copy)]
+struct BranchPoint {
+    file: usize,
+    branches: usize,
+    kind: BranchKind,
+}
+
+#[derive(Clone, Copy)]
+enum BranchKind {
+    If,
+    Or,
+    And,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The records in LCOV's tracefile format.
+    pub fn lcov(&self, source_map: &SourceMap) -> String {
+        let data = self.data.borrow();
+        let mut lcov = String::new();
+
+        for file in &data.files {
+            lcov.push_str(&format!("TN:\nSF:{}\n", source_map.get(file.file).path));
+
+            for function in &file.functions {
+                lcov.push_str(&format!("FN:{},{}\n", function.line, function.name));
+            }
+            for function in &file.functions {
+                lcov.push_str(&format!("FNDA:{},{}\n", function.calls, function.name));
+            }
+            let functions_hit = file.functions.iter().filter(|f| f.calls > 0).count();
+            lcov.push_str(&format!(
+                "FNF:{}\nFNH:{functions_hit}\n",
+                file.functions.len()
+            ));
+
+            for (block, branches) in file.branches.iter().enumerate() {
+                let reached = branches.taken.iter().any(|taken| *taken > 0);
+                for (branch, taken) in branches.taken.iter().enumerate() {
+                    let taken = match reached {
+                        true => taken.to_string(),
+                        false => "-".to_string(),
+                    };
+                    lcov.push_str(&format!(
+                        "BRDA:{},{block},{branch},{taken}\n",
+                        branches.line
+                    ));
+                }
+            }
+            let (branches_found, branches_hit) = file.branch_counts();
+            lcov.push_str(&format!("BRF:{branches_found}\nBRH:{branches_hit}\n"));
+
+            for (line, count) in &file.lines {
+                lcov.push_str(&format!("DA:{line},{count}\n"));
+            }
+            let lines_hit = file.lines.values().filter(|count| **count > 0).count();
+            lcov.push_str(&format!("LF:{}\nLH:{lines_hit}\n", file.lines.len()));
+
+            lcov.push_str("end_of_record\n");
+        }
+
+        lcov
+    }
+
+    /// One line of totals, e.g. `Lines: 9/10 (90.0%), ...`.
+    pub fn summary(&self) -> String {
+        let data = self.data.borrow();
+        let mut lines = (0, 0);
+        let mut branches = (0, 0);
+        let mut functions = (0, 0);
+
+        for file in &data.files {
+            lines.0 += file.lines.values().filter(|count| **count > 0).count();
+            lines.1 += file.lines.len();
+            let (found, hit) = file.branch_counts();
+            branches.0 += hit;
+            branches.1 += found;
+            functions.0 += file.functions.iter().filter(|f| f.calls > 0).count();
+            functions.1 += file.functions.len();
+        }
+
+        let ratio = |(hit, found): (usize, usize)| match found {
+            0 => format!("{hit}/{found}"),
+            _ => format!("{hit}/{found} ({:.1}%)", 100.0 * hit as f64 / found as f64),
+        };
+
+        format!(
+            "Lines: {}, branches: {}, functions: {}",
+            ratio(lines),
+            ratio(branches),
+            ratio(functions)
+        )
+    }
+}
+
+impl FileCoverage {
+    fn branch_counts(&self) -> (usize, usize) {
+        let found = self.branches.len() * 2;
+        let hit = self
+            .branches
+            .iter()
+            .flat_map(|branches| branches.taken)
+            .filter(|taken| *taken > 0)
+            .count();
+
+        (found, hit)
+    }
+}
+
+impl Hook for Coverage {
+    fn program_loaded(&mut self, _interpreter: &Interpreter, stmts: &[Stmt]) {
+        let Some(file) = stmts.first().map(|stmt| stmt.location().file) else {
+            return;
+        };
+        let mut data = self.data.borrow_mut();
+        if data.files.iter().any(|covered| covered.file == file) {
+            return;
+        }
+
+        data.files.push(FileCoverage {
+            file,
+            lines: BTreeMap::new(),
+            branches: vec![],
+            functions: vec![],
+        });
+        let file = data.files.len() - 1;
+        let mut collector = Collector {
+            data: &mut data,
+            file,
+            line_starts: HashMap::new(),
+        };
+        collector.stmts(stmts);
+
+        let line_starts = collector.line_starts;
+        data.line_starts
+            .extend(line_starts.into_values().map(|start| (start, file)));
+    }
+
+    fn before_stmt(
+        &mut self,
+        _interpreter: &mut Interpreter,
+        stmt: &Stmt,
+    ) -> Result<(), RutoxError> {
+        let mut data = self.data.borrow_mut();
+        let location = stmt.location();
+
+        if let Some(&file) = data.line_starts.get(&location) {
+            *data.files[file].lines.entry(location.line).or_default() += 1;
+        }
+
+        Ok(())
+    }
+
+    fn after_expr(&mut self, interpreter: &Interpreter, expr: &Expr, value: &LoxObj) {
+        let mut data = self.data.borrow_mut();
+        let Some(&point) = data.branch_points.get(&expr.span()) else {
+            return;
+        };
+
+        let truthy = interpreter.is_truthy(value);
+        // Branch 0 is the `then` branch, or the operator short-circuiting
+        let branch = match point.kind {
+            BranchKind::If | BranchKind::Or => usize::from(!truthy),
+            BranchKind::And => usize::from(truthy),
+        };
+        data.files[point.file].branches[point.branches].taken[branch] += 1;
+    }
+
+    fn enter_function(&mut self, _interpreter: &Interpreter, function: &LoxFunction) {
+        let mut data = self.data.borrow_mut();
+
+        if let Some(&(file, index)) = data.functions.get(&function.declaration.location) {
+            data.files[file].functions[index].calls += 1;
+        }
+    }
+}
+
+/// Finds every line, branch and function of a program before it runs, so
+/// those that never do are reported too.
+struct Collector<'a> {
+    data: &'a mut CoverageData,
+    /// The index of the file being collected in `data.files`.
+    file: usize,
+    /// The first statement on each line of the file.
+    line_starts: HashMap<usize, SrcLocation>,
+}
+
+impl Collector<'_> {
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            // Nothing is fatal here, so the result is always `Ok`
+            let _ = self.visit_stmt(stmt);
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        let _ = self.visit_expr(expr);
+    }
+
+    fn line(&mut self, location: &SrcLocation) {
+        self.data.files[self.file].lines.insert(location.line, 0);
+
+        let start = self
+            .line_starts
+            .entry(location.line)
+            .or_insert_with(|| location.clone());
+        if location.col < start.col {
+            *start = location.clone();
+        }
+    }
+
+    fn branches(&mut self, span: SrcLocation, line: usize, kind: BranchKind) {
+        let file = &mut self.data.files[self.file];
+        file.branches.push(Branches {
+            line,
+            taken: [0, 0],
+        });
+        let point = BranchPoint {
+            file: self.file,
+            branches: file.branches.len() - 1,
+            kind,
+        };
+        self.data.branch_points.insert(span, point);
+    }
+
+    fn function(&mut self, function: &FunctionData) {
+        let name = match &function.name {
+            Some(name) => name.lexeme.clone(),
+            None => format!(
+                "<lambda>:{}:{}",
+                function.location.line, function.location.col
+            ),
+        };
+        let file = &mut self.data.files[self.file];
+        file.functions.push(Function {
+            line: function.location.line,
+            name,
+            calls: 0,
+        });
+        self.data.functions.insert(
+            function.location.clone(),
+            (self.file, file.functions.len() - 1),
+        );
+
+        self.stmts(&function.body);
+    }
+}
+
+impl StmtVisitor<()> for Collector<'_> {
+    fn visit_stmt(&mut self, stmt: &Stmt) -> Result<(), RutoxError> {
+        // Blocks are located at their `}`, which is not code that runs
+        if !matches!(stmt, Stmt::Block(_, _)) {
+            self.line(&stmt.location());
+        }
+
+        self.walk_stmt(stmt)
+    }
+
+    fn visit_print_stmt(&mut self, expr: &Expr, _location: &SrcLocation) -> Result<(), RutoxError> {
+        self.visit_expr(expr)
+    }
+
+    fn visit_expr_stmt(&mut self, expr: &Expr, _location: &SrcLocation) -> Result<(), RutoxError> {
+        self.visit_expr(expr)
+    }
+
+    fn visit_var_stmt(
+        &mut self,
+        _name: &Token,
+        initializer: &Option<Expr>,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        match initializer {
+            Some(initializer) => self.visit_expr(initializer),
+            None => Ok(()),
+        }
+    }
+
+    fn visit_block_stmt(
+        &mut self,
+        stmts: &[Stmt],
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        self.stmts(stmts);
+
+        Ok(())
+    }
+
+    fn visit_if_stmt(
+        &mut self,
+        cond: &Expr,
+        then_branch: &Stmt,
+        else_branch: &Option<Box<Stmt>>,
+        location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        self.branches(cond.span(), location.line, BranchKind::If);
+        self.expr(cond);
+        self.visit_stmt(then_branch)?;
+        if let Some(else_branch) = else_branch {
+            self.visit_stmt(else_branch)?;
+        }
+
+        Ok(())
+    }
+
+    fn visit_while_stmt(
+        &mut self,
+        cond: &Expr,
+        body: &Stmt,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        self.expr(cond);
+        self.visit_stmt(body)
+    }
+
+    fn visit_break_stmt(&mut self, _location: &SrcLocation) -> Result<(), RutoxError> {
+        Ok(())
+    }
+
+    fn visit_continue_stmt(&mut self, _location: &SrcLocation) -> Result<(), RutoxError> {
+        Ok(())
+    }
+
+    fn visit_function_stmt(&mut self, function: &Rc<FunctionData>) -> Result<(), RutoxError> {
+        self.function(function);
+
+        Ok(())
+    }
+
+    fn visit_return_stmt(
+        &mut self,
+        value: &Option<Expr>,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        match value {
+            Some(value) => self.visit_expr(value),
+            None => Ok(()),
+        }
+    }
+
+    fn visit_throw_stmt(
+        &mut self,
+        value: &Expr,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        self.visit_expr(value)
+    }
+
+    fn visit_try_stmt(&mut self, try_data: &TryData) -> Result<(), RutoxError> {
+        self.stmts(&try_data.body);
+        if let Some(catch) = &try_data.catch {
+            self.stmts(&catch.body);
+        }
+        if let Some(finally) = &try_data.finally {
+            self.stmts(finally);
+        }
+
+        Ok(())
+    }
+
+    fn visit_import_stmt(&mut self, _import: &ImportData) -> Result<(), RutoxError> {
+        Ok(())
+    }
+
+    fn visit_test_stmt(&mut self, test: &TestData) -> Result<(), RutoxError> {
+        self.stmts(&test.body);
+
+        Ok(())
+    }
+}
+
+impl ExprVisitor<()> for Collector<'_> {
+    fn visit_literal_expr(&mut self, _literal: &LiteralData) -> Result<(), RutoxError> {
+        Ok(())
+    }
+
+    fn visit_unary_expr(&mut self, unary: &UnaryData) -> Result<(), RutoxError> {
+        self.visit_expr(&unary.expr)
+    }
+
+    fn visit_binary_expr(&mut self, binary: &BinaryData) -> Result<(), RutoxError> {
+        self.visit_expr(&binary.left)?;
+        self.visit_expr(&binary.right)
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &Expr) -> Result<(), RutoxError> {
+        self.visit_expr(expr)
+    }
+
+    fn visit_variable_expr(
+        &mut self,
+        _name: &Token,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        Ok(())
+    }
+
+    fn visit_assign_expr(
+        &mut self,
+        _name: &Token,
+        value: &Expr,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        self.visit_expr(value)
+    }
+
+    fn visit_logical_expr(
+        &mut self,
+        left: &Expr,
+        op: &LogicalOp,
+        right: &Expr,
+        location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        let kind = match op {
+            LogicalOp::Or(_) => BranchKind::Or,
+            LogicalOp::And(_) => BranchKind::And,
+        };
+        self.branches(left.span(), location.line, kind);
+
+        self.visit_expr(left)?;
+        self.visit_expr(right)
+    }
+
+    fn visit_call_expr(&mut self, call: &CallData) -> Result<(), RutoxError> {
+        self.visit_expr(&call.callee)?;
+        for arg in &call.args {
+            self.visit_expr(arg)?;
+        }
+
+        Ok(())
+    }
+
+    fn visit_lambda_expr(&mut self, function: &Rc<FunctionData>) -> Result<(), RutoxError> {
+        self.function(function);
+
+        Ok(())
+    }
+
+    fn visit_get_expr(
+        &mut self,
+        object: &Expr,
+        _name: &Token,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        self.visit_expr(object)
+    }
+}
@@ -0,0 +1,473 @@
+use super::{evaluate, Mode, Pauses};
+use crate::error_format::ErrorFormat;
+use crate::exitcodes;
+use crate::interpreter::{Hook, Interpreter, LoxFunction};
+use crate::lsp::transport;
+use crate::parser::{ast::Stmt, Parser};
+use crate::rutox_error::RutoxError;
+use crate::scanner::{Scanner, SourceMap, SrcLocation};
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, StdinLock, StdoutLock};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::rc::Rc;
+
+/// The program runs on a single thread, which is all clients are told of.
+const THREAD_ID: u64 = 1;
+/// The `variablesReference`s of the scopes of the innermost frame, the only
+/// one whose variables can be shown.
+const LOCALS: u64 = 1;
+const GLOBALS: u64 = 2;
+
+/// Serves the Debug Adapter Protocol over stdin and stdout until the client
+/// disconnects. The script to debug is the `program` of the `launch`
+/// request, and lines and columns count from 1. Returns the exit code.
+pub fn run() -> io::Result<i32> {
+    let client = Rc::new(RefCell::new(Client::new()));
+    let Some(launch) = configure(&mut client.borrow_mut())? else {
+        return Ok(0);
+    };
+
+    let exit_code = debug(&launch, &client)?;
+    {
+        let mut client = client.borrow_mut();
+        client.event("exited", json!({ "exitCode": exit_code }))?;
+        client.event("terminated", json!({}))?;
+    }
+
+    // The client may still ask for threads before it disconnects
+    let mut client = client.borrow_mut();
+    while let Some(request) = client.read()? {
+        match request["command"].as_str().unwrap_or_default() {
+            "disconnect" | "terminate" => {
+                client.respond(&request, Ok(Value::Null))?;
+                return Ok(0);
+            }
+            "threads" => client.respond(&request, Ok(json!({ "threads": [] })))?,
+            _ => client.respond(&request, Err("The program has ended".to_string()))?,
+        }
+    }
+
+    Ok(0)
+}
+
+/// What the client asked to debug before `configurationDone`.
+#[derive(Default)]
+struct Launch {
+    program: Option<String>,
+    stop_on_entry: bool,
+    /// Breakpoint lines by the canonical path of their file.
+    breakpoints: Vec<(PathBuf, BTreeSet<usize>)>,
+    configured: bool,
+}
+
+/// Answers requests until the client is done configuring the launch,
+/// returning `None` if it disconnected instead.
+fn configure(client: &mut Client) -> io::Result<Option<Launch>> {
+    let mut launch = Launch::default();
+
+    while let Some(request) = client.read()? {
+        let arguments = &request["arguments"];
+        let result = match request["command"].as_str().unwrap_or_default() {
+            "initialize" => {
+                client.respond(
+                    &request,
+                    Ok(json!({
+                        "supportsConfigurationDoneRequest": true,
+                        "supportsEvaluateForHovers": true,
+                        "supportsTerminateRequest": true,
+                    })),
+                )?;
+                client.event("initialized", json!({}))?;
+                continue;
+            }
+            "launch" => match arguments["program"].as_str() {
+                Some(program) => {
+                    launch.program = Some(program.to_string());
+                    launch.stop_on_entry = arguments["stopOnEntry"] == true;
+                    Ok(Value::Null)
+                }
+                None => Err("Expected the script to debug as `program`".to_string()),
+            },
+            "setBreakpoints" => {
+                let (path, lines) = breakpoint_lines(arguments);
+                let verified = path.is_some();
+                if let Some(path) = path {
+                    launch.breakpoints.retain(|(other, _)| *other != path);
+                    launch.breakpoints.push((path, lines.clone()));
+                }
+                Ok(breakpoints_body(&lines, verified))
+            }
+            "configurationDone" => {
+                launch.configured = true;
+                Ok(Value::Null)
+            }
+            "threads" => Ok(threads()),
+            "disconnect" | "terminate" => {
+                client.respond(&request, Ok(Value::Null))?;
+                return Ok(None);
+            }
+            command => Err(format!("Unsupported request `{command}`")),
+        };
+        client.respond(&request, result)?;
+
+        if launch.configured && launch.program.is_some() {
+            return Ok(Some(launch));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Runs the program under the adapter, returning the code rutox would exit
+/// with.
+fn debug(launch: &Launch, client: &Rc<RefCell<Client>>) -> io::Result<i32> {
+    let program = launch.program.as_deref().unwrap_or_default();
+    let code = match fs::read_to_string(program) {
+        Ok(code) => code,
+        Err(err) => {
+            let message = format!("Error while opening {program}: {err}\n");
+            client.borrow_mut().output("stderr", &message)?;
+            return Ok(exitcodes::IOERR);
+        }
+    };
+
+    let source_map = Rc::new(RefCell::new(SourceMap::new()));
+    let file = source_map.borrow_mut().add(program, &code);
+    let mut pauses = Pauses::new(
+        file,
+        match launch.stop_on_entry {
+            true => Mode::StepIn,
+            false => Mode::Continue,
+        },
+    );
+    let program_path = canonical(program);
+    if let Some((_, lines)) = launch
+        .breakpoints
+        .iter()
+        .find(|(path, _)| *path == program_path)
+    {
+        pauses.breakpoints = lines.clone();
+    }
+
+    let mut interpreter = Interpreter::for_file(program, source_map.clone());
+    let output = client.clone();
+    interpreter.set_output(Box::new(move |text| {
+        // The client is gone if this fails, which the adapter finds out
+        // the next time it reads
+        let _ = output.borrow_mut().output("stdout", text);
+    }));
+    interpreter.set_hook(Box::new(Adapter {
+        client: client.clone(),
+        pauses,
+        program_path,
+        entry: launch.stop_on_entry,
+        calls: vec![],
+        current: None,
+        detached: false,
+    }));
+
+    let result = Scanner::new(code, file)
+        .scan_tokens()
+        .and_then(|tokens| Parser::new(tokens).parse())
+        .and_then(|stmts| interpreter.interpret(stmts));
+
+    match result {
+        Ok(()) => Ok(0),
+        Err(error) => {
+            let message = ErrorFormat::Human.render(&error, &source_map.borrow());
+            client
+                .borrow_mut()
+                .output("stderr", &format!("{message}\n"))?;
+            Ok(exitcodes::DATAERR)
+        }
+    }
+}
+
+/// Pauses the program where the client asks to, and answers its requests
+/// while it is paused.
+struct Adapter {
+    client: Rc<RefCell<Client>>,
+    pauses: Pauses,
+    program_path: PathBuf,
+    /// Whether the next pause is the one `stopOnEntry` asked for.
+    entry: bool,
+    /// The functions being run, outermost first, with the location of the
+    /// statement that called each.
+    calls: Vec<(String, SrcLocation)>,
+    /// The statement about to run, which is where the innermost frame is.
+    current: Option<SrcLocation>,
+    /// Set when the client is gone. The program then runs to the end.
+    detached: bool,
+}
+
+impl Adapter {
+    fn pause(&mut self, interpreter: &mut Interpreter, stmt: &Stmt) -> io::Result<()> {
+        let reason = match (self.entry, self.pauses.is_breakpoint(stmt)) {
+            (_, true) => "breakpoint",
+            (true, false) => "entry",
+            (false, false) => "step",
+        };
+        self.entry = false;
+        self.client.borrow_mut().event(
+            "stopped",
+            json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+        )?;
+
+        loop {
+            let Some(request) = self.client.borrow_mut().read()? else {
+                self.detached = true;
+                return Ok(());
+            };
+            let arguments = &request["arguments"];
+            let depth = interpreter.call_depth();
+
+            let resume = match request["command"].as_str().unwrap_or_default() {
+                "continue" => Some(Mode::Continue),
+                "next" => Some(Mode::StepOver(depth)),
+                "stepIn" => Some(Mode::StepIn),
+                "stepOut" => Some(Mode::StepOut(depth)),
+                "disconnect" | "terminate" => {
+                    self.client
+                        .borrow_mut()
+                        .respond(&request, Ok(Value::Null))?;
+                    process::exit(0);
+                }
+                _ => None,
+            };
+            if let Some(mode) = resume {
+                self.pauses.mode = mode;
+                let body = json!({ "allThreadsContinued": true });
+                return self.client.borrow_mut().respond(&request, Ok(body));
+            }
+
+            let result = match request["command"].as_str().unwrap_or_default() {
+                "threads" => Ok(threads()),
+                "pause" => Ok(Value::Null),
+                "stackTrace" => Ok(self.stack_trace(interpreter)),
+                "scopes" => Ok(Self::scopes(arguments)),
+                "variables" => Ok(Self::variables(interpreter, arguments)),
+                "evaluate" => {
+                    let expression = arguments["expression"].as_str().unwrap_or_default();
+                    evaluate(interpreter, expression)
+                        .map(
+                            |value| json!({ "result": value.to_string(), "variablesReference": 0 }),
+                        )
+                        .map_err(|error| error.message())
+                }
+                "setBreakpoints" => {
+                    let (path, lines) = breakpoint_lines(arguments);
+                    let verified = path.as_ref() == Some(&self.program_path);
+                    if verified {
+                        self.pauses.breakpoints = lines.clone();
+                    }
+                    Ok(breakpoints_body(&lines, verified))
+                }
+                command => Err(format!("Unsupported request `{command}`")),
+            };
+            self.client.borrow_mut().respond(&request, result)?;
+        }
+    }
+
+    /// The frames of the calls being run, innermost first.
+    fn stack_trace(&self, interpreter: &Interpreter) -> Value {
+        let names = self
+            .calls
+            .iter()
+            .rev()
+            .map(|(name, _)| name.as_str())
+            .chain(["<script>"]);
+        let locations = self
+            .current
+            .iter()
+            .chain(self.calls.iter().rev().map(|(_, location)| location));
+
+        let source_map = interpreter.source_map();
+        let frames: Vec<Value> = names
+            .zip(locations)
+            .enumerate()
+            .map(|(id, (name, location))| {
+                let path = &source_map.get(location.file).path;
+                json!({
+                    "id": id,
+                    "name": name,
+                    "source": {
+                        "name": Path::new(path).file_name().map(|name| name.to_string_lossy()),
+                        "path": canonical(path),
+                    },
+                    "line": location.line,
+                    "column": location.col,
+                })
+            })
+            .collect();
+
+        json!({ "stackFrames": frames, "totalFrames": frames.len() })
+    }
+
+    fn scopes(arguments: &Value) -> Value {
+        if arguments["frameId"] != 0 {
+            return json!({ "scopes": [] });
+        }
+
+        json!({
+            "scopes": [
+                { "name": "Locals", "variablesReference": LOCALS, "expensive": false },
+                { "name": "Globals", "variablesReference": GLOBALS, "expensive": false },
+            ],
+        })
+    }
+
+    /// The variables in scope, innermost first and leaving out the ones
+    /// shadowed, or the globals.
+    fn variables(interpreter: &Interpreter, arguments: &Value) -> Value {
+        let globals = arguments["variablesReference"] == GLOBALS;
+        let mut variables: Vec<Value> = vec![];
+        let mut env = Some(interpreter.env());
+
+        while let Some(scope) = env {
+            let scope = scope.borrow();
+            let is_global = scope.enclosing().is_none();
+            if is_global == globals {
+                for (name, value) in scope.values() {
+                    if variables.iter().any(|variable| variable["name"] == name) {
+                        continue;
+                    }
+                    variables.push(json!({
+                        "name": name,
+                        "value": value.to_string(),
+                        "type": value.type_name(),
+                        "variablesReference": 0,
+                    }));
+                }
+            }
+
+            env = scope.enclosing();
+        }
+
+        json!({ "variables": variables })
+    }
+}
+
+impl Hook for Adapter {
+    fn before_stmt(
+        &mut self,
+        interpreter: &mut Interpreter,
+        stmt: &Stmt,
+    ) -> Result<(), RutoxError> {
+        if self.detached {
+            return Ok(());
+        }
+        if !matches!(stmt, Stmt::Block(_, _)) {
+            self.current = Some(stmt.location());
+        }
+
+        if self.pauses.before(stmt, interpreter.call_depth())
+            && self.pause(interpreter, stmt).is_err()
+        {
+            self.detached = true;
+        }
+
+        Ok(())
+    }
+
+    fn enter_function(&mut self, _interpreter: &Interpreter, function: &LoxFunction) {
+        if let Some(current) = &self.current {
+            self.calls.push((function.to_string(), current.clone()));
+        }
+    }
+
+    fn exit_function(&mut self, _interpreter: &Interpreter, _function: &LoxFunction) {
+        self.calls.pop();
+    }
+}
+
+/// The connection to the editor, which the program's output is sent over
+/// too.
+struct Client {
+    input: StdinLock<'static>,
+    output: StdoutLock<'static>,
+    seq: u64,
+}
+
+impl Client {
+    fn new() -> Self {
+        Self {
+            input: io::stdin().lock(),
+            output: io::stdout().lock(),
+            seq: 0,
+        }
+    }
+
+    fn read(&mut self) -> io::Result<Option<Value>> {
+        transport::read_message(&mut self.input)
+    }
+
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+
+        transport::write_message(&mut self.output, &message)
+    }
+
+    fn respond(&mut self, request: &Value, result: Result<Value, String>) -> io::Result<()> {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(Value::Null) => {}
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+
+        self.send(response)
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    fn output(&mut self, category: &str, text: &str) -> io::Result<()> {
+        self.event("output", json!({ "category": category, "output": text }))
+    }
+}
+
+fn threads() -> Value {
+    json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] })
+}
+
+/// The canonical path and lines of a `setBreakpoints` request.
+fn breakpoint_lines(arguments: &Value) -> (Option<PathBuf>, BTreeSet<usize>) {
+    let path = arguments["source"]["path"].as_str().map(canonical);
+    let lines = arguments["breakpoints"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|breakpoint| breakpoint["line"].as_u64())
+        .map(|line| line as usize)
+        .collect();
+
+    (path, lines)
+}
+
+/// The reply to `setBreakpoints`. Breakpoints can only be set in the
+/// program, so the others are not verified.
+fn breakpoints_body(lines: &BTreeSet<usize>, verified: bool) -> Value {
+    let breakpoints: Vec<Value> = lines
+        .iter()
+        .map(|line| json!({ "verified": verified, "line": line }))
+        .collect();
+
+    json!({ "breakpoints": breakpoints })
+}
+
+fn canonical(path: &str) -> PathBuf {
+    Path::new(path)
+        .canonicalize()
+        .unwrap_or_else(|_| PathBuf::from(path))
+}
@@ -0,0 +1,286 @@
+mod dap;
+use crate::error_code::ErrorCode;
+use crate::error_format::ErrorFormat;
+use crate::interpreter::{Hook, Interpreter, LoxObj};
+use crate::parser::{ast::Stmt, visitors::ExprVisitor, Parser};
+use crate::rutox_error::RutoxError;
+use crate::scanner::{FileId, Scanner, SrcLocation};
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+use std::process;
+
+pub use dap::run as serve_dap;
+
+const HELP: &str = "Commands:
+  s, step              Run until the next line, entering function calls.
+  n, next              Run until the next line, stepping over function calls.
+  o, out               Run until the current function returns.
+  c, continue          Run until the next breakpoint.
+  b, break <line>      Pause whenever <line> is about to run.
+  d, delete <line>     Remove the breakpoint on <line>.
+  breakpoints          List the breakpoints.
+  l, locals            Show the variables in scope.
+  p, print <expr>      Evaluate <expr> where the program is paused.
+  w, watch <expr>      Evaluate <expr> every time the program pauses.
+  unwatch <n>          Stop watching the <n>th expression.
+  where                Show where the program is paused.
+  q, quit              Stop the program.";
+
+/// Pauses the program before the statements the user asked for, and
+/// reads commands from stdin while it is paused.
+pub struct Debugger {
+    pauses: Pauses,
+    watches: Vec<String>,
+    /// Set when stdin is closed. The program then runs to the end.
+    detached: bool,
+}
+
+/// How the program was last resumed.
+pub enum Mode {
+    Continue,
+    StepIn,
+    /// Pauses at a call depth of at most the one given.
+    StepOver(usize),
+    /// Pauses at a call depth below the one given.
+    StepOut(usize),
+}
+
+/// Decides which statements the program pauses before, from the
+/// breakpoints and how it was last resumed.
+pub struct Pauses {
+    /// The script being debugged. Breakpoints are lines in this file.
+    file: FileId,
+    pub breakpoints: BTreeSet<usize>,
+    pub mode: Mode,
+    /// The file, line and call depth of the line being run, so several
+    /// statements on one line only pause once.
+    line: Option<(FileId, usize, usize)>,
+    /// The columns of the statements run since that line was entered. One
+    /// running again starts the line over, e.g. in the next loop iteration.
+    run_on_line: Vec<usize>,
+}
+
+impl Pauses {
+    pub fn new(file: FileId, mode: Mode) -> Self {
+        Self {
+            file,
+            breakpoints: BTreeSet::new(),
+            mode,
+            line: None,
+            run_on_line: vec![],
+        }
+    }
+
+    /// Whether to pause before `stmt`, which runs at a call depth of `depth`.
+    pub fn before(&mut self, stmt: &Stmt, depth: usize) -> bool {
+        // A block pauses at its first statement instead
+        if matches!(stmt, Stmt::Block(_, _)) {
+            return false;
+        }
+
+        let location = stmt.location();
+        let here = (location.file, location.line, depth);
+        let new_line = self.line != Some(here) || self.run_on_line.contains(&location.col);
+        if new_line {
+            self.line = Some(here);
+            self.run_on_line.clear();
+        }
+        self.run_on_line.push(location.col);
+
+        let stepped_here = match self.mode {
+            Mode::StepIn => true,
+            Mode::StepOver(max_depth) => depth <= max_depth,
+            Mode::StepOut(max_depth) => depth < max_depth,
+            Mode::Continue => false,
+        };
+
+        new_line && (stepped_here || self.is_breakpoint(stmt))
+    }
+
+    pub fn is_breakpoint(&self, stmt: &Stmt) -> bool {
+        let location = stmt.location();
+
+        location.file == self.file && self.breakpoints.contains(&location.line)
+    }
+}
+
+impl Debugger {
+    pub fn new(file: FileId) -> Self {
+        Self {
+            pauses: Pauses::new(file, Mode::StepIn),
+            watches: vec![],
+            detached: false,
+        }
+    }
+
+    fn pause(&mut self, interpreter: &mut Interpreter, stmt: &Stmt) {
+        self.show_location(interpreter, stmt);
+        self.show_watches(interpreter);
+
+        loop {
+            print!("(rutox) ");
+            let _ = io::stdout().flush();
+
+            let mut line = String::new();
+            if io::stdin().read_line(&mut line).unwrap_or(0) == 0 {
+                println!();
+                self.detached = true;
+                return;
+            }
+
+            let line = line.trim();
+            let (command, arg) = line.split_once(' ').unwrap_or((line, ""));
+            if let Some(mode) = self.run_command(interpreter, stmt, command, arg.trim()) {
+                self.pauses.mode = mode;
+                return;
+            }
+        }
+    }
+
+    /// Runs a command, returning how to resume the program if it is one
+    /// that does.
+    fn run_command(
+        &mut self,
+        interpreter: &mut Interpreter,
+        stmt: &Stmt,
+        command: &str,
+        arg: &str,
+    ) -> Option<Mode> {
+        let depth = interpreter.call_depth();
+
+        match command {
+            "s" | "step" => return Some(Mode::StepIn),
+            "n" | "next" => return Some(Mode::StepOver(depth)),
+            "o" | "out" => return Some(Mode::StepOut(depth)),
+            "c" | "continue" => return Some(Mode::Continue),
+            "" => {}
+            "b" | "break" => match arg.parse::<usize>() {
+                Ok(line) if line > 0 => {
+                    self.pauses.breakpoints.insert(line);
+                    println!("Breakpoint on line {line}");
+                }
+                _ => println!("Expected a line number after `{command}`"),
+            },
+            "d" | "delete" => match arg.parse::<usize>() {
+                Ok(line) if self.pauses.breakpoints.remove(&line) => {
+                    println!("Removed the breakpoint on line {line}")
+                }
+                _ => println!("No breakpoint on line `{arg}`"),
+            },
+            "breakpoints" if self.pauses.breakpoints.is_empty() => println!("No breakpoints"),
+            "breakpoints" => {
+                for line in &self.pauses.breakpoints {
+                    println!("  line {line}");
+                }
+            }
+            "l" | "locals" => Self::show_locals(interpreter),
+            "p" | "print" if arg.is_empty() => println!("Expected an expression to print"),
+            "p" | "print" => match evaluate(interpreter, arg) {
+                Ok(value) => println!("{value}"),
+                Err(error) => Self::show_error(interpreter, &error),
+            },
+            "w" | "watch" if arg.is_empty() => println!("Expected an expression to watch"),
+            "w" | "watch" => {
+                self.watches.push(arg.to_string());
+                self.show_watches(interpreter);
+            }
+            "unwatch" => match arg.parse::<usize>() {
+                Ok(n) if (1..=self.watches.len()).contains(&n) => {
+                    let watch = self.watches.remove(n - 1);
+                    println!("Stopped watching `{watch}`");
+                }
+                _ => println!("No watch expression number `{arg}`"),
+            },
+            "where" => self.show_location(interpreter, stmt),
+            "q" | "quit" => process::exit(0),
+            "h" | "help" => println!("{HELP}"),
+            other => println!("Unknown command `{other}`, type `help` for a list"),
+        }
+
+        None
+    }
+
+    fn show_location(&self, interpreter: &Interpreter, stmt: &Stmt) {
+        let location = stmt.location();
+        let source_map = interpreter.source_map();
+        let file = source_map.get(location.file);
+        let code = file
+            .content
+            .lines()
+            .nth(location.line - 1)
+            .unwrap_or_default();
+
+        println!("{}:{}", file.path, location.line);
+        println!("{:>4} | {code}", location.line);
+    }
+
+    fn show_watches(&self, interpreter: &mut Interpreter) {
+        for (i, watch) in self.watches.iter().enumerate() {
+            match evaluate(interpreter, watch) {
+                Ok(value) => println!("  {}: {watch} = {value}", i + 1),
+                Err(error) => println!("  {}: {watch} = <{}>", i + 1, error.message()),
+            }
+        }
+    }
+
+    /// Shows each scope's variables, innermost first. Shadowed variables are
+    /// shown too, in the scope they belong to.
+    fn show_locals(interpreter: &Interpreter) {
+        let mut env = Some(interpreter.env());
+
+        while let Some(scope) = env {
+            let scope = scope.borrow();
+            let title = match scope.enclosing() {
+                Some(_) => "locals",
+                None => "globals",
+            };
+            let values = scope.values();
+            if !values.is_empty() {
+                println!("{title}:");
+                for (name, value) in values {
+                    println!("  {name} = {value}");
+                }
+            }
+
+            env = scope.enclosing();
+        }
+    }
+
+    fn show_error(interpreter: &Interpreter, error: &RutoxError) {
+        println!(
+            "{}",
+            ErrorFormat::Human.render(error, &interpreter.source_map())
+        );
+    }
+}
+
+/// Evaluates `code` as an expression in the scope the program is paused in.
+fn evaluate(interpreter: &mut Interpreter, code: &str) -> Result<LoxObj, RutoxError> {
+    let file = interpreter.source_map_mut().add("<debugger>", code);
+    let stmts = Scanner::new(format!("{code};"), file)
+        .scan_tokens()
+        .and_then(|tokens| Parser::new(tokens).parse())?;
+
+    match stmts.as_slice() {
+        [Stmt::Expr(expr, _)] => interpreter.visit_expr(expr),
+        _ => Err(RutoxError::Syntax(
+            ErrorCode::ExpectedExpression,
+            "Expect a single expression".to_string(),
+            SrcLocation::new(1, 1, file),
+        )),
+    }
+}
+
+impl Hook for Debugger {
+    fn before_stmt(
+        &mut self,
+        interpreter: &mut Interpreter,
+        stmt: &Stmt,
+    ) -> Result<(), RutoxError> {
+        if !self.detached && self.pauses.before(stmt, interpreter.call_depth()) {
+            self.pause(interpreter, stmt);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,638 @@
+/// A stable identifier for each kind of diagnostic, shown next to the error
+/// and described at length by `rutox explain <code>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    // Scanner
+    UnterminatedString,
+    UnexpectedCharacter,
+
+    // Parser
+    ExpectedExpression,
+    MissingToken,
+    ExpectedIdentifier,
+    InvalidAssignmentTarget,
+    LoopControlOutsideLoop,
+    ReturnOutsideFunction,
+    InvalidImport,
+    TryWithoutHandler,
+    TooDeeplyNested,
+
+    // Interpreter
+    UndefinedVariable,
+    InvalidOperands,
+    NotCallable,
+    WrongArity,
+    StackOverflow,
+    UncaughtException,
+    ImportFailed,
+    ImportCycle,
+    UnknownModuleMember,
+    NoProperties,
+    AssertionFailed,
+
+    // Warnings
+    UnusedVariable,
+    ShadowedVariable,
+    UnreachableCode,
+    NilComparison,
+    ConstantCondition,
+
+    // Lints
+    SelfAssignment,
+    SelfComparison,
+    NegativeRepeat,
+    EmptyBlock,
+    IdenticalBranches,
+    AssignmentInCondition,
+    UnknownLintRule,
+
+    // Bugs in rutox itself
+    Internal,
+}
+
+impl ErrorCode {
+    pub const ALL: [ErrorCode; 35] = [
+        ErrorCode::UnterminatedString,
+        ErrorCode::UnexpectedCharacter,
+        ErrorCode::ExpectedExpression,
+        ErrorCode::MissingToken,
+        ErrorCode::ExpectedIdentifier,
+        ErrorCode::InvalidAssignmentTarget,
+        ErrorCode::LoopControlOutsideLoop,
+        ErrorCode::ReturnOutsideFunction,
+        ErrorCode::InvalidImport,
+        ErrorCode::TryWithoutHandler,
+        ErrorCode::TooDeeplyNested,
+        ErrorCode::UndefinedVariable,
+        ErrorCode::InvalidOperands,
+        ErrorCode::NotCallable,
+        ErrorCode::WrongArity,
+        ErrorCode::StackOverflow,
+        ErrorCode::UncaughtException,
+        ErrorCode::ImportFailed,
+        ErrorCode::ImportCycle,
+        ErrorCode::UnknownModuleMember,
+        ErrorCode::NoProperties,
+        ErrorCode::AssertionFailed,
+        ErrorCode::UnusedVariable,
+        ErrorCode::ShadowedVariable,
+        ErrorCode::UnreachableCode,
+        ErrorCode::NilComparison,
+        ErrorCode::ConstantCondition,
+        ErrorCode::SelfAssignment,
+        ErrorCode::SelfComparison,
+        ErrorCode::NegativeRepeat,
+        ErrorCode::EmptyBlock,
+        ErrorCode::IdenticalBranches,
+        ErrorCode::AssignmentInCondition,
+        ErrorCode::UnknownLintRule,
+        ErrorCode::Internal,
+    ];
+
+    /// Looks a code up by its identifier, ignoring case (`e0001` works too).
+    pub fn from_code(code: &str) -> Option<ErrorCode> {
+        Self::ALL
+            .into_iter()
+            .find(|error_code| error_code.code().eq_ignore_ascii_case(code))
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorCode::UnterminatedString => "E0001",
+            ErrorCode::UnexpectedCharacter => "E0002",
+            ErrorCode::ExpectedExpression => "E0100",
+            ErrorCode::MissingToken => "E0101",
+            ErrorCode::ExpectedIdentifier => "E0102",
+            ErrorCode::InvalidAssignmentTarget => "E0103",
+            ErrorCode::LoopControlOutsideLoop => "E0104",
+            ErrorCode::ReturnOutsideFunction => "E0105",
+            ErrorCode::InvalidImport => "E0106",
+            ErrorCode::TryWithoutHandler => "E0107",
+            ErrorCode::TooDeeplyNested => "E0108",
+            ErrorCode::UndefinedVariable => "E0200",
+            ErrorCode::InvalidOperands => "E0201",
+            ErrorCode::NotCallable => "E0202",
+            ErrorCode::WrongArity => "E0203",
+            ErrorCode::StackOverflow => "E0204",
+            ErrorCode::UncaughtException => "E0205",
+            ErrorCode::ImportFailed => "E0206",
+            ErrorCode::ImportCycle => "E0207",
+            ErrorCode::UnknownModuleMember => "E0208",
+            ErrorCode::NoProperties => "E0209",
+            ErrorCode::AssertionFailed => "E0210",
+            ErrorCode::UnusedVariable => "W0001",
+            ErrorCode::ShadowedVariable => "W0002",
+            ErrorCode::UnreachableCode => "W0003",
+            ErrorCode::NilComparison => "W0004",
+            ErrorCode::ConstantCondition => "W0005",
+            ErrorCode::SelfAssignment => "W0101",
+            ErrorCode::SelfComparison => "W0102",
+            ErrorCode::NegativeRepeat => "W0103",
+            ErrorCode::EmptyBlock => "W0104",
+            ErrorCode::IdenticalBranches => "W0105",
+            ErrorCode::AssignmentInCondition => "W0106",
+            ErrorCode::UnknownLintRule => "W0107",
+            ErrorCode::Internal => "E0900",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            ErrorCode::UnterminatedString => "Unterminated string",
+            ErrorCode::UnexpectedCharacter => "Unexpected character",
+            ErrorCode::ExpectedExpression => "Expected an expression",
+            ErrorCode::MissingToken => "Missing token",
+            ErrorCode::ExpectedIdentifier => "Expected a name",
+            ErrorCode::InvalidAssignmentTarget => "Invalid assignment target",
+            ErrorCode::LoopControlOutsideLoop => "`break` or `continue` outside of a loop",
+            ErrorCode::ReturnOutsideFunction => "`return` outside of a function",
+            ErrorCode::InvalidImport => "Invalid import",
+            ErrorCode::TryWithoutHandler => "`try` without `catch` or `finally`",
+            ErrorCode::TooDeeplyNested => "Code nested too deeply",
+            ErrorCode::UndefinedVariable => "Undefined variable",
+            ErrorCode::InvalidOperands => "Invalid operand types",
+            ErrorCode::NotCallable => "Value is not callable",
+            ErrorCode::WrongArity => "Wrong number of arguments",
+            ErrorCode::StackOverflow => "Stack overflow",
+            ErrorCode::UncaughtException => "Uncaught exception",
+            ErrorCode::ImportFailed => "Import failed",
+            ErrorCode::ImportCycle => "Import cycle",
+            ErrorCode::UnknownModuleMember => "Unknown module member",
+            ErrorCode::NoProperties => "Value has no properties",
+            ErrorCode::AssertionFailed => "Assertion failed",
+            ErrorCode::UnusedVariable => "Unused variable",
+            ErrorCode::ShadowedVariable => "Shadowed variable",
+            ErrorCode::UnreachableCode => "Unreachable code",
+            ErrorCode::NilComparison => "Comparison with `nil`",
+            ErrorCode::ConstantCondition => "Constant condition",
+            ErrorCode::SelfAssignment => "Self-assignment",
+            ErrorCode::SelfComparison => "Comparison with itself",
+            ErrorCode::NegativeRepeat => "String repeated a negative number of times",
+            ErrorCode::EmptyBlock => "Empty block",
+            ErrorCode::IdenticalBranches => "Identical `if` branches",
+            ErrorCode::AssignmentInCondition => "Assignment in condition",
+            ErrorCode::UnknownLintRule => "Unknown rule in `rutox-ignore`",
+            ErrorCode::Internal => "Internal error",
+        }
+    }
+
+    /// A longer description of the error, with an example and how to fix it.
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            ErrorCode::UnterminatedString => {
+                "A string literal was opened with `\"` but the file ended before it was closed.
+
+Erroneous code example:
+
+    print \"hello;
+
+Close the string with a matching `\"`:
+
+    print \"hello\";"
+            }
+            ErrorCode::UnexpectedCharacter => {
+                "The source contains a character that is not part of Lox, such as `@`,
+`#` or `%`.
+
+Erroneous code example:
+
+    var total = 10 % 3;
+
+Remove the character, or use an operator Lox supports:
+
+    var total = 10 - 3;"
+            }
+            ErrorCode::ExpectedExpression => {
+                "An expression (a value, variable, call or operation) was expected, but
+something else was found, or the input ended.
+
+Erroneous code example:
+
+    var x = ;
+
+Give the missing expression:
+
+    var x = 1;"
+            }
+            ErrorCode::MissingToken => {
+                "A piece of punctuation or a keyword that the grammar requires is missing,
+such as the `;` at the end of a statement or the `)` closing a call.
+
+Erroneous code example:
+
+    print \"hi\"
+
+Add the token the error message asks for:
+
+    print \"hi\";"
+            }
+            ErrorCode::ExpectedIdentifier => {
+                "A name was expected, e.g. after `var`, in a parameter list or after `.`.
+
+Erroneous code example:
+
+    var 1 = 2;
+
+Use a name made of letters, digits and `_` that does not start with a digit:
+
+    var one = 2;"
+            }
+            ErrorCode::InvalidAssignmentTarget => {
+                "Only variables can be assigned to with `=`.
+
+Erroneous code example:
+
+    1 + 2 = 3;
+
+Assign to a variable instead:
+
+    var sum = 1 + 2;"
+            }
+            ErrorCode::LoopControlOutsideLoop => {
+                "`break` and `continue` only make sense inside a loop. A function body
+starts afresh, so a loop around the function declaration does not count.
+
+Erroneous code example:
+
+    if (done) break;
+
+Put the statement inside a `while` loop:
+
+    while (true) {
+      if (done) break;
+    }"
+            }
+            ErrorCode::ReturnOutsideFunction => {
+                "`return` can only be used inside a function.
+
+Erroneous code example:
+
+    return 1;
+
+Move it into a function:
+
+    fun one() {
+      return 1;
+    }"
+            }
+            ErrorCode::InvalidImport => {
+                "An `import` must be followed by a path string, and, when no name is
+given with `from`, the file name must be a valid variable name.
+
+Erroneous code example:
+
+    import \"lib/my-module.lox\";
+
+Choose the name the module is bound to:
+
+    import my_module from \"lib/my-module.lox\";"
+            }
+            ErrorCode::TryWithoutHandler => {
+                "A `try` block must be followed by a `catch` clause, a `finally` block, or
+both.
+
+Erroneous code example:
+
+    try { risky(); }
+
+Handle the error:
+
+    try { risky(); } catch (error) { print error; }"
+            }
+            ErrorCode::TooDeeplyNested => {
+                "Expressions and statements can only be nested so deeply, e.g. a couple of
+hundred parentheses or blocks inside each other, before rutox gives up
+instead of running out of stack.
+
+Erroneous code example:
+
+    print ((((((((((((((((((((((((((((((((((((((((1))))))))))))))))))))))))))))))))))))))));
+
+with far more parentheses. Move the inner parts into variables or functions:
+
+    var inner = (1);
+    print (inner);"
+            }
+            ErrorCode::UndefinedVariable => {
+                "A variable was read or assigned before being declared with `var`, or
+outside of the block it was declared in.
+
+Erroneous code example:
+
+    count = 1;
+
+Declare it first:
+
+    var count = 1;"
+            }
+            ErrorCode::InvalidOperands => {
+                "An operator was applied to values of the wrong type, e.g. subtracting a
+string. `+` works on two numbers or two strings, `*` on numbers or a string
+and a number, and the other arithmetic and comparison operators on numbers.
+
+Erroneous code example:
+
+    print \"10\" - 1;
+
+Use values of the right type:
+
+    print 10 - 1;"
+            }
+            ErrorCode::NotCallable => {
+                "Only functions can be called with `()`.
+
+Erroneous code example:
+
+    var name = \"lox\";
+    name();
+
+Call a function instead, or remove the parentheses:
+
+    print name;"
+            }
+            ErrorCode::WrongArity => {
+                "A function was called with a different number of arguments than it has
+parameters.
+
+Erroneous code example:
+
+    fun add(a, b) { return a + b; }
+    add(1);
+
+Pass one argument per parameter:
+
+    add(1, 2);"
+            }
+            ErrorCode::StackOverflow => {
+                "Function calls were nested too deeply, usually because of recursion
+without a base case.
+
+Erroneous code example:
+
+    fun forever(n) { return forever(n + 1); }
+
+Make sure the recursion stops:
+
+    fun count(n) {
+      if (n == 0) return 0;
+      return count(n - 1);
+    }"
+            }
+            ErrorCode::UncaughtException => {
+                "A value was thrown with `throw` and no `catch` handled it.
+
+Erroneous code example:
+
+    throw \"something went wrong\";
+
+Catch it where it can be handled:
+
+    try {
+      throw \"something went wrong\";
+    } catch (error) {
+      print error;
+    }"
+            }
+            ErrorCode::ImportFailed => {
+                "The file given to `import` could not be found or read. Paths are
+relative to the file that contains the `import`.
+
+Erroneous code example:
+
+    import \"lib/mat.lox\";
+
+Check the path:
+
+    import \"lib/math.lox\";"
+            }
+            ErrorCode::ImportCycle => {
+                "A file ended up importing itself, directly or through other files, so it
+would never finish loading.
+
+Erroneous code example:
+
+    // a.lox
+    import \"b.lox\";
+    // b.lox
+    import \"a.lox\";
+
+Move the code both files need into a third file that they both import."
+            }
+            ErrorCode::UnknownModuleMember => {
+                "A module was asked for a name that its file does not define at the top
+level.
+
+Erroneous code example:
+
+    import \"lib/math.lox\";
+    print math.tau;
+
+Use a name the module defines, or define it in the module."
+            }
+            ErrorCode::NoProperties => {
+                "Properties can only be read with `.` from modules, and from errors caught
+by `catch`, which have `message`, `code`, `line` and `column`.
+
+Erroneous code example:
+
+    var name = \"lox\";
+    print name.length;
+
+Only use `.` on a module or a caught error:
+
+    import \"lib/math.lox\";
+    print math.pi;"
+            }
+            ErrorCode::AssertionFailed => {
+                "An `assert` or `assertEqual` in a test did not hold. These are only
+defined when running tests with `rutox test`.
+
+Erroneous code example:
+
+    test \"addition\" {
+      assertEqual(1 + 1, 3);
+    }
+
+The message shows the values involved. Fix the code under test, or the
+expectation if it is the one that is wrong."
+            }
+            ErrorCode::UnusedVariable => {
+                "A local variable or function is declared but never read. It may be left
+over from a refactoring, or a sign that another variable is used by mistake.
+Names starting with `_` are never reported.
+
+Erroneous code example:
+
+    fun greet(name) {
+      var message = \"Hello, \" + name;
+      print name;
+    }
+
+Use the variable, or remove it:
+
+    fun greet(name) {
+      var message = \"Hello, \" + name;
+      print message;
+    }"
+            }
+            ErrorCode::ShadowedVariable => {
+                "A local variable has the same name as a variable in an enclosing scope,
+which then cannot be reached until the inner block ends.
+
+Erroneous code example:
+
+    fun total(items) {
+      var sum = 0;
+      {
+        var sum = items;
+      }
+      return sum;
+    }
+
+Give the inner variable a different name:
+
+    var count = items;"
+            }
+            ErrorCode::UnreachableCode => {
+                "Statements that follow a `return`, `throw`, `break` or `continue` in the
+same block can never run.
+
+Erroneous code example:
+
+    fun answer() {
+      return 42;
+      print \"done\";
+    }
+
+Remove the statements, or move them before the `return`."
+            }
+            ErrorCode::NilComparison => {
+                "A value is compared with `nil` using `==` or `!=`. If the value can never
+be `false`, its truthiness says the same thing more directly.
+
+Erroneous code example:
+
+    if (user == nil) print \"anonymous\";
+
+Test the value itself:
+
+    if (!user) print \"anonymous\";"
+            }
+            ErrorCode::ConstantCondition => {
+                "The condition of an `if` is a literal, so the same branch is taken every
+time. This is often debugging code that was left behind.
+
+Erroneous code example:
+
+    if (true) print \"always\";
+
+Remove the `if`, or test a real condition:
+
+    print \"always\";"
+            }
+            ErrorCode::SelfAssignment => {
+                "A variable is assigned to itself, which does nothing. Usually another
+variable was meant on one of the sides. Reported by the `self-assignment` lint.
+
+Erroneous code example:
+
+    fun setName(name) {
+      name = name;
+    }
+
+Assign the value that was meant:
+
+    fun setName(newName) {
+      name = newName;
+    }"
+            }
+            ErrorCode::SelfComparison => {
+                "Both sides of a comparison are the same expression, so the result never
+changes. Usually another variable was meant on one of the sides. Reported by
+the `self-comparison` lint.
+
+Erroneous code example:
+
+    if (a == a) print \"equal\";
+
+Compare the values that were meant:
+
+    if (a == b) print \"equal\";"
+            }
+            ErrorCode::NegativeRepeat => {
+                "Multiplying a string by a number repeats it, and repeating it a negative
+number of times gives an empty string. Reported by the `negative-repeat` lint.
+
+Erroneous code example:
+
+    print \"-\" * -10;
+
+Use a positive count:
+
+    print \"-\" * 10;"
+            }
+            ErrorCode::EmptyBlock => {
+                "A block, or the body of a function, test, `try`, `catch` or `finally`, has
+no statements, so it does nothing. It may be code that was never written, or a
+left-over from removing some. Reported by the `empty-block` lint.
+
+Erroneous code example:
+
+    while (poll()) {}
+
+Remove the block, or ignore the lint where it is intentional:
+
+    while (poll()) {} // rutox-ignore empty-block"
+            }
+            ErrorCode::IdenticalBranches => {
+                "Both branches of an `if` do the same thing, so the condition does not
+matter. Usually one of them was copied and never changed. Reported by the
+`identical-branches` lint.
+
+Erroneous code example:
+
+    if (ready) print \"go\"; else print \"go\";
+
+Change the branch that was meant to differ, or remove the `if`:
+
+    if (ready) print \"go\"; else print \"wait\";"
+            }
+            ErrorCode::AssignmentInCondition => {
+                "The condition of an `if` or `while` is an assignment, which is often a
+typo for `==`. Reported by the `assignment-in-condition` lint.
+
+Erroneous code example:
+
+    if (answer = 42) print \"right\";
+
+Compare instead, or wrap the assignment in parentheses if it is intentional:
+
+    if (answer == 42) print \"right\";
+    while ((line = next())) print line;"
+            }
+            ErrorCode::UnknownLintRule => {
+                "A `// rutox-ignore` comment names a rule or code that does not exist,
+usually because of a typo. The comment ignores nothing until it is fixed.
+
+Erroneous code example:
+
+    x = x; // rutox-ignore self-asignment
+
+Name one of the rules `rutox lint` checks, or a code like `W0101`:
+
+    x = x; // rutox-ignore self-assignment"
+            }
+            ErrorCode::Internal => {
+                "Something went wrong inside rutox itself. This is a bug, not a problem
+with your code. Please report it at https://github.com/MatheusRich/rutox with
+the code that triggered it."
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
@@ -0,0 +1,157 @@
+use super::error_code::ErrorCode;
+use super::rutox_error::{RutoxError, Severity};
+use super::scanner::{SourceMap, SrcLocation};
+use serde_json::{json, Value};
+use std::str::FromStr;
+
+/// How errors are reported, chosen with `--error-format`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorFormat {
+    /// Colored messages with code excerpts, for people.
+    Human,
+    /// One JSON record per error, per line.
+    Json,
+    /// A SARIF 2.1.0 log, for CI and code scanning tools.
+    Sarif,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "human" => Ok(ErrorFormat::Human),
+            "json" => Ok(ErrorFormat::Json),
+            "sarif" => Ok(ErrorFormat::Sarif),
+            other => Err(format!(
+                "Unknown error format `{other}`, expected `human`, `json` or `sarif`"
+            )),
+        }
+    }
+}
+
+impl ErrorFormat {
+    pub fn render(&self, error: &RutoxError, source_map: &SourceMap) -> String {
+        match self {
+            ErrorFormat::Human => error
+                .errors()
+                .into_iter()
+                .map(|error| format!("{error}\n{}", error.details(source_map)))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            ErrorFormat::Json => error
+                .errors()
+                .into_iter()
+                .map(|error| json_record(error, source_map).to_string())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            ErrorFormat::Sarif => sarif_log(error, source_map).to_string(),
+        }
+    }
+}
+
+fn json_record(error: &RutoxError, source_map: &SourceMap) -> Value {
+    let location = error.location();
+
+    json!({
+        "kind": error.kind(),
+        "severity": match error.severity() {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        },
+        "code": error.code().code(),
+        "message": error.message(),
+        "file": source_map.get(location.file).path,
+        "line": location.line,
+        "column": location.col,
+        "span": json_span(&location),
+        "labels": error.labels().iter().map(|label| json!({
+            "message": label.message,
+            "file": source_map.get(label.location.file).path,
+            "span": json_span(&label.location),
+        })).collect::<Vec<_>>(),
+        "help": error.helps(),
+        "traceback": error.frames().iter().rev().map(|frame| json!({
+            "function": frame.function_name,
+            "file": source_map.get(frame.call_location.file).path,
+            "line": frame.call_location.line,
+            "column": frame.call_location.col,
+        })).collect::<Vec<_>>(),
+    })
+}
+
+pub fn json_span(location: &SrcLocation) -> Value {
+    json!({
+        "start": { "line": location.line, "column": location.col },
+        "end": { "line": location.end_line, "column": location.end_col },
+    })
+}
+
+fn sarif_log(error: &RutoxError, source_map: &SourceMap) -> Value {
+    let results: Vec<Value> = error
+        .errors()
+        .into_iter()
+        .map(|error| {
+            json!({
+                "ruleId": error.code().code(),
+                "level": match error.severity() {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning",
+                },
+                "message": { "text": sarif_message(error) },
+                "locations": [sarif_location(&error.location(), source_map)],
+                "relatedLocations": error.labels().iter().map(|label| {
+                    let mut location = sarif_location(&label.location, source_map);
+                    location["message"] = json!({ "text": label.message });
+
+                    location
+                }).collect::<Vec<_>>(),
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "rutox",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": "https://github.com/MatheusRich/rutox",
+                    "rules": ErrorCode::ALL.iter().map(|code| json!({
+                        "id": code.code(),
+                        "shortDescription": { "text": code.title() },
+                        "fullDescription": { "text": code.explanation() },
+                    })).collect::<Vec<_>>(),
+                }
+            },
+            "results": results,
+        }],
+    })
+}
+
+fn sarif_location(location: &SrcLocation, source_map: &SourceMap) -> Value {
+    json!({
+        "physicalLocation": {
+            "artifactLocation": { "uri": source_map.get(location.file).path },
+            "region": {
+                "startLine": location.line,
+                "startColumn": location.col,
+                "endLine": location.end_line,
+                // SARIF end columns are exclusive
+                "endColumn": location.end_col + 1,
+            }
+        }
+    })
+}
+
+/// SARIF has no place for help notes, so they follow the message.
+fn sarif_message(error: &RutoxError) -> String {
+    let mut message = error.message();
+    for help in error.helps() {
+        message.push_str(&format!("\nhelp: {help}"));
+    }
+
+    message
+}
@@ -0,0 +1,499 @@
+use crate::parser::{
+    ast::{
+        BinaryData, CallData, Expr, FunctionData, ImportData, LiteralData, LogicalOp, Stmt,
+        TestData, TryData, UnaryData,
+    },
+    visitors::{ExprVisitor, StmtVisitor},
+    Parser,
+};
+use crate::rutox_error::RutoxError;
+use crate::scanner::{
+    token::{Comment, Token, TokenKind},
+    FileId, Scanner, SrcLocation,
+};
+use std::rc::Rc;
+
+const INDENT: &str = "  ";
+
+/// Formats Lox source code in the canonical style: two-space indentation,
+/// spaces around binary operators, and opening braces on the same line.
+/// Comments and single blank lines between statements are kept.
+pub fn format(source: &str, file: FileId) -> Result<String, RutoxError> {
+    let mut scanner = Scanner::new(source.to_string(), file);
+    let tokens = scanner.scan_tokens()?;
+    let stmts = Parser::new(tokens.clone()).parse()?;
+
+    let mut formatter = Formatter {
+        source_lines: source.lines().collect(),
+        tokens: &tokens,
+        comments: scanner.comments().to_vec(),
+        next_comment: 0,
+        output: String::new(),
+        line: String::new(),
+        indent: 0,
+        at_block_start: true,
+    };
+    formatter.stmts(&stmts, usize::MAX);
+
+    Ok(formatter.output)
+}
+
+struct Formatter<'a> {
+    source_lines: Vec<&'a str>,
+    /// Used to find the closing braces of bodies, which the AST doesn't keep.
+    tokens: &'a [Token],
+    comments: Vec<Comment>,
+    next_comment: usize,
+    output: String,
+    /// The line being written, without indentation.
+    line: String,
+    indent: usize,
+    /// Whether nothing was written since the current block (or the file)
+    /// started, so no blank line is needed.
+    at_block_start: bool,
+}
+
+impl Formatter<'_> {
+    /// Writes each statement on its own line, then the comments that come
+    /// before `end_line`, the line of the closing brace.
+    fn stmts(&mut self, stmts: &[Stmt], end_line: usize) {
+        for stmt in stmts {
+            let line = self.start_line(stmt);
+
+            self.comments_before(line);
+            self.blank_line_before(line);
+            self.stmt(stmt);
+            self.newline();
+        }
+
+        self.comments_before(end_line);
+    }
+
+    /// `{`, the statements indented, and `}`, without a line break after it.
+    fn body(&mut self, stmts: &[Stmt], end_line: usize) {
+        let has_comments = self
+            .comments
+            .get(self.next_comment)
+            .is_some_and(|comment| comment.location.line < end_line);
+        if stmts.is_empty() && !has_comments {
+            self.write("{}");
+            return;
+        }
+
+        self.write("{");
+        self.newline();
+        self.indent += 1;
+        self.at_block_start = true;
+        self.stmts(stmts, end_line);
+        self.indent -= 1;
+        self.write("}");
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        self.visit_stmt(stmt)
+            .unwrap_or_else(|_| unreachable!("Formatting never fails"))
+    }
+
+    fn expr(&mut self, expr: &Expr) -> String {
+        self.visit_expr(expr)
+            .unwrap_or_else(|_| unreachable!("Formatting never fails"))
+    }
+
+    fn function(&mut self, function: &FunctionData) {
+        let params: Vec<&str> = function
+            .params
+            .iter()
+            .map(|param| param.lexeme.as_str())
+            .collect();
+        let header = match &function.name {
+            Some(name) => format!("fun {}({}) ", name.lexeme, params.join(", ")),
+            None => format!("fun ({}) ", params.join(", ")),
+        };
+
+        self.write(&header);
+        let end_line = self.closing_brace_line(&function.location);
+        self.body(&function.body, end_line);
+    }
+
+    fn comments_before(&mut self, line: usize) {
+        while let Some(comment) = self.comments.get(self.next_comment).cloned() {
+            if comment.location.line >= line {
+                break;
+            }
+            self.next_comment += 1;
+
+            if comment.trailing {
+                self.append_to_last_line(&comment.text);
+            } else {
+                if !self.line.is_empty() {
+                    self.newline();
+                }
+                self.blank_line_before(comment.location.line);
+                self.write(&comment.text);
+                self.newline();
+            }
+        }
+    }
+
+    /// Keeps one blank line where the source had at least one.
+    fn blank_line_before(&mut self, line: usize) {
+        let follows_blank_line = line >= 2
+            && self
+                .source_lines
+                .get(line - 2)
+                .is_some_and(|previous| previous.trim().is_empty());
+
+        if follows_blank_line && !self.at_block_start && !self.output.is_empty() {
+            self.output.push('\n');
+        }
+        self.at_block_start = false;
+    }
+
+    fn append_to_last_line(&mut self, text: &str) {
+        if self.line.is_empty() {
+            let last_line_end = self.output.trim_end_matches('\n').len();
+            self.output.truncate(last_line_end);
+            self.output.push_str(&format!(" {text}\n"));
+        } else {
+            self.line.push_str(&format!(" {text}"));
+        }
+    }
+
+    fn write(&mut self, text: &str) {
+        self.line.push_str(text);
+    }
+
+    fn newline(&mut self) {
+        if self.line.is_empty() {
+            return;
+        }
+
+        let line = std::mem::take(&mut self.line);
+        self.output.push_str(&INDENT.repeat(self.indent));
+        self.output.push_str(&line);
+        self.output.push('\n');
+    }
+
+    /// The line `stmt` starts on, which comments before it must come before.
+    fn start_line(&self, stmt: &Stmt) -> usize {
+        match stmt {
+            // A block's location is its closing brace
+            Stmt::Block(_, location) => self
+                .opening_brace(location)
+                .map_or(location.line, |brace| brace.location.line),
+            _ => stmt.location().line,
+        }
+    }
+
+    /// The `{` matching the `}` at `location`.
+    fn opening_brace(&self, location: &SrcLocation) -> Option<&Token> {
+        let mut depth = 0;
+
+        self.tokens
+            .iter()
+            .rev()
+            .skip_while(|token| {
+                (token.location.line, token.location.col) != (location.line, location.col)
+            })
+            .find(|token| {
+                match token.kind {
+                    TokenKind::RBrace => depth += 1,
+                    TokenKind::LBrace => depth -= 1,
+                    _ => {}
+                }
+
+                depth == 0
+            })
+    }
+
+    fn closing_brace_line(&self, location: &SrcLocation) -> usize {
+        self.closing_brace(location)
+            .map_or(usize::MAX, |brace| brace.location.line)
+    }
+
+    /// The `}` matching the first `{` at or after `location`.
+    fn closing_brace<'b>(&'b self, location: &'b SrcLocation) -> Option<&'b Token> {
+        let mut depth = 0;
+
+        self.tokens_from(location)
+            .skip_while(|token| token.kind != TokenKind::LBrace)
+            .find(|token| {
+                match token.kind {
+                    TokenKind::LBrace => depth += 1,
+                    TokenKind::RBrace => depth -= 1,
+                    _ => {}
+                }
+
+                depth == 0
+            })
+    }
+
+    fn tokens_from<'b>(&'b self, location: &'b SrcLocation) -> impl Iterator<Item = &'b Token> {
+        self.tokens.iter().filter(move |token| {
+            (token.location.line, token.location.col) >= (location.line, location.col)
+        })
+    }
+
+    /// The `finally` keyword of a `try` statement.
+    fn finally_location(&self, try_data: &TryData) -> SrcLocation {
+        let last_clause = match &try_data.catch {
+            Some(catch) => &catch.name.location,
+            None => &try_data.location,
+        };
+
+        self.closing_brace(last_clause)
+            .and_then(|brace| {
+                self.tokens_from(&brace.location)
+                    .find(|token| token.kind == TokenKind::Finally)
+            })
+            .map_or_else(|| try_data.location.clone(), |token| token.location.clone())
+    }
+}
+
+impl StmtVisitor<()> for Formatter<'_> {
+    fn visit_print_stmt(&mut self, expr: &Expr, _location: &SrcLocation) -> Result<(), RutoxError> {
+        let expr = self.expr(expr);
+        self.write(&format!("print {expr};"));
+
+        Ok(())
+    }
+
+    fn visit_expr_stmt(&mut self, expr: &Expr, _location: &SrcLocation) -> Result<(), RutoxError> {
+        let expr = self.expr(expr);
+        self.write(&format!("{expr};"));
+
+        Ok(())
+    }
+
+    fn visit_var_stmt(
+        &mut self,
+        name: &Token,
+        initializer: &Option<Expr>,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        match initializer {
+            Some(initializer) => {
+                let initializer = self.expr(initializer);
+                self.write(&format!("var {} = {initializer};", name.lexeme));
+            }
+            None => self.write(&format!("var {};", name.lexeme)),
+        }
+
+        Ok(())
+    }
+
+    fn visit_block_stmt(
+        &mut self,
+        stmts: &[Stmt],
+        location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        // A block's location is its closing brace
+        self.body(stmts, location.line);
+
+        Ok(())
+    }
+
+    fn visit_if_stmt(
+        &mut self,
+        cond: &Expr,
+        then_branch: &Stmt,
+        else_branch: &Option<Box<Stmt>>,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        let cond = self.expr(cond);
+        self.write(&format!("if ({cond}) "));
+        self.stmt(then_branch);
+
+        if let Some(else_branch) = else_branch {
+            if let Stmt::Block(_, _) = then_branch {
+                self.write(" ");
+            } else {
+                self.newline();
+            }
+            self.write("else ");
+            self.stmt(else_branch);
+        }
+
+        Ok(())
+    }
+
+    fn visit_while_stmt(
+        &mut self,
+        cond: &Expr,
+        body: &Stmt,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        let cond = self.expr(cond);
+        self.write(&format!("while ({cond}) "));
+        self.stmt(body);
+
+        Ok(())
+    }
+
+    fn visit_break_stmt(&mut self, _location: &SrcLocation) -> Result<(), RutoxError> {
+        self.write("break;");
+
+        Ok(())
+    }
+
+    fn visit_continue_stmt(&mut self, _location: &SrcLocation) -> Result<(), RutoxError> {
+        self.write("continue;");
+
+        Ok(())
+    }
+
+    fn visit_function_stmt(&mut self, function: &Rc<FunctionData>) -> Result<(), RutoxError> {
+        self.function(function);
+
+        Ok(())
+    }
+
+    fn visit_return_stmt(
+        &mut self,
+        value: &Option<Expr>,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        match value {
+            Some(value) => {
+                let value = self.expr(value);
+                self.write(&format!("return {value};"));
+            }
+            None => self.write("return;"),
+        }
+
+        Ok(())
+    }
+
+    fn visit_throw_stmt(
+        &mut self,
+        value: &Expr,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        let value = self.expr(value);
+        self.write(&format!("throw {value};"));
+
+        Ok(())
+    }
+
+    fn visit_try_stmt(&mut self, try_data: &TryData) -> Result<(), RutoxError> {
+        self.write("try ");
+        let end_line = self.closing_brace_line(&try_data.location);
+        self.body(&try_data.body, end_line);
+
+        if let Some(catch) = &try_data.catch {
+            self.write(&format!(" catch ({}) ", catch.name.lexeme));
+            let end_line = self.closing_brace_line(&catch.name.location);
+            self.body(&catch.body, end_line);
+        }
+        if let Some(finally) = &try_data.finally {
+            self.write(" finally ");
+            let end_line = self.closing_brace_line(&self.finally_location(try_data));
+            self.body(finally, end_line);
+        }
+
+        Ok(())
+    }
+
+    fn visit_import_stmt(&mut self, import: &ImportData) -> Result<(), RutoxError> {
+        if Parser::module_name(&import.path).as_ref() == Some(&import.name) {
+            self.write(&format!("import \"{}\";", import.path));
+        } else {
+            self.write(&format!("import {} from \"{}\";", import.name, import.path));
+        }
+
+        Ok(())
+    }
+
+    fn visit_test_stmt(&mut self, test: &TestData) -> Result<(), RutoxError> {
+        self.write(&format!("test \"{}\" ", test.name));
+        let end_line = self.closing_brace_line(&test.location);
+        self.body(&test.body, end_line);
+
+        Ok(())
+    }
+}
+
+impl ExprVisitor<String> for Formatter<'_> {
+    fn visit_literal_expr(&mut self, literal: &LiteralData) -> Result<String, RutoxError> {
+        match literal {
+            LiteralData::String(s, _) => Ok(format!("\"{s}\"")),
+            LiteralData::Number(n, _) => Ok(format!("{n}")),
+            LiteralData::Bool(b, _) => Ok(format!("{b}")),
+            LiteralData::Nil(_) => Ok("nil".to_string()),
+        }
+    }
+
+    fn visit_unary_expr(&mut self, unary: &UnaryData) -> Result<String, RutoxError> {
+        Ok(format!("{}{}", unary.operator, self.expr(&unary.expr)))
+    }
+
+    fn visit_binary_expr(&mut self, binary: &BinaryData) -> Result<String, RutoxError> {
+        Ok(format!(
+            "{} {} {}",
+            self.expr(&binary.left),
+            binary.operator,
+            self.expr(&binary.right)
+        ))
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &Expr) -> Result<String, RutoxError> {
+        Ok(format!("({})", self.expr(expr)))
+    }
+
+    fn visit_variable_expr(
+        &mut self,
+        name: &Token,
+        _location: &SrcLocation,
+    ) -> Result<String, RutoxError> {
+        Ok(name.lexeme.clone())
+    }
+
+    fn visit_assign_expr(
+        &mut self,
+        name: &Token,
+        value: &Expr,
+        _location: &SrcLocation,
+    ) -> Result<String, RutoxError> {
+        Ok(format!("{} = {}", name.lexeme, self.expr(value)))
+    }
+
+    fn visit_logical_expr(
+        &mut self,
+        left: &Expr,
+        op: &LogicalOp,
+        right: &Expr,
+        _location: &SrcLocation,
+    ) -> Result<String, RutoxError> {
+        Ok(format!("{} {op} {}", self.expr(left), self.expr(right)))
+    }
+
+    fn visit_call_expr(&mut self, call: &CallData) -> Result<String, RutoxError> {
+        let args: Vec<String> = call.args.iter().map(|arg| self.expr(arg)).collect();
+
+        Ok(format!("{}({})", self.expr(&call.callee), args.join(", ")))
+    }
+
+    /// Lambdas are written where they appear, with their body indented one
+    /// level deeper than the line they start on.
+    fn visit_lambda_expr(&mut self, function: &Rc<FunctionData>) -> Result<String, RutoxError> {
+        let line = std::mem::take(&mut self.line);
+        let output = std::mem::take(&mut self.output);
+
+        self.function(function);
+        let lambda = std::mem::replace(&mut self.output, output) + &self.line;
+        self.line = line;
+
+        // The lambda's lines were indented as if it started its own line, so
+        // only its first line needs trimming
+        Ok(lambda.trim_start().to_string())
+    }
+
+    fn visit_get_expr(
+        &mut self,
+        object: &Expr,
+        name: &Token,
+        _location: &SrcLocation,
+    ) -> Result<String, RutoxError> {
+        Ok(format!("{}.{}", self.expr(object), name.lexeme))
+    }
+}
@@ -1,50 +1,75 @@
 use super::LoxObj;
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::rc::Rc;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Default)]
 pub struct Env {
     values: HashMap<String, LoxObj>,
-    enclosing: Option<Box<Env>>,
+    enclosing: Option<Rc<RefCell<Env>>>,
 }
 
 impl Env {
-    pub fn new(enclosing: Box<Env>) -> Self {
+    pub fn new(enclosing: Rc<RefCell<Env>>) -> Self {
         Self {
             values: HashMap::new(),
             enclosing: Some(enclosing),
         }
     }
 
-    pub fn default() -> Self {
-        Self {
-            values: HashMap::new(),
-            enclosing: None,
+    pub fn define(&mut self, name: &str, value: LoxObj) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    pub fn get(&self, name: &str) -> Option<LoxObj> {
+        match self.values.get(name) {
+            Some(value) => Some(value.clone()),
+            None => self
+                .enclosing
+                .as_ref()
+                .and_then(|enclosing| enclosing.borrow().get(name)),
         }
     }
 
-    pub fn define(&mut self, name: &str, value: LoxObj) {
-        self.values.insert(name.to_string(), value);
+    /// Every name visible from this scope, innermost scope first and sorted
+    /// within each scope.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.values.keys().cloned().collect();
+        names.sort();
+        if let Some(enclosing) = &self.enclosing {
+            names.extend(enclosing.borrow().names());
+        }
+
+        names
+    }
+
+    /// The variables of this scope alone, sorted by name.
+    pub fn values(&self) -> Vec<(String, LoxObj)> {
+        let mut values: Vec<(String, LoxObj)> = self
+            .values
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        values.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        values
     }
 
-    pub fn get(&self, name: &str) -> Option<&LoxObj> {
-        self.values.get(name).or_else(|| {
-            if let Some(enclosing) = &self.enclosing {
-                enclosing.get(name)
-            } else {
-                None
-            }
-        })
+    pub fn enclosing(&self) -> Option<Rc<RefCell<Env>>> {
+        self.enclosing.clone()
     }
 
-    pub fn assign(&mut self, name: &str, value: LoxObj) -> Result<(), ()> {
+    /// Assigns to the variable in the innermost scope that has it, returning
+    /// whether any did.
+    pub fn assign(&mut self, name: &str, value: LoxObj) -> bool {
         if self.values.contains_key(name) {
             self.values.insert(name.to_string(), value);
 
-            Ok(())
-        } else if let Some(enclosing) = &mut self.enclosing {
-            enclosing.assign(name, value)
+            true
+        } else if let Some(enclosing) = &self.enclosing {
+            enclosing.borrow_mut().assign(name, value)
         } else {
-            Err(())
+            false
         }
     }
 }
@@ -0,0 +1,11 @@
+use super::LoxObj;
+
+/// How control leaves a statement. Anything other than `Normal` unwinds the
+/// enclosing statements until something handles it (a loop or a function call).
+#[derive(Debug)]
+pub enum Flow {
+    Normal,
+    Break,
+    Continue,
+    Return(LoxObj),
+}
@@ -0,0 +1,47 @@
+use super::Env;
+use crate::parser::ast::FunctionData;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// A user-defined function (or lambda) together with the env it was declared
+/// in, so it can see the variables around it when called.
+pub struct LoxFunction {
+    pub declaration: Rc<FunctionData>,
+    pub closure: Rc<RefCell<Env>>,
+}
+
+impl LoxFunction {
+    pub fn new(declaration: Rc<FunctionData>, closure: Rc<RefCell<Env>>) -> Self {
+        Self {
+            declaration,
+            closure,
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.declaration
+            .name
+            .as_ref()
+            .map(|name| name.lexeme.as_str())
+    }
+
+    pub fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+}
+
+impl PartialEq for LoxFunction {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.declaration, &other.declaration)
+            && Rc::ptr_eq(&self.closure, &other.closure)
+    }
+}
+
+impl std::fmt::Display for LoxFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "<fn {name}>"),
+            None => write!(f, "<fn>"),
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use super::{Interpreter, LoxFunction, LoxObj};
+use crate::parser::ast::{Expr, Stmt};
+use crate::rutox_error::RutoxError;
+
+/// Code that runs around what the interpreter executes, e.g. the debugger
+/// or `--trace`. Every method does nothing unless overridden.
+pub trait Hook {
+    /// Runs before a program (the script, a REPL entry or an imported
+    /// module) starts running, with all of its statements.
+    fn program_loaded(&mut self, _interpreter: &Interpreter, _stmts: &[Stmt]) {}
+
+    /// Runs before every statement, with the interpreter paused at it.
+    fn before_stmt(
+        &mut self,
+        _interpreter: &mut Interpreter,
+        _stmt: &Stmt,
+    ) -> Result<(), RutoxError> {
+        Ok(())
+    }
+
+    /// Runs after every statement, however it ended.
+    fn after_stmt(&mut self, _interpreter: &Interpreter, _stmt: &Stmt) {}
+
+    /// Runs after every expression that evaluates without an error.
+    fn after_expr(&mut self, _interpreter: &Interpreter, _expr: &Expr, _value: &LoxObj) {}
+
+    /// Runs when a call starts running the function's body.
+    fn enter_function(&mut self, _interpreter: &Interpreter, _function: &LoxFunction) {}
+
+    /// Runs when the function's body is done, however it ended.
+    fn exit_function(&mut self, _interpreter: &Interpreter, _function: &LoxFunction) {}
+}
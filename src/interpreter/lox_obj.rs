@@ -1,5 +1,9 @@
+use super::{LoxFunction, LoxModule, NativeFunction};
+use crate::error_code::ErrorCode;
 use crate::parser::ast::LiteralData;
 use crate::scanner::src_location::SrcLocation;
+use crate::vm::Closure;
+use std::rc::Rc;
 
 // Why should lox objs have a location?
 #[derive(Clone, PartialEq)]
@@ -8,6 +12,14 @@ pub enum LoxObj {
     Number(f64, SrcLocation),
     Bool(bool, SrcLocation),
     Nil(SrcLocation),
+    Function(Rc<LoxFunction>, SrcLocation),
+    /// A function compiled for the VM, with the variables it captured.
+    Closure(Rc<Closure>, SrcLocation),
+    Native(Rc<NativeFunction>, SrcLocation),
+    /// A runtime error caught by a `catch` clause. The location is where the
+    /// error happened.
+    Error(ErrorCode, String, SrcLocation),
+    Module(Rc<LoxModule>, SrcLocation),
 }
 
 impl From<LiteralData> for LoxObj {
@@ -28,6 +40,11 @@ impl std::fmt::Display for LoxObj {
             LoxObj::Number(n, _) => write!(f, "{n}"),
             LoxObj::Bool(bool, _) => write!(f, "{bool}"),
             LoxObj::Nil(_) => write!(f, "nil"),
+            LoxObj::Function(function, _) => write!(f, "{function}"),
+            LoxObj::Closure(closure, _) => write!(f, "{closure}"),
+            LoxObj::Native(native, _) => write!(f, "{native}"),
+            LoxObj::Error(_, message, _) => write!(f, "{message}"),
+            LoxObj::Module(module, _) => write!(f, "{module}"),
         }
     }
 }
@@ -39,18 +56,44 @@ impl std::fmt::Debug for LoxObj {
             LoxObj::Number(n, _) => write!(f, "number {n}"),
             LoxObj::Bool(bool, _) => write!(f, "boolean {bool}"),
             LoxObj::Nil(_) => write!(f, "nil"),
+            LoxObj::Function(function, _) => write!(f, "function {function}"),
+            LoxObj::Closure(closure, _) => write!(f, "function {closure}"),
+            LoxObj::Native(native, _) => write!(f, "function {native}"),
+            LoxObj::Error(code, message, location) => {
+                write!(f, "error {code} \"{message}\" at {location}")
+            }
+            LoxObj::Module(module, _) => write!(f, "module {}", module.name),
         }
     }
 }
 
 use colored::*;
 impl LoxObj {
+    /// The type of the value, as used in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            LoxObj::String(_, _) => "a string",
+            LoxObj::Number(_, _) => "a number",
+            LoxObj::Bool(_, _) => "a boolean",
+            LoxObj::Nil(_) => "nil",
+            LoxObj::Function(_, _) | LoxObj::Closure(_, _) | LoxObj::Native(_, _) => "a function",
+            LoxObj::Error(_, _, _) => "an error",
+            LoxObj::Module(_, _) => "a module",
+        }
+    }
+
+    #[allow(dead_code)]
     pub fn as_colored_string(&self) -> ColoredString {
         match self {
             LoxObj::String(_, _) => format!("{self}").green(),
             LoxObj::Number(_, _) => format!("{self}").blue().bold(),
             LoxObj::Bool(_, _) => format!("{self}").cyan().bold(),
             LoxObj::Nil(_) => format!("{self}").cyan().bold(),
+            LoxObj::Function(_, _) | LoxObj::Closure(_, _) | LoxObj::Native(_, _) => {
+                format!("{self}").magenta()
+            }
+            LoxObj::Error(_, _, _) => format!("{self}").red(),
+            LoxObj::Module(_, _) => format!("{self}").magenta(),
         }
     }
 }
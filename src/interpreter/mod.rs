@@ -1,61 +1,207 @@
 mod env;
+mod flow;
+mod function;
+mod hook;
 mod lox_obj;
+mod module;
+mod native;
+pub mod operators;
+use crate::error_code::ErrorCode;
 use crate::parser::{
-    ast::{BinaryData, BinaryOp, Expr, LiteralData, LogicalOp, Stmt, UnaryData, UnaryOp},
+    ast::{
+        BinaryData, CallData, Expr, FunctionData, ImportData, LiteralData, LogicalOp, Stmt,
+        TestData, TryData, UnaryData, UnaryOp,
+    },
     visitors::{ExprVisitor, StmtVisitor},
 };
-use crate::rutox_error::RutoxError;
-use crate::scanner::{token::Token, SrcLocation};
+use crate::rutox_error::{RutoxError, StackFrame};
+use crate::scanner::{token::Token, FileId, SourceMap, SrcLocation};
+use crate::suggestion;
 pub use env::Env;
+pub use flow::Flow;
+pub use function::LoxFunction;
+pub use hook::Hook;
 pub use lox_obj::LoxObj;
-use std::cmp::Ordering;
+pub use module::{Import, LoxModule, Modules};
+pub use native::NativeFunction;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// How deep function calls can nest before we give up with a stack overflow,
+/// instead of overflowing the interpreter's own stack.
+const MAX_CALL_DEPTH: usize = 255;
+
+/// The stack the tree-walker needs for any program that parses: each of up
+/// to `MAX_CALL_DEPTH` calls can run code nested as deeply as the parser
+/// allows, which takes about 128 MiB in debug builds. Run the interpreter on
+/// a thread with this much stack, as `rutox` does.
+pub const STACK_SIZE: usize = 1024 * 1024 * 1024;
+
+/// Receives the lines `print` writes, e.g. to send them to an editor.
+pub type Output = Box<dyn FnMut(&str)>;
 
 pub struct Interpreter {
-    env: Env,
+    env: Rc<RefCell<Env>>,
+    call_depth: usize,
+    modules: Modules,
+    /// Where imported files are registered, so their errors can be shown.
+    source_map: Rc<RefCell<SourceMap>>,
+    hook: Option<Box<dyn Hook>>,
+    /// Where `print` writes its lines, instead of stdout.
+    output: Option<Output>,
 }
 
-impl StmtVisitor<()> for Interpreter {
+impl StmtVisitor<Flow> for Interpreter {
+    fn visit_stmt(&mut self, stmt: &Stmt) -> Result<Flow, RutoxError> {
+        self.run_hook(|hook, interpreter| hook.before_stmt(interpreter, stmt))
+            .transpose()?;
+        let flow = self.walk_stmt(stmt);
+        self.run_hook(|hook, interpreter| hook.after_stmt(interpreter, stmt));
+
+        flow
+    }
+
     fn visit_if_stmt(
         &mut self,
         cond: &Expr,
-        then_branch: &Box<Stmt>,
+        then_branch: &Stmt,
         else_branch: &Option<Box<Stmt>>,
         _location: &SrcLocation,
-    ) -> Result<(), RutoxError> {
+    ) -> Result<Flow, RutoxError> {
         let cond = self.visit_expr(cond)?;
 
         if self.is_truthy(&cond) {
-            self.visit_stmt(then_branch)?;
-
-            Ok(())
+            self.visit_stmt(then_branch)
         } else if let Some(else_branch) = else_branch {
-            self.visit_stmt(else_branch)?;
-
-            Ok(())
+            self.visit_stmt(else_branch)
         } else {
-            Ok(())
+            Ok(Flow::Normal)
         }
     }
 
-    fn visit_print_stmt(&mut self, expr: &Expr, _location: &SrcLocation) -> Result<(), RutoxError> {
+    fn visit_while_stmt(
+        &mut self,
+        cond: &Expr,
+        body: &Stmt,
+        _location: &SrcLocation,
+    ) -> Result<Flow, RutoxError> {
+        loop {
+            let cond = self.visit_expr(cond)?;
+            if !self.is_truthy(&cond) {
+                break;
+            }
+
+            match self.visit_stmt(body)? {
+                Flow::Break => break,
+                Flow::Continue | Flow::Normal => (),
+                flow @ Flow::Return(_) => return Ok(flow),
+            }
+        }
+
+        Ok(Flow::Normal)
+    }
+
+    fn visit_break_stmt(&mut self, _location: &SrcLocation) -> Result<Flow, RutoxError> {
+        Ok(Flow::Break)
+    }
+
+    fn visit_continue_stmt(&mut self, _location: &SrcLocation) -> Result<Flow, RutoxError> {
+        Ok(Flow::Continue)
+    }
+
+    fn visit_function_stmt(&mut self, function: &Rc<FunctionData>) -> Result<Flow, RutoxError> {
+        let name = function
+            .name
+            .as_ref()
+            .expect("A function statement should always have a name");
+        let value = LoxObj::Function(
+            Rc::new(LoxFunction::new(function.clone(), self.env.clone())),
+            function.location.clone(),
+        );
+
+        self.env.borrow_mut().define(&name.lexeme, value);
+
+        Ok(Flow::Normal)
+    }
+
+    fn visit_return_stmt(
+        &mut self,
+        value: &Option<Expr>,
+        location: &SrcLocation,
+    ) -> Result<Flow, RutoxError> {
+        let value = match value {
+            Some(value) => self.visit_expr(value)?,
+            None => LoxObj::Nil(location.clone()),
+        };
+
+        Ok(Flow::Return(value))
+    }
+
+    fn visit_throw_stmt(
+        &mut self,
+        value: &Expr,
+        location: &SrcLocation,
+    ) -> Result<Flow, RutoxError> {
+        let value = self.visit_expr(value)?;
+
+        Err(Self::thrown(value, location))
+    }
+
+    fn visit_try_stmt(&mut self, try_data: &TryData) -> Result<Flow, RutoxError> {
+        let mut result = self.visit_block_stmt(&try_data.body, &try_data.location);
+
+        if let (Err(error), Some(catch)) = (&result, &try_data.catch) {
+            if let Some(caught) = Self::caught(error) {
+                let mut env = Env::new(self.env.clone());
+                env.define(&catch.name.lexeme, caught);
+
+                result = self.execute_block(&catch.body, Rc::new(RefCell::new(env)));
+            }
+        }
+
+        if let Some(finally) = &try_data.finally {
+            // A `finally` that jumps or fails overrides whatever happened before it
+            match self.visit_block_stmt(finally, &try_data.location)? {
+                Flow::Normal => (),
+                flow => return Ok(flow),
+            }
+        }
+
+        result
+    }
+
+    fn visit_print_stmt(
+        &mut self,
+        expr: &Expr,
+        _location: &SrcLocation,
+    ) -> Result<Flow, RutoxError> {
         let value = self.visit_expr(expr)?;
-        println!("{value}");
+        match &mut self.output {
+            Some(output) => output(&format!("{value}\n")),
+            None => println!("{value}"),
+        }
 
-        Ok(())
+        Ok(Flow::Normal)
     }
 
     fn visit_block_stmt(
         &mut self,
         stmts: &[Stmt],
         _location: &SrcLocation,
-    ) -> Result<(), RutoxError> {
-        self.execute_block(stmts, Env::new(Box::new(self.env.clone())))
+    ) -> Result<Flow, RutoxError> {
+        let env = Env::new(self.env.clone());
+
+        self.execute_block(stmts, Rc::new(RefCell::new(env)))
     }
 
-    fn visit_expr_stmt(&mut self, expr: &Expr, _location: &SrcLocation) -> Result<(), RutoxError> {
+    fn visit_expr_stmt(
+        &mut self,
+        expr: &Expr,
+        _location: &SrcLocation,
+    ) -> Result<Flow, RutoxError> {
         self.visit_expr(expr)?;
 
-        Ok(())
+        Ok(Flow::Normal)
     }
 
     fn visit_var_stmt(
@@ -63,20 +209,43 @@ impl StmtVisitor<()> for Interpreter {
         name: &Token,
         initializer: &Option<Expr>,
         location: &SrcLocation,
-    ) -> Result<(), RutoxError> {
+    ) -> Result<Flow, RutoxError> {
         let mut value = LoxObj::Nil(location.clone());
 
         if let Some(initial_val) = initializer {
             value = self.visit_expr(initial_val)?;
         }
 
-        self.env.define(&name.lexeme, value);
+        self.env.borrow_mut().define(&name.lexeme, value);
 
-        Ok(())
+        Ok(Flow::Normal)
+    }
+
+    fn visit_import_stmt(&mut self, import: &ImportData) -> Result<Flow, RutoxError> {
+        let module = self.load_module(import)?;
+
+        self.env.borrow_mut().define(
+            &import.name,
+            LoxObj::Module(module, import.location.clone()),
+        );
+
+        Ok(Flow::Normal)
+    }
+
+    /// Tests only run under `rutox test`, which runs their bodies itself.
+    fn visit_test_stmt(&mut self, _test: &TestData) -> Result<Flow, RutoxError> {
+        Ok(Flow::Normal)
     }
 }
 
 impl ExprVisitor<LoxObj> for Interpreter {
+    fn visit_expr(&mut self, expr: &Expr) -> Result<LoxObj, RutoxError> {
+        let value = self.walk_expr(expr)?;
+        self.run_hook(|hook, interpreter| hook.after_expr(interpreter, expr, &value));
+
+        Ok(value)
+    }
+
     fn visit_logical_expr(
         &mut self,
         left: &Expr,
@@ -102,7 +271,7 @@ impl ExprVisitor<LoxObj> for Interpreter {
         self.visit_expr(right)
     }
 
-    fn visit_literal_expr(&self, literal: &LiteralData) -> Result<LoxObj, RutoxError> {
+    fn visit_literal_expr(&mut self, literal: &LiteralData) -> Result<LoxObj, RutoxError> {
         Ok(literal.clone().into())
     }
 
@@ -120,170 +289,120 @@ impl ExprVisitor<LoxObj> for Interpreter {
             UnaryOp::Minus(location) => {
                 let value = self.visit_expr(&unary.expr)?;
 
-                match value {
-                    LoxObj::Number(number, _) => Ok(LoxObj::Number(-number, location.clone())),
-                    other => Err(RutoxError::Runtime(
-                        format!(
-                            "Unary operator `-` can only be applied to numbers, but got {other}"
-                        ),
-                        location.clone(),
-                    )),
-                }
+                operators::negate(&value, location)
+                    .map_err(|invalid| invalid.error(unary.span(), &[(&value, unary.expr.span())]))
             }
         }
     }
 
     fn visit_binary_expr(&mut self, binary: &BinaryData) -> Result<LoxObj, RutoxError> {
-        match &binary.operator {
-            BinaryOp::EqualEqual(location) => {
-                let a = self.visit_expr(&binary.left)?;
-                let b = self.visit_expr(&binary.right)?;
-
-                Ok(LoxObj::Bool(self.is_equal(a, b), location.clone()))
-            }
-            BinaryOp::BangEqual(location) => {
-                let a = self.visit_expr(&binary.left)?;
-                let b = self.visit_expr(&binary.right)?;
-
-                Ok(LoxObj::Bool(!self.is_equal(a, b), location.clone()))
-            }
-            BinaryOp::Greater(location)
-            | BinaryOp::Less(location)
-            | BinaryOp::GreaterEqual(location)
-            | BinaryOp::LessEqual(location) => {
-                let a = &self.visit_expr(&binary.left)?;
-                let b = &self.visit_expr(&binary.right)?;
-                let ordering = self.compare(a, b).ok_or_else(|| {
-                    RutoxError::Runtime(
-                        format!("Cannot compare {:?} and {:?}", a, b),
-                        location.clone(),
-                    )
-                })?;
-
-                match &binary.operator {
-                    BinaryOp::Greater(_) => Ok(LoxObj::Bool(
-                        ordering == Ordering::Greater,
-                        location.clone(),
-                    )),
-                    BinaryOp::GreaterEqual(_) => {
-                        Ok(LoxObj::Bool(ordering != Ordering::Less, location.clone()))
-                    }
-                    BinaryOp::Less(_) => {
-                        Ok(LoxObj::Bool(ordering == Ordering::Less, location.clone()))
-                    }
-                    BinaryOp::LessEqual(_) => Ok(LoxObj::Bool(
-                        ordering != Ordering::Greater,
-                        location.clone(),
-                    )),
-                    _ => panic!("Unreachable"),
-                }
-            }
-            BinaryOp::Plus(location) => {
-                let a = &self.visit_expr(&binary.left)?;
-                let b = &self.visit_expr(&binary.right)?;
-
-                match (a, b) {
-                    (LoxObj::Number(a, _), LoxObj::Number(b, _)) => {
-                        Ok(LoxObj::Number(a + b, location.clone()))
-                    }
-                    (LoxObj::String(s1, _), LoxObj::String(s2, _)) => {
-                        Ok(LoxObj::String(format!("{}{}", s1, s2), location.clone()))
-                    }
-                    _ => Err(RutoxError::Runtime(
-                        format!("Cannot compare {:?} and {:?}", a, b),
-                        location.clone(),
-                    )),
-                }
-            }
-            BinaryOp::Minus(location) => {
-                let a = &self.visit_expr(&binary.left)?;
-                let b = &self.visit_expr(&binary.right)?;
-
-                match (a, b) {
-                    (LoxObj::Number(a, _), LoxObj::Number(b, _)) => {
-                        Ok(LoxObj::Number(a - b, location.clone()))
-                    }
-                    _ => Err(RutoxError::Runtime(
-                        format!("Cannot subtract {:?} and {:?}", a, b),
-                        location.clone(),
-                    )),
-                }
-            }
-            BinaryOp::Div(location) => {
-                let a = &self.visit_expr(&binary.left)?;
-                let b = &self.visit_expr(&binary.right)?;
-
-                match (a, b) {
-                    (LoxObj::Number(a, _), LoxObj::Number(b, _)) => {
-                        Ok(LoxObj::Number(a / b, location.clone()))
-                    }
-                    _ => Err(RutoxError::Runtime(
-                        format!("Cannot divide {:?} and {:?}", a, b),
-                        location.clone(),
-                    )),
-                }
-            }
-            BinaryOp::Mul(location) => {
-                let a = &self.visit_expr(&binary.left)?;
-                let b = &self.visit_expr(&binary.right)?;
-
-                match (a, b) {
-                    (LoxObj::Number(a, _), LoxObj::Number(b, _)) => {
-                        Ok(LoxObj::Number(a * b, location.clone()))
-                    }
-                    (LoxObj::String(s, _), LoxObj::Number(times, _)) => Ok(LoxObj::String(
-                        s.repeat((*times) as usize),
-                        location.clone(),
-                    )),
-                    _ => Err(RutoxError::Runtime(
-                        format!("Cannot multiply {:?} and {:?}", a, b),
-                        location.clone(),
-                    )),
-                }
-            }
-        }
+        let a = self.visit_expr(&binary.left)?;
+        let b = self.visit_expr(&binary.right)?;
+
+        operators::binary((&binary.operator).into(), &a, &b, &binary.location).map_err(|invalid| {
+            invalid.error(
+                binary.span(),
+                &[(&a, binary.left.span()), (&b, binary.right.span())],
+            )
+        })
     }
 
     fn visit_variable_expr(
-        &self,
+        &mut self,
         name: &Token,
         location: &SrcLocation,
     ) -> Result<LoxObj, RutoxError> {
-        match self.env.get(&name.lexeme) {
-            Some(value) => Ok(value.clone()),
-            None => Err(RutoxError::Runtime(
-                format!("Undefined variable `{}`", name.lexeme),
-                location.clone(),
-            )),
-        }
+        let value = self.env.borrow().get(&name.lexeme);
+
+        value.ok_or_else(|| {
+            Self::undefined_variable(&name.lexeme, location, &self.env.borrow().names())
+        })
     }
 
     fn visit_assign_expr(
         &mut self,
         name: &Token,
-        value: Box<Expr>,
+        value: &Expr,
         location: &SrcLocation,
     ) -> Result<LoxObj, RutoxError> {
-        let value = self.visit_expr(&value)?;
+        let value = self.visit_expr(value)?;
 
-        match self.env.assign(&name.lexeme, value.clone()) {
-            Ok(_) => Ok(value),
-            Err(_) => Err(RutoxError::Runtime(
-                format!("Undefined variable `{}`", name.lexeme),
-                location.clone(),
+        let assigned = self.env.borrow_mut().assign(&name.lexeme, value.clone());
+
+        match assigned {
+            true => Ok(value),
+            false => Err(Self::undefined_variable(
+                &name.lexeme,
+                location,
+                &self.env.borrow().names(),
             )),
         }
     }
+
+    fn visit_call_expr(&mut self, call: &CallData) -> Result<LoxObj, RutoxError> {
+        let callee = self.visit_expr(&call.callee)?;
+        let args = call
+            .args
+            .iter()
+            .map(|arg| self.visit_expr(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match callee {
+            LoxObj::Function(function, _) => self.call_function(&function, args, &call.location),
+            LoxObj::Native(native, _) => {
+                Self::check_arity(native.arity, args.len(), &call.location)?;
+                (native.function)(self, args, &call.location)
+            }
+            other => Err(RutoxError::Runtime(
+                ErrorCode::NotCallable,
+                format!("Can only call functions, but got {:?}", other),
+                call.callee.span(),
+            )),
+        }
+    }
+
+    fn visit_lambda_expr(&mut self, function: &Rc<FunctionData>) -> Result<LoxObj, RutoxError> {
+        Ok(LoxObj::Function(
+            Rc::new(LoxFunction::new(function.clone(), self.env.clone())),
+            function.location.clone(),
+        ))
+    }
+
+    fn visit_get_expr(
+        &mut self,
+        object: &Expr,
+        name: &Token,
+        location: &SrcLocation,
+    ) -> Result<LoxObj, RutoxError> {
+        let object = self.visit_expr(object)?;
+
+        Self::get_member(object, &name.lexeme, location)
+    }
 }
 
 impl Interpreter {
-    pub fn new() -> Self {
+    pub fn new(source_map: Rc<RefCell<SourceMap>>) -> Self {
         Interpreter {
-            env: Env::default(),
+            env: Rc::new(RefCell::new(Env::default())),
+            call_depth: 0,
+            modules: Modules::default(),
+            source_map,
+            hook: None,
+            output: None,
+        }
+    }
+
+    /// An interpreter for the script at `path`, which imports are resolved
+    /// relative to.
+    pub fn for_file(path: &str, source_map: Rc<RefCell<SourceMap>>) -> Self {
+        Interpreter {
+            modules: Modules::for_file(path),
+            ..Self::new(source_map)
         }
     }
 
     pub fn interpret(&mut self, stmts: Vec<Stmt>) -> Result<(), RutoxError> {
+        self.run_hook(|hook, interpreter| hook.program_loaded(interpreter, &stmts));
         for stmt in stmts {
             self.visit_stmt(&stmt)?;
         }
@@ -291,44 +410,235 @@ impl Interpreter {
         Ok(())
     }
 
-    fn execute_block(&mut self, stmts: &[Stmt], new_env: Env) -> Result<(), RutoxError> {
-        let old_env = self.env.clone();
+    fn call_function(
+        &mut self,
+        function: &LoxFunction,
+        args: Vec<LoxObj>,
+        location: &SrcLocation,
+    ) -> Result<LoxObj, RutoxError> {
+        Self::check_arity(function.arity(), args.len(), location)?;
+        Self::check_call_depth(self.call_depth, location)?;
+
+        let mut env = Env::new(function.closure.clone());
+        for (param, arg) in function.declaration.params.iter().zip(args) {
+            env.define(&param.lexeme, arg);
+        }
+
+        self.call_depth += 1;
+        self.run_hook(|hook, interpreter| hook.enter_function(interpreter, function));
+        let result = self.execute_block(&function.declaration.body, Rc::new(RefCell::new(env)));
+        self.run_hook(|hook, interpreter| hook.exit_function(interpreter, function));
+        self.call_depth -= 1;
+
+        let flow = result.map_err(|error| {
+            error.traced(StackFrame {
+                function_name: function.to_string(),
+                call_location: location.clone(),
+            })
+        })?;
+
+        match flow {
+            Flow::Return(value) => Ok(value),
+            _ => Ok(LoxObj::Nil(location.clone())),
+        }
+    }
+
+    pub fn check_arity(
+        expected: usize,
+        given: usize,
+        location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        if given == expected {
+            return Ok(());
+        }
+
+        Err(RutoxError::Runtime(
+            ErrorCode::WrongArity,
+            format!("Expected {expected} arguments but got {given}"),
+            location.clone(),
+        ))
+    }
+
+    /// Fails once `call_depth` calls are running and another one can't start.
+    pub fn check_call_depth(call_depth: usize, location: &SrcLocation) -> Result<(), RutoxError> {
+        if call_depth < MAX_CALL_DEPTH {
+            return Ok(());
+        }
+
+        Err(RutoxError::Runtime(
+            ErrorCode::StackOverflow,
+            "Stack overflow".to_string(),
+            location.clone(),
+        ))
+    }
+
+    fn load_module(&mut self, import: &ImportData) -> Result<Rc<LoxModule>, RutoxError> {
+        let (path, stmts) = match self.modules.import(import, &self.source_map)? {
+            Import::Loaded(module) => return Ok(module),
+            Import::Parsed(path, stmts) => (path, stmts),
+        };
 
-        self.env = new_env;
+        let previous_dir = self.modules.enter(&path);
+        let module_env = Rc::new(RefCell::new(Env::default()));
+        self.run_hook(|hook, interpreter| hook.program_loaded(interpreter, &stmts));
+        let result = self.execute_block(&stmts, module_env.clone());
+        self.modules.leave(previous_dir);
+        result?;
 
+        Ok(self.modules.finish(path, &import.name, module_env))
+    }
+
+    /// Runs `stmts` inside `new_env`. The previous env is restored however the
+    /// block is left (normally, through a `break`/`continue`/`return` or an
+    /// error).
+    fn execute_block(
+        &mut self,
+        stmts: &[Stmt],
+        new_env: Rc<RefCell<Env>>,
+    ) -> Result<Flow, RutoxError> {
+        let previous = std::mem::replace(&mut self.env, new_env);
+
+        let mut result = Ok(Flow::Normal);
         for stmt in stmts {
             match self.visit_stmt(stmt) {
-                Ok(_) => (),
-                Err(e) => {
-                    self.env = old_env;
-                    return Err(e);
+                Ok(Flow::Normal) => (),
+                other => {
+                    result = other;
+                    break;
                 }
             }
         }
-        self.env = old_env;
 
-        Ok(())
+        self.env = previous;
+
+        result
     }
 
-    fn is_truthy(&self, obj: &LoxObj) -> bool {
-        !matches!(obj, LoxObj::Bool(false, _) | LoxObj::Nil(_))
+    pub fn source_map(&self) -> std::cell::Ref<'_, SourceMap> {
+        self.source_map.borrow()
+    }
+
+    pub fn source_map_mut(&self) -> std::cell::RefMut<'_, SourceMap> {
+        self.source_map.borrow_mut()
+    }
+
+    /// Makes `native` a global, e.g. the assertions of `rutox test`.
+    pub fn define_native(&mut self, native: NativeFunction) {
+        // Natives have no source of their own
+        let location = SrcLocation::new(0, 0, FileId::default());
+        self.env
+            .borrow_mut()
+            .define(native.name, LoxObj::Native(Rc::new(native), location));
+    }
+
+    pub fn set_hook(&mut self, hook: Box<dyn Hook>) {
+        self.hook = Some(hook);
+    }
+
+    pub fn set_output(&mut self, output: Output) {
+        self.output = Some(output);
+    }
+
+    /// Calls `f` with the hook, if there is one. The hook is taken out while
+    /// it runs, so code it runs itself (e.g. functions a debugger watch
+    /// expression calls) doesn't call it again.
+    fn run_hook<T>(&mut self, f: impl FnOnce(&mut dyn Hook, &mut Self) -> T) -> Option<T> {
+        let mut hook = self.hook.take()?;
+        let result = f(hook.as_mut(), self);
+        self.hook = Some(hook);
+
+        Some(result)
     }
 
-    fn is_equal(&self, a: LoxObj, b: LoxObj) -> bool {
-        match (a, b) {
-            (LoxObj::Nil(_), LoxObj::Nil(_)) => true,
-            (LoxObj::Bool(b1, _), LoxObj::Bool(b2, _)) => b1 == b2,
-            (LoxObj::Number(n1, _), LoxObj::Number(n2, _)) => n1 == n2,
-            (LoxObj::String(s1, _), LoxObj::String(s2, _)) => s1 == s2,
-            _ => false,
+    /// The scope of the statement being run.
+    pub fn env(&self) -> Rc<RefCell<Env>> {
+        self.env.clone()
+    }
+
+    /// How many function calls are being run, 0 at the top level.
+    pub fn call_depth(&self) -> usize {
+        self.call_depth
+    }
+
+    /// The error for `name` not being defined, suggesting a similar one of
+    /// `names` if there is one.
+    pub fn undefined_variable(name: &str, location: &SrcLocation, names: &[String]) -> RutoxError {
+        let error = RutoxError::Runtime(
+            ErrorCode::UndefinedVariable,
+            format!("Undefined variable `{name}`"),
+            location.clone(),
+        );
+
+        match suggestion::did_you_mean(name, names.iter().map(String::as_str)) {
+            Some(similar) => error.with_help(format!("did you mean `{similar}`?")),
+            None => error,
+        }
+    }
+
+    /// `object.name`, which only modules and caught errors have.
+    pub fn get_member(
+        object: LoxObj,
+        name: &str,
+        location: &SrcLocation,
+    ) -> Result<LoxObj, RutoxError> {
+        match object {
+            LoxObj::Module(module, _) => module.get(name).ok_or_else(|| {
+                RutoxError::Runtime(
+                    ErrorCode::UnknownModuleMember,
+                    format!("Module `{}` has no binding `{name}`", module.name),
+                    location.clone(),
+                )
+            }),
+            LoxObj::Error(code, message, error_location) => match name {
+                "message" => Ok(LoxObj::String(message, location.clone())),
+                "code" => Ok(LoxObj::String(code.code().to_string(), location.clone())),
+                "line" => Ok(LoxObj::Number(error_location.line as f64, location.clone())),
+                "column" => Ok(LoxObj::Number(error_location.col as f64, location.clone())),
+                _ => Err(RutoxError::Runtime(
+                    ErrorCode::NoProperties,
+                    format!("Errors have no property `{name}`"),
+                    location.clone(),
+                )
+                .with_help("errors have `message`, `code`, `line` and `column`".to_string())),
+            },
+            other => Err(RutoxError::Runtime(
+                ErrorCode::NoProperties,
+                format!(
+                    "Only modules and errors have properties, but got {:?}",
+                    other
+                ),
+                location.clone(),
+            )),
+        }
+    }
+
+    /// The error `throw value` raises.
+    pub fn thrown(value: LoxObj, location: &SrcLocation) -> RutoxError {
+        match value {
+            // Rethrowing a caught runtime error keeps its original message and location
+            LoxObj::Error(code, message, error_location) => {
+                RutoxError::Runtime(code, message, error_location)
+            }
+            value => RutoxError::Thrown(value, location.clone()),
         }
     }
 
-    fn compare(&self, a: &LoxObj, b: &LoxObj) -> Option<Ordering> {
-        match (a, b) {
-            (LoxObj::Number(a, _), LoxObj::Number(b, _)) => a.partial_cmp(b),
-            (LoxObj::String(a, _), LoxObj::String(b, _)) => a.partial_cmp(b),
+    /// What a `catch` clause binds for `error`, if it catches it at all.
+    pub fn caught(error: &RutoxError) -> Option<LoxObj> {
+        match error.untraced() {
+            RutoxError::Runtime(code, message, location) => {
+                Some(LoxObj::Error(*code, message.clone(), location.clone()))
+            }
+            RutoxError::Thrown(value, _) => Some(value.clone()),
             _ => None,
         }
     }
+
+    pub fn is_truthy(&self, obj: &LoxObj) -> bool {
+        operators::is_truthy(obj)
+    }
+
+    pub fn is_equal(&self, a: LoxObj, b: LoxObj) -> bool {
+        operators::is_equal(&a, &b)
+    }
 }
@@ -0,0 +1,161 @@
+use super::{Env, LoxObj};
+use crate::error_code::ErrorCode;
+use crate::parser::{
+    ast::{ImportData, Stmt},
+    Parser,
+};
+use crate::rutox_error::RutoxError;
+use crate::scanner::{Scanner, SourceMap};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// An imported file. Its top-level bindings live in `env`, which is also the
+/// closure of the functions it declares.
+pub struct LoxModule {
+    pub name: String,
+    pub env: Rc<RefCell<Env>>,
+}
+
+impl LoxModule {
+    pub fn get(&self, name: &str) -> Option<LoxObj> {
+        self.env.borrow().get(name)
+    }
+}
+
+impl PartialEq for LoxModule {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.env, &other.env)
+    }
+}
+
+impl std::fmt::Display for LoxModule {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "<module {}>", self.name)
+    }
+}
+
+/// Finds and parses imported files, and keeps the modules they became so
+/// each file only runs once. Running them is up to the backend.
+#[derive(Default)]
+pub struct Modules {
+    /// Directory imports are resolved from, i.e. that of the file being run.
+    current_dir: PathBuf,
+    /// Every module imported so far.
+    loaded: HashMap<PathBuf, Rc<LoxModule>>,
+    /// Files currently being run, outermost first, to detect import cycles.
+    importing: Vec<PathBuf>,
+}
+
+/// What an import statement found.
+pub enum Import {
+    /// A module imported before.
+    Loaded(Rc<LoxModule>),
+    /// A file to run, then `finish` into a module.
+    Parsed(PathBuf, Vec<Stmt>),
+}
+
+impl Modules {
+    /// Imports resolved relative to the script at `path`.
+    pub fn for_file(path: &str) -> Self {
+        let path = Path::new(path);
+        let mut modules = Self::default();
+
+        if let Some(dir) = path.parent() {
+            modules.current_dir = dir.to_path_buf();
+        }
+        if let Ok(path) = path.canonicalize() {
+            modules.importing.push(path);
+        }
+
+        modules
+    }
+
+    pub fn import(
+        &self,
+        import: &ImportData,
+        source_map: &RefCell<SourceMap>,
+    ) -> Result<Import, RutoxError> {
+        let import_error = |code: ErrorCode, message: String| {
+            RutoxError::Runtime(code, message, import.location.clone())
+        };
+        let path = self
+            .current_dir
+            .join(&import.path)
+            .canonicalize()
+            .map_err(|err| {
+                import_error(
+                    ErrorCode::ImportFailed,
+                    format!("Could not import `{}`: {err}", import.path),
+                )
+            })?;
+
+        if let Some(module) = self.loaded.get(&path) {
+            return Ok(Import::Loaded(module.clone()));
+        }
+        if let Some(start) = self.importing.iter().position(|p| *p == path) {
+            let cycle = self.importing[start..]
+                .iter()
+                .chain(std::iter::once(&path))
+                .map(|p| display_path(p))
+                .collect::<Vec<_>>()
+                .join(" -> ");
+
+            return Err(import_error(
+                ErrorCode::ImportCycle,
+                format!("Import cycle detected: {cycle}"),
+            ));
+        }
+
+        let content = fs::read_to_string(&path).map_err(|err| {
+            import_error(
+                ErrorCode::ImportFailed,
+                format!("Could not import `{}`: {err}", import.path),
+            )
+        })?;
+        let file = source_map.borrow_mut().add(&display_path(&path), &content);
+        let stmts = Scanner::new(content, file)
+            .scan_tokens()
+            .and_then(|tokens| Parser::new(tokens).parse())?;
+
+        Ok(Import::Parsed(path, stmts))
+    }
+
+    /// Starts running the file at `path`, returning the directory to `leave`
+    /// back to.
+    pub fn enter(&mut self, path: &Path) -> PathBuf {
+        let module_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        self.importing.push(path.to_path_buf());
+
+        std::mem::replace(&mut self.current_dir, module_dir)
+    }
+
+    /// Stops running the innermost file being imported, however it ended.
+    pub fn leave(&mut self, previous_dir: PathBuf) {
+        self.importing.pop();
+        self.current_dir = previous_dir;
+    }
+
+    /// Keeps the bindings the file at `path` left in `env` as a module.
+    pub fn finish(&mut self, path: PathBuf, name: &str, env: Rc<RefCell<Env>>) -> Rc<LoxModule> {
+        let module = Rc::new(LoxModule {
+            name: name.to_string(),
+            env,
+        });
+        self.loaded.insert(path, module.clone());
+
+        module
+    }
+}
+
+/// `path` relative to the working directory, if it is inside it.
+fn display_path(path: &Path) -> String {
+    std::env::current_dir()
+        .ok()
+        .and_then(|cwd| path.strip_prefix(cwd).ok())
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
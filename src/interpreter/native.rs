@@ -0,0 +1,24 @@
+use super::{Interpreter, LoxObj};
+use crate::rutox_error::RutoxError;
+use crate::scanner::SrcLocation;
+
+/// A function implemented in Rust, such as the assertions `rutox test`
+/// defines.
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub arity: usize,
+    /// Called with the arguments and the location of the call.
+    pub function: fn(&mut Interpreter, Vec<LoxObj>, &SrcLocation) -> Result<LoxObj, RutoxError>,
+}
+
+impl std::fmt::Display for NativeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
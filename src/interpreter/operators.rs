@@ -0,0 +1,166 @@
+use super::LoxObj;
+use crate::error_code::ErrorCode;
+use crate::parser::ast::BinaryOp;
+use crate::rutox_error::RutoxError;
+use crate::scanner::SrcLocation;
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+/// A binary operator without where it is written, so the tree-walking
+/// interpreter and the VM can share what it does to values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl From<&BinaryOp> for Operator {
+    fn from(operator: &BinaryOp) -> Self {
+        match operator {
+            BinaryOp::EqualEqual(_) => Operator::Equal,
+            BinaryOp::BangEqual(_) => Operator::NotEqual,
+            BinaryOp::Greater(_) => Operator::Greater,
+            BinaryOp::GreaterEqual(_) => Operator::GreaterEqual,
+            BinaryOp::Less(_) => Operator::Less,
+            BinaryOp::LessEqual(_) => Operator::LessEqual,
+            BinaryOp::Plus(_) => Operator::Add,
+            BinaryOp::Minus(_) => Operator::Subtract,
+            BinaryOp::Mul(_) => Operator::Multiply,
+            BinaryOp::Div(_) => Operator::Divide,
+        }
+    }
+}
+
+/// Why an operator can't be applied to its operands.
+pub struct InvalidOperands {
+    message: String,
+    /// Operands this accepts aren't pointed at.
+    is_valid: fn(&LoxObj) -> bool,
+}
+
+impl InvalidOperands {
+    /// The error for the operation covering `span`, with a label on each
+    /// operand (its value and span) that is at fault.
+    pub fn error(self, span: SrcLocation, operands: &[(&LoxObj, SrcLocation)]) -> RutoxError {
+        let mut error = RutoxError::Runtime(ErrorCode::InvalidOperands, self.message, span);
+        let sides: &[&str] = match operands.len() {
+            1 => &[""],
+            _ => &["left ", "right "],
+        };
+
+        for ((operand, location), side) in operands.iter().zip(sides) {
+            if !(self.is_valid)(operand) {
+                error = error.with_label(
+                    format!("{side}operand is {} here", operand.type_name()),
+                    location.clone(),
+                );
+            }
+        }
+
+        error
+    }
+}
+
+pub fn is_truthy(value: &LoxObj) -> bool {
+    !matches!(value, LoxObj::Bool(false, _) | LoxObj::Nil(_))
+}
+
+pub fn is_equal(a: &LoxObj, b: &LoxObj) -> bool {
+    match (a, b) {
+        (LoxObj::Nil(_), LoxObj::Nil(_)) => true,
+        (LoxObj::Bool(b1, _), LoxObj::Bool(b2, _)) => b1 == b2,
+        (LoxObj::Number(n1, _), LoxObj::Number(n2, _)) => n1 == n2,
+        (LoxObj::String(s1, _), LoxObj::String(s2, _)) => s1 == s2,
+        (LoxObj::Function(f1, _), LoxObj::Function(f2, _)) => Rc::ptr_eq(f1, f2),
+        (LoxObj::Closure(c1, _), LoxObj::Closure(c2, _)) => Rc::ptr_eq(c1, c2),
+        (LoxObj::Native(n1, _), LoxObj::Native(n2, _)) => Rc::ptr_eq(n1, n2),
+        (LoxObj::Error(c1, m1, l1), LoxObj::Error(c2, m2, l2)) => c1 == c2 && m1 == m2 && l1 == l2,
+        (LoxObj::Module(m1, _), LoxObj::Module(m2, _)) => Rc::ptr_eq(m1, m2),
+        _ => false,
+    }
+}
+
+/// `-value`, located at the operator.
+pub fn negate(value: &LoxObj, location: &SrcLocation) -> Result<LoxObj, InvalidOperands> {
+    match value {
+        LoxObj::Number(number, _) => Ok(LoxObj::Number(-number, location.clone())),
+        other => Err(InvalidOperands {
+            message: format!("Unary operator `-` can only be applied to numbers, but got {other}"),
+            is_valid: |_| false,
+        }),
+    }
+}
+
+/// `a <operator> b`, located at the operator.
+pub fn binary(
+    operator: Operator,
+    a: &LoxObj,
+    b: &LoxObj,
+    location: &SrcLocation,
+) -> Result<LoxObj, InvalidOperands> {
+    let is_number = |obj: &LoxObj| matches!(obj, LoxObj::Number(_, _));
+    let invalid = |verb: &str, is_valid| InvalidOperands {
+        message: format!("Cannot {verb} {:?} and {:?}", a, b),
+        is_valid,
+    };
+
+    let value = match (operator, a, b) {
+        (Operator::Equal, _, _) => LoxObj::Bool(is_equal(a, b), location.clone()),
+        (Operator::NotEqual, _, _) => LoxObj::Bool(!is_equal(a, b), location.clone()),
+        (
+            Operator::Greater | Operator::GreaterEqual | Operator::Less | Operator::LessEqual,
+            _,
+            _,
+        ) => {
+            let ordering = compare(a, b).ok_or_else(|| invalid("compare", |_| false))?;
+            let result = match operator {
+                Operator::Greater => ordering == Ordering::Greater,
+                Operator::GreaterEqual => ordering != Ordering::Less,
+                Operator::Less => ordering == Ordering::Less,
+                _ => ordering != Ordering::Greater,
+            };
+
+            LoxObj::Bool(result, location.clone())
+        }
+        (Operator::Add, LoxObj::Number(a, _), LoxObj::Number(b, _)) => {
+            LoxObj::Number(a + b, location.clone())
+        }
+        (Operator::Add, LoxObj::String(s1, _), LoxObj::String(s2, _)) => {
+            LoxObj::String(format!("{}{}", s1, s2), location.clone())
+        }
+        (Operator::Add, _, _) => return Err(invalid("add", |_| false)),
+        (Operator::Subtract, LoxObj::Number(a, _), LoxObj::Number(b, _)) => {
+            LoxObj::Number(a - b, location.clone())
+        }
+        (Operator::Subtract, _, _) => return Err(invalid("subtract", is_number)),
+        (Operator::Divide, LoxObj::Number(a, _), LoxObj::Number(b, _)) => {
+            LoxObj::Number(a / b, location.clone())
+        }
+        (Operator::Divide, _, _) => return Err(invalid("divide", is_number)),
+        (Operator::Multiply, LoxObj::Number(a, _), LoxObj::Number(b, _)) => {
+            LoxObj::Number(a * b, location.clone())
+        }
+        (Operator::Multiply, LoxObj::String(s, _), LoxObj::Number(times, _)) => {
+            LoxObj::String(s.repeat((*times) as usize), location.clone())
+        }
+        (Operator::Multiply, _, _) => return Err(invalid("multiply", |_| false)),
+    };
+
+    Ok(value)
+}
+
+fn compare(a: &LoxObj, b: &LoxObj) -> Option<Ordering> {
+    match (a, b) {
+        (LoxObj::Number(a, _), LoxObj::Number(b, _)) => a.partial_cmp(b),
+        (LoxObj::String(a, _), LoxObj::String(b, _)) => a.partial_cmp(b),
+        _ => None,
+    }
+}
@@ -0,0 +1,24 @@
+//! The scanner, parser, interpreter and bytecode VM behind the `rutox` binary,
+//! and its tooling. Exposed as a library so they can be driven directly, e.g.
+//! by the fuzz targets in `fuzz/`.
+
+pub mod cli;
+pub mod color;
+pub mod coverage;
+pub mod debugger;
+pub mod error_code;
+pub mod error_format;
+pub mod exitcodes;
+pub mod formatter;
+pub mod interpreter;
+pub mod linter;
+pub mod lsp;
+pub mod parser;
+pub mod profiler;
+pub mod resolver;
+pub mod rutox_error;
+pub mod scanner;
+pub mod suggestion;
+pub mod test_runner;
+pub mod tracer;
+pub mod vm;
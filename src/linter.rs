@@ -0,0 +1,560 @@
+use crate::error_code::ErrorCode;
+use crate::parser::{
+    ast::{
+        BinaryData, BinaryOp, CallData, Expr, FunctionData, ImportData, LiteralData, LogicalOp,
+        Stmt, TestData, TryData, UnaryData, UnaryOp,
+    },
+    ast_printer::SexprPrinter,
+    visitors::{ExprVisitor, StmtVisitor},
+};
+use crate::resolver::Resolver;
+use crate::rutox_error::{RutoxError, Severity};
+use crate::scanner::{
+    token::{Comment, Token},
+    SrcLocation,
+};
+use crate::suggestion;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// A check made by `rutox lint`, on top of the resolver's warnings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    SelfAssignment,
+    SelfComparison,
+    NegativeRepeat,
+    EmptyBlock,
+    IdenticalBranches,
+    AssignmentInCondition,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 6] = [
+        Rule::SelfAssignment,
+        Rule::SelfComparison,
+        Rule::NegativeRepeat,
+        Rule::EmptyBlock,
+        Rule::IdenticalBranches,
+        Rule::AssignmentInCondition,
+    ];
+
+    /// How the rule is called in the config file and in `// rutox-ignore`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::SelfAssignment => "self-assignment",
+            Rule::SelfComparison => "self-comparison",
+            Rule::NegativeRepeat => "negative-repeat",
+            Rule::EmptyBlock => "empty-block",
+            Rule::IdenticalBranches => "identical-branches",
+            Rule::AssignmentInCondition => "assignment-in-condition",
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Rule::SelfAssignment => ErrorCode::SelfAssignment,
+            Rule::SelfComparison => ErrorCode::SelfComparison,
+            Rule::NegativeRepeat => ErrorCode::NegativeRepeat,
+            Rule::EmptyBlock => ErrorCode::EmptyBlock,
+            Rule::IdenticalBranches => ErrorCode::IdenticalBranches,
+            Rule::AssignmentInCondition => ErrorCode::AssignmentInCondition,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Rule> {
+        Self::ALL.into_iter().find(|rule| rule.name() == name)
+    }
+}
+
+/// Which rules are reported, and how seriously. Every rule is a warning
+/// unless the config file says otherwise, e.g.
+///
+/// ```json
+/// { "rules": { "empty-block": "off", "self-comparison": "error" } }
+/// ```
+#[derive(Default)]
+pub struct LintConfig {
+    /// `None` turns the rule off.
+    levels: HashMap<Rule, Option<Severity>>,
+}
+
+impl LintConfig {
+    /// Looked up in the current directory when `--config` is not given.
+    pub const FILE_NAME: &'static str = ".rutoxlint.json";
+
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let config: Value = serde_json::from_str(json).map_err(|err| err.to_string())?;
+        let mut levels = HashMap::new();
+
+        let rules = match config.get("rules") {
+            Some(Value::Object(rules)) => rules.clone(),
+            Some(_) => return Err("`rules` should be an object".to_string()),
+            None => Default::default(),
+        };
+        for (name, level) in rules {
+            let rule = Rule::from_name(&name).ok_or(format!("Unknown rule `{name}`"))?;
+            let level = match level.as_str() {
+                Some("off") => None,
+                Some("warn") => Some(Severity::Warning),
+                Some("error") => Some(Severity::Error),
+                _ => {
+                    return Err(format!(
+                    "Unknown level {level} for `{name}`, expected \"off\", \"warn\" or \"error\""
+                ))
+                }
+            };
+            levels.insert(rule, level);
+        }
+
+        Ok(Self { levels })
+    }
+
+    fn level(&self, rule: Rule) -> Option<Severity> {
+        self.levels
+            .get(&rule)
+            .copied()
+            .unwrap_or(Some(Severity::Warning))
+    }
+}
+
+/// Runs the resolver and every enabled rule over `stmts`, leaving out what
+/// `// rutox-ignore` comments silence.
+pub fn lint(stmts: &[Stmt], comments: &[Comment], config: &LintConfig) -> Vec<RutoxError> {
+    let mut linter = Linter {
+        config,
+        warnings: Resolver::new().resolve(stmts),
+    };
+    linter.lint_stmts(stmts);
+
+    let ignores: Vec<Ignore> = comments.iter().filter_map(Ignore::parse).collect();
+    let mut warnings = linter.warnings;
+    warnings.retain(|warning| !ignores.iter().any(|ignore| ignore.covers(warning)));
+    warnings.extend(ignores.iter().flat_map(Ignore::unknown_rules));
+    warnings.sort_by_key(|warning| {
+        let location = warning.location();
+
+        (location.line, location.col)
+    });
+
+    warnings
+}
+
+/// A `// rutox-ignore` comment. At the end of a line it silences that line,
+/// on a line of its own it silences the next one. It can be narrowed down
+/// to some rules or codes: `// rutox-ignore empty-block, W0001`.
+struct Ignore {
+    line: usize,
+    /// Whether no rules were named, so everything is ignored.
+    all: bool,
+    codes: Vec<ErrorCode>,
+    /// Names that are neither a rule nor a code, e.g. because of a typo.
+    unknown: Vec<String>,
+    location: SrcLocation,
+}
+
+impl Ignore {
+    const PREFIX: &'static str = "rutox-ignore";
+
+    fn parse(comment: &Comment) -> Option<Ignore> {
+        let text = comment.text.trim_start_matches('/').trim();
+        let rules = text.strip_prefix(Self::PREFIX)?;
+        if !rules.is_empty() && !rules.starts_with(char::is_whitespace) {
+            return None;
+        }
+
+        let mut codes = vec![];
+        let mut unknown = vec![];
+        let names: Vec<&str> = rules
+            .split([',', ' '])
+            .filter(|rule| !rule.is_empty())
+            .collect();
+        for name in &names {
+            let code = Rule::from_name(name)
+                .map(|rule| rule.code())
+                .or_else(|| ErrorCode::from_code(name));
+            match code {
+                Some(code) => codes.push(code),
+                None => unknown.push(name.to_string()),
+            }
+        }
+        let line = match comment.trailing {
+            true => comment.location.line,
+            false => comment.location.line + 1,
+        };
+
+        Some(Ignore {
+            line,
+            all: names.is_empty(),
+            codes,
+            unknown,
+            location: comment.location.clone(),
+        })
+    }
+
+    fn covers(&self, warning: &RutoxError) -> bool {
+        warning.location().line == self.line && (self.all || self.codes.contains(&warning.code()))
+    }
+
+    /// A warning for each name that is neither a rule nor a code.
+    fn unknown_rules(&self) -> Vec<RutoxError> {
+        self.unknown
+            .iter()
+            .map(|name| {
+                let warning = RutoxError::Diagnostic(
+                    Severity::Warning,
+                    ErrorCode::UnknownLintRule,
+                    format!("Unknown rule `{name}` in `rutox-ignore`"),
+                    self.location.clone(),
+                );
+
+                match suggestion::did_you_mean(name, Rule::ALL.map(|rule| rule.name())) {
+                    Some(rule) => warning.with_help(format!("did you mean `{rule}`?")),
+                    None => warning,
+                }
+            })
+            .collect()
+    }
+}
+
+struct Linter<'a> {
+    config: &'a LintConfig,
+    warnings: Vec<RutoxError>,
+}
+
+impl Linter<'_> {
+    /// The warning for `rule`, unless the config turns it off.
+    fn diagnostic(&self, rule: Rule, message: String, location: SrcLocation) -> Option<RutoxError> {
+        let severity = self.config.level(rule)?;
+
+        Some(RutoxError::Diagnostic(
+            severity,
+            rule.code(),
+            message,
+            location,
+        ))
+    }
+
+    fn check_condition(&mut self, cond: &Expr, keyword: &str) {
+        if let Expr::Assign(name, _, _) = cond {
+            let warning = self.diagnostic(
+                Rule::AssignmentInCondition,
+                format!("Assignment to `{}` in `{keyword}` condition", name.lexeme),
+                cond.span(),
+            );
+            self.warnings.extend(warning.map(|warning| {
+                warning
+                    .with_help("to compare the values, use `==`".to_string())
+                    .with_help(format!(
+                        "if the assignment is intentional, wrap it in parentheses: `{keyword} ((...))`"
+                    ))
+            }));
+        }
+    }
+
+    /// Lints the statements of a body, e.g. `what` is "`catch` block",
+    /// warning if there are none.
+    fn lint_body(&mut self, stmts: &[Stmt], what: &str, location: &SrcLocation) {
+        if stmts.is_empty() {
+            let warning =
+                self.diagnostic(Rule::EmptyBlock, format!("Empty {what}"), location.clone());
+            self.warnings.extend(warning);
+        }
+        self.lint_stmts(stmts);
+    }
+
+    fn lint_stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            // Nothing is fatal here, so the result is always `Ok`
+            let _ = self.visit_stmt(stmt);
+        }
+    }
+}
+
+fn strip_groupings(mut expr: &Expr) -> &Expr {
+    while let Expr::Grouping(inner, _) = expr {
+        expr = inner;
+    }
+
+    expr
+}
+
+/// Whether evaluating `expr` twice surely gives the same value, so that
+/// comparing it with itself is a mistake rather than a way of calling
+/// something twice.
+fn is_pure(expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal(_) | Expr::Variable(_, _) => true,
+        Expr::Grouping(inner, _) => is_pure(inner),
+        Expr::Unary(unary) => is_pure(&unary.expr),
+        Expr::Binary(binary) => is_pure(&binary.left) && is_pure(&binary.right),
+        Expr::Logical(left, _, right, _) => is_pure(left) && is_pure(right),
+        Expr::Get(object, _, _) => is_pure(object),
+        Expr::Assign(_, _, _) | Expr::Call(_) | Expr::Lambda(_) => false,
+    }
+}
+
+fn is_negative_number(expr: &Expr) -> bool {
+    match strip_groupings(expr) {
+        Expr::Literal(LiteralData::Number(n, _)) => *n < 0.0,
+        Expr::Unary(UnaryData {
+            operator: UnaryOp::Minus(_),
+            expr,
+            ..
+        }) => matches!(
+            strip_groupings(expr),
+            Expr::Literal(LiteralData::Number(n, _)) if *n > 0.0
+        ),
+        _ => false,
+    }
+}
+
+impl StmtVisitor<()> for Linter<'_> {
+    fn visit_print_stmt(&mut self, expr: &Expr, _location: &SrcLocation) -> Result<(), RutoxError> {
+        self.visit_expr(expr)
+    }
+
+    fn visit_expr_stmt(&mut self, expr: &Expr, _location: &SrcLocation) -> Result<(), RutoxError> {
+        self.visit_expr(expr)
+    }
+
+    fn visit_var_stmt(
+        &mut self,
+        _name: &Token,
+        initializer: &Option<Expr>,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        match initializer {
+            Some(initializer) => self.visit_expr(initializer),
+            None => Ok(()),
+        }
+    }
+
+    fn visit_block_stmt(
+        &mut self,
+        stmts: &[Stmt],
+        location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        self.lint_body(stmts, "block", location);
+
+        Ok(())
+    }
+
+    fn visit_if_stmt(
+        &mut self,
+        cond: &Expr,
+        then_branch: &Stmt,
+        else_branch: &Option<Box<Stmt>>,
+        location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        self.check_condition(cond, "if");
+        if let Some(else_branch) = else_branch {
+            let mut printer = SexprPrinter {};
+            if printer.stmt(then_branch) == printer.stmt(else_branch) {
+                let warning = self.diagnostic(
+                    Rule::IdenticalBranches,
+                    "Both branches of this `if` are the same".to_string(),
+                    location.clone(),
+                );
+                self.warnings.extend(warning.map(|warning| {
+                    warning.with_label("the else branch".to_string(), else_branch.location())
+                }));
+            }
+        }
+
+        self.visit_expr(cond)?;
+        self.visit_stmt(then_branch)?;
+        if let Some(else_branch) = else_branch {
+            self.visit_stmt(else_branch)?;
+        }
+
+        Ok(())
+    }
+
+    fn visit_while_stmt(
+        &mut self,
+        cond: &Expr,
+        body: &Stmt,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        self.check_condition(cond, "while");
+        self.visit_expr(cond)?;
+        self.visit_stmt(body)
+    }
+
+    fn visit_break_stmt(&mut self, _location: &SrcLocation) -> Result<(), RutoxError> {
+        Ok(())
+    }
+
+    fn visit_continue_stmt(&mut self, _location: &SrcLocation) -> Result<(), RutoxError> {
+        Ok(())
+    }
+
+    fn visit_function_stmt(&mut self, function: &Rc<FunctionData>) -> Result<(), RutoxError> {
+        self.lint_body(&function.body, "function body", &function.location);
+
+        Ok(())
+    }
+
+    fn visit_return_stmt(
+        &mut self,
+        value: &Option<Expr>,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        match value {
+            Some(value) => self.visit_expr(value),
+            None => Ok(()),
+        }
+    }
+
+    fn visit_throw_stmt(
+        &mut self,
+        value: &Expr,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        self.visit_expr(value)
+    }
+
+    fn visit_try_stmt(&mut self, try_data: &TryData) -> Result<(), RutoxError> {
+        self.lint_body(&try_data.body, "`try` block", &try_data.location);
+        if let Some(catch) = &try_data.catch {
+            self.lint_body(&catch.body, "`catch` block", &catch.name.location);
+        }
+        if let Some(finally) = &try_data.finally {
+            self.lint_body(finally, "`finally` block", &try_data.location);
+        }
+
+        Ok(())
+    }
+
+    fn visit_import_stmt(&mut self, _import: &ImportData) -> Result<(), RutoxError> {
+        Ok(())
+    }
+
+    fn visit_test_stmt(&mut self, test: &TestData) -> Result<(), RutoxError> {
+        self.lint_body(&test.body, "test body", &test.location);
+
+        Ok(())
+    }
+}
+
+impl ExprVisitor<()> for Linter<'_> {
+    fn visit_literal_expr(&mut self, _literal: &LiteralData) -> Result<(), RutoxError> {
+        Ok(())
+    }
+
+    fn visit_unary_expr(&mut self, unary: &UnaryData) -> Result<(), RutoxError> {
+        self.visit_expr(&unary.expr)
+    }
+
+    fn visit_binary_expr(&mut self, binary: &BinaryData) -> Result<(), RutoxError> {
+        let always = match binary.operator {
+            BinaryOp::EqualEqual(_) | BinaryOp::GreaterEqual(_) | BinaryOp::LessEqual(_) => {
+                Some(true)
+            }
+            BinaryOp::BangEqual(_) | BinaryOp::Greater(_) | BinaryOp::Less(_) => Some(false),
+            _ => None,
+        };
+        if let Some(always) = always {
+            let mut printer = SexprPrinter {};
+            if is_pure(&binary.left) && printer.expr(&binary.left) == printer.expr(&binary.right) {
+                let warning = self.diagnostic(
+                    Rule::SelfComparison,
+                    format!(
+                        "Both sides of `{}` are the same, so this is always {always}",
+                        binary.operator
+                    ),
+                    binary.span(),
+                );
+                self.warnings.extend(warning);
+            }
+        }
+
+        if let BinaryOp::Mul(_) = binary.operator {
+            let is_string = matches!(
+                strip_groupings(&binary.left),
+                Expr::Literal(LiteralData::String(_, _))
+            );
+            if is_string && is_negative_number(&binary.right) {
+                let warning = self.diagnostic(
+                    Rule::NegativeRepeat,
+                    "Repeating a string a negative number of times".to_string(),
+                    binary.span(),
+                );
+                self.warnings.extend(warning.map(|warning| {
+                    warning.with_help("this always gives an empty string".to_string())
+                }));
+            }
+        }
+
+        self.visit_expr(&binary.left)?;
+        self.visit_expr(&binary.right)
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &Expr) -> Result<(), RutoxError> {
+        self.visit_expr(expr)
+    }
+
+    fn visit_variable_expr(
+        &mut self,
+        _name: &Token,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        Ok(())
+    }
+
+    fn visit_assign_expr(
+        &mut self,
+        name: &Token,
+        value: &Expr,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        if let Expr::Variable(value_name, _) = strip_groupings(value) {
+            if value_name.lexeme == name.lexeme {
+                let warning = self.diagnostic(
+                    Rule::SelfAssignment,
+                    format!("`{}` is assigned to itself", name.lexeme),
+                    name.location.to(&value.span()),
+                );
+                self.warnings.extend(warning);
+            }
+        }
+
+        self.visit_expr(value)
+    }
+
+    fn visit_logical_expr(
+        &mut self,
+        left: &Expr,
+        _op: &LogicalOp,
+        right: &Expr,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        self.visit_expr(left)?;
+        self.visit_expr(right)
+    }
+
+    fn visit_call_expr(&mut self, call: &CallData) -> Result<(), RutoxError> {
+        self.visit_expr(&call.callee)?;
+        for arg in &call.args {
+            self.visit_expr(arg)?;
+        }
+
+        Ok(())
+    }
+
+    fn visit_lambda_expr(&mut self, function: &Rc<FunctionData>) -> Result<(), RutoxError> {
+        self.lint_body(&function.body, "function body", &function.location);
+
+        Ok(())
+    }
+
+    fn visit_get_expr(
+        &mut self,
+        object: &Expr,
+        _name: &Token,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        self.visit_expr(object)
+    }
+}
@@ -0,0 +1,340 @@
+mod position;
+pub mod transport;
+
+use crate::formatter;
+use crate::linter::{self, LintConfig};
+use crate::parser::{ast::Stmt, Parser};
+use crate::resolver::{LocalKind, Resolver, SymbolIndex};
+use crate::rutox_error::{RutoxError, Severity};
+use crate::scanner::{token::Comment, FileId, Scanner};
+use position::{PositionEncoding, Positions};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::{fs, io};
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_REQUEST: i64 = -32600;
+
+/// Serves the Language Server Protocol over stdin and stdout until the
+/// client sends `exit`. Returns the exit code the protocol asks for.
+pub fn run() -> io::Result<i32> {
+    let mut input = io::stdin().lock();
+    let mut output = io::stdout().lock();
+    let mut server = Server::new();
+
+    while let Some(message) = transport::read_message(&mut input)? {
+        if message["method"] == "exit" {
+            return Ok(if server.shut_down { 0 } else { 1 });
+        }
+
+        for reply in server.handle(&message) {
+            transport::write_message(&mut output, &reply)?;
+        }
+    }
+
+    Ok(1)
+}
+
+struct Server {
+    /// The text of each open document, by URI.
+    documents: HashMap<String, String>,
+    lint_config: LintConfig,
+    encoding: PositionEncoding,
+    shut_down: bool,
+}
+
+/// A parsed document. Only the diagnostics are available when it does not
+/// parse.
+struct Analysis {
+    stmts: Result<Vec<Stmt>, RutoxError>,
+    comments: Vec<Comment>,
+}
+
+impl Server {
+    fn new() -> Self {
+        let lint_config = fs::read_to_string(LintConfig::FILE_NAME)
+            .ok()
+            .and_then(|config| LintConfig::parse(&config).ok())
+            .unwrap_or_default();
+
+        Self {
+            documents: HashMap::new(),
+            lint_config,
+            encoding: PositionEncoding::Utf16,
+            shut_down: false,
+        }
+    }
+
+    /// The replies to `message`: a response when it is a request, plus any
+    /// notifications it causes.
+    fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let id = &message["id"];
+
+        if self.shut_down && !id.is_null() {
+            return vec![error_response(
+                id,
+                INVALID_REQUEST,
+                "The server is shut down",
+            )];
+        }
+
+        let result = match method {
+            "initialize" => {
+                self.encoding = PositionEncoding::negotiate(params);
+                json!({
+                    "capabilities": {
+                        "positionEncoding": self.encoding.name(),
+                        "textDocumentSync": 1,
+                        "hoverProvider": true,
+                        "definitionProvider": true,
+                        "referencesProvider": true,
+                        "documentSymbolProvider": true,
+                        "documentFormattingProvider": true,
+                    },
+                    "serverInfo": { "name": "rutox", "version": env!("CARGO_PKG_VERSION") },
+                })
+            }
+            "shutdown" => {
+                self.shut_down = true;
+                Value::Null
+            }
+            "textDocument/didOpen" => {
+                let document = &params["textDocument"];
+                return self.update(document["uri"].as_str(), document["text"].as_str());
+            }
+            "textDocument/didChange" => {
+                // Documents are synced in full, so the last change is the whole text
+                let text = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str());
+                return self.update(params["textDocument"]["uri"].as_str(), text);
+            }
+            "textDocument/didClose" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                self.documents.remove(uri);
+                return vec![publish_diagnostics(uri, vec![])];
+            }
+            "textDocument/hover" => self.hover(params),
+            "textDocument/definition" => self.definition(params),
+            "textDocument/references" => self.references(params),
+            "textDocument/documentSymbol" => self.document_symbols(params),
+            "textDocument/formatting" => self.formatting(params),
+            _ if id.is_null() => return vec![],
+            _ => {
+                return vec![error_response(
+                    id,
+                    METHOD_NOT_FOUND,
+                    &format!("Unknown method `{method}`"),
+                )]
+            }
+        };
+
+        match id {
+            Value::Null => vec![],
+            id => vec![json!({ "jsonrpc": "2.0", "id": id, "result": result })],
+        }
+    }
+
+    fn update(&mut self, uri: Option<&str>, text: Option<&str>) -> Vec<Value> {
+        let (Some(uri), Some(text)) = (uri, text) else {
+            return vec![];
+        };
+        self.documents.insert(uri.to_string(), text.to_string());
+
+        let analysis = analyze(text);
+        let positions = Positions::new(text, self.encoding);
+        let errors = match analysis.stmts {
+            Ok(stmts) => linter::lint(&stmts, &analysis.comments, &self.lint_config),
+            Err(error) => vec![error],
+        };
+        let diagnostics = errors
+            .iter()
+            .flat_map(|error| error.errors())
+            .map(|error| diagnostic(uri, error, &positions))
+            .collect();
+
+        vec![publish_diagnostics(uri, diagnostics)]
+    }
+
+    fn hover(&self, params: &Value) -> Value {
+        let Some((_, text, index, symbol)) = self.symbol_at(params) else {
+            return Value::Null;
+        };
+        let symbol = &index.symbols[symbol];
+        let kind = match symbol.kind {
+            LocalKind::Variable => "variable",
+            LocalKind::Function => "function",
+            LocalKind::Binding => "binding",
+        };
+        let container = match &symbol.container {
+            Some(function) => format!(" in `{function}`"),
+            None => String::new(),
+        };
+        let declaration = text
+            .lines()
+            .nth(symbol.location.line - 1)
+            .unwrap_or_default()
+            .trim();
+
+        json!({
+            "contents": {
+                "kind": "markdown",
+                "value": format!(
+                    "{kind} `{}`{container}\n\n```lox\n{declaration}\n```",
+                    symbol.name
+                ),
+            },
+        })
+    }
+
+    fn definition(&self, params: &Value) -> Value {
+        match self.symbol_at(params) {
+            Some((uri, text, index, symbol)) => {
+                Positions::new(text, self.encoding).location(uri, &index.symbols[symbol].location)
+            }
+            None => Value::Null,
+        }
+    }
+
+    fn references(&self, params: &Value) -> Value {
+        let Some((uri, text, index, symbol)) = self.symbol_at(params) else {
+            return Value::Null;
+        };
+        let positions = Positions::new(text, self.encoding);
+        let mut locations = vec![];
+        if params["context"]["includeDeclaration"] == true {
+            locations.push(positions.location(uri, &index.symbols[symbol].location));
+        }
+        locations.extend(
+            index
+                .references_to(symbol)
+                .map(|reference| positions.location(uri, reference)),
+        );
+
+        Value::Array(locations)
+    }
+
+    fn document_symbols(&self, params: &Value) -> Value {
+        let Some((uri, text)) = self.document(params) else {
+            return Value::Null;
+        };
+        let Ok(stmts) = analyze(text).stmts else {
+            return Value::Null;
+        };
+        let positions = Positions::new(text, self.encoding);
+
+        Resolver::new()
+            .index(&stmts)
+            .symbols
+            .iter()
+            .filter(|symbol| symbol.kind != LocalKind::Binding)
+            .map(|symbol| {
+                json!({
+                    "name": symbol.name,
+                    // Function and Variable, in LSP's numbering
+                    "kind": match symbol.kind {
+                        LocalKind::Function => 12,
+                        _ => 13,
+                    },
+                    "location": positions.location(uri, &symbol.location),
+                    "containerName": symbol.container,
+                })
+            })
+            .collect()
+    }
+
+    fn formatting(&self, params: &Value) -> Value {
+        let Some((_, text)) = self.document(params) else {
+            return Value::Null;
+        };
+        let Ok(formatted) = formatter::format(text, FileId::default()) else {
+            return Value::Null;
+        };
+
+        json!([{
+            "range": {
+                "start": { "line": 0, "character": 0 },
+                "end": { "line": text.lines().count() + 1, "character": 0 },
+            },
+            "newText": formatted,
+        }])
+    }
+
+    fn document<'a>(&'a self, params: &'a Value) -> Option<(&'a str, &'a str)> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let text = self.documents.get(uri)?;
+
+        Some((uri, text))
+    }
+
+    /// The symbol at the request's position, with the document it is in.
+    fn symbol_at<'a>(
+        &'a self,
+        params: &'a Value,
+    ) -> Option<(&'a str, &'a str, SymbolIndex, usize)> {
+        let (uri, text) = self.document(params)?;
+        let stmts = analyze(text).stmts.ok()?;
+        let index = Resolver::new().index(&stmts);
+
+        let line = params["position"]["line"].as_u64()? as usize + 1;
+        let character = params["position"]["character"].as_u64()? as usize;
+        let col = Positions::new(text, self.encoding).col(line, character);
+        let symbol = index.symbol_at(line, col)?;
+
+        Some((uri, text, index, symbol))
+    }
+}
+
+fn analyze(text: &str) -> Analysis {
+    let mut scanner = Scanner::new(text.to_string(), FileId::default());
+    let stmts = scanner
+        .scan_tokens()
+        .and_then(|tokens| Parser::new(tokens).parse());
+
+    Analysis {
+        stmts,
+        comments: scanner.comments().to_vec(),
+    }
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+fn diagnostic(uri: &str, error: &RutoxError, positions: &Positions) -> Value {
+    let mut message = error.message();
+    for help in error.helps() {
+        message.push_str(&format!("\nhelp: {help}"));
+    }
+
+    json!({
+        "range": positions.range(&error.location()),
+        // Error and Warning, in LSP's numbering
+        "severity": match error.severity() {
+            Severity::Error => 1,
+            Severity::Warning => 2,
+        },
+        "code": error.code().code(),
+        "source": "rutox",
+        "message": message,
+        "relatedInformation": error.labels().iter().map(|label| json!({
+            "location": positions.location(uri, &label.location),
+            "message": label.message,
+        })).collect::<Vec<_>>(),
+    })
+}
+
+fn error_response(id: &Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
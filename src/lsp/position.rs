@@ -0,0 +1,111 @@
+use crate::scanner::SrcLocation;
+use serde_json::{json, Value};
+
+/// What the `character` of an LSP position counts. The protocol defaults to
+/// UTF-16 code units, while `SrcLocation` columns count characters, which
+/// is what `utf-32` means.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PositionEncoding {
+    Utf16,
+    Utf32,
+}
+
+impl PositionEncoding {
+    /// `utf-32` if the client offers it in its `initialize` params, since it
+    /// needs no conversion, and the default `utf-16` otherwise.
+    pub fn negotiate(params: &Value) -> Self {
+        let offered = params["capabilities"]["general"]["positionEncodings"]
+            .as_array()
+            .is_some_and(|encodings| encodings.iter().any(|encoding| encoding == "utf-32"));
+
+        match offered {
+            true => PositionEncoding::Utf32,
+            false => PositionEncoding::Utf16,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PositionEncoding::Utf16 => "utf-16",
+            PositionEncoding::Utf32 => "utf-32",
+        }
+    }
+}
+
+/// Converts between `SrcLocation`s and LSP positions in a document.
+pub struct Positions<'a> {
+    lines: Vec<&'a str>,
+    encoding: PositionEncoding,
+}
+
+impl<'a> Positions<'a> {
+    pub fn new(text: &'a str, encoding: PositionEncoding) -> Self {
+        Self {
+            lines: text.split('\n').collect(),
+            encoding,
+        }
+    }
+
+    fn line(&self, line: usize) -> &'a str {
+        line.checked_sub(1)
+            .and_then(|index| self.lines.get(index))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// The LSP `character` after the first `chars` characters of `line`,
+    /// which counts from 1.
+    fn character(&self, line: usize, chars: usize) -> usize {
+        let text = self.line(line);
+
+        match self.encoding {
+            PositionEncoding::Utf32 => chars,
+            PositionEncoding::Utf16 => {
+                let units: usize = text.chars().take(chars).map(char::len_utf16).sum();
+                // Locations past the end of the line, e.g. at the end of the
+                // file, count one unit per missing character
+                units + chars.saturating_sub(text.chars().count())
+            }
+        }
+    }
+
+    /// The column, counting from 1, of the LSP `character` on `line`, which
+    /// counts from 1 too.
+    pub fn col(&self, line: usize, character: usize) -> usize {
+        let text = self.line(line);
+
+        let chars = match self.encoding {
+            PositionEncoding::Utf32 => character,
+            PositionEncoding::Utf16 => {
+                let mut units = 0;
+                text.chars()
+                    .take_while(|c| {
+                        units += c.len_utf16();
+                        units <= character
+                    })
+                    .count()
+            }
+        };
+
+        chars + 1
+    }
+
+    /// LSP positions count from 0 and ranges exclude their end, while
+    /// `SrcLocation`s count from 1 and include it.
+    pub fn range(&self, location: &SrcLocation) -> Value {
+        json!({
+            "start": {
+                "line": location.line - 1,
+                "character": self.character(location.line, location.col - 1),
+            },
+            "end": {
+                "line": location.end_line - 1,
+                "character": self.character(location.end_line, location.end_col),
+            },
+        })
+    }
+
+    pub fn location(&self, uri: &str, location: &SrcLocation) -> Value {
+        json!({ "uri": uri, "range": self.range(location) })
+    }
+}
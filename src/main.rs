@@ -1,47 +1,417 @@
-use std::{env, fs, process};
-mod exitcodes;
-mod interpreter;
-mod parser;
-mod rutox_error;
-mod scanner;
+use cli::{Command, Input, Options};
+use colored::*;
+use coverage::Coverage;
+use error_code::ErrorCode;
+use error_format::ErrorFormat;
 use interpreter::Interpreter;
-use parser::Parser;
-use scanner::Scanner;
+use linter::LintConfig;
+use parser::{ast::Stmt, ast_printer::AstFormat, Parser};
+use profiler::Profiler;
+use resolver::Resolver;
+use rutox::{
+    cli, coverage, debugger, error_code, error_format, exitcodes, formatter, interpreter, linter,
+    lsp, parser, profiler, resolver, rutox_error, scanner, test_runner, tracer, vm,
+};
+use rutox_error::{RutoxError, Severity};
+use scanner::{FileId, Scanner, SourceMap};
+use std::{cell::RefCell, env, fs, panic, path::Path, process, rc::Rc, thread};
+use test_runner::Summary;
+use tracer::Tracer;
+use vm::{Backend, Vm};
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    // The main thread's stack is too small for the tree-walker, while a
+    // spawned thread's is only backed by memory as it is used
+    let cli = thread::Builder::new()
+        .name("main".to_string())
+        .stack_size(interpreter::STACK_SIZE)
+        .spawn(run_cli)
+        .expect("Could not start the interpreter thread");
 
-    match args.len() {
-        1 => run_repl(),
-        2 => run_file(&args[1]),
-        _ => {
-            println!("Usage: rutox [script]");
+    if let Err(payload) = cli.join() {
+        panic::resume_unwind(payload);
+    }
+}
+
+fn run_cli() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = Options::parse(&args).unwrap_or_else(|err| {
+        println!("{err}\n\n{}", cli::USAGE);
+        process::exit(exitcodes::USAGE);
+    });
+    options.color.apply();
+
+    match (&options.command, &options.input) {
+        (Command::Explain(code), _) => explain(code),
+        (Command::Lsp, _) => serve_lsp(),
+        (Command::Debug { dap: true }, _) => serve_dap(),
+        (
+            Command::Ast(_)
+            | Command::Fmt { .. }
+            | Command::Lint { .. }
+            | Command::Debug { .. }
+            | Command::Profile { .. },
+            Input::Repl,
+        ) => {
+            println!("Expected a script or `-e` snippet\n\n{}", cli::USAGE);
             process::exit(exitcodes::USAGE);
         }
+        (Command::Ast(format), Input::File(path)) => {
+            print_ast(*format, path, &read_script(path), &options)
+        }
+        (Command::Ast(format), Input::Snippet(code)) => print_ast(*format, "-e", code, &options),
+        (Command::Fmt { check }, Input::File(path)) => format_file(path, *check, &options),
+        (Command::Fmt { check }, Input::Snippet(code)) => format_snippet(code, *check, &options),
+        (Command::Lint { config }, Input::File(path)) => {
+            lint(path, &read_script(path), config.as_deref(), &options)
+        }
+        (Command::Lint { config }, Input::Snippet(code)) => {
+            lint("-e", code, config.as_deref(), &options)
+        }
+        (Command::Debug { .. }, Input::File(path)) => debug(path, &read_script(path), &options),
+        (Command::Debug { .. }, Input::Snippet(code)) => debug("-e", code, &options),
+        (Command::Profile { folded }, Input::File(path)) => {
+            let folded = folded.clone().unwrap_or(format!("{path}.folded"));
+            profile(path, &read_script(path), &folded, &options)
+        }
+        (Command::Profile { folded }, Input::Snippet(code)) => {
+            let folded = folded.as_deref().unwrap_or("rutox.folded");
+            profile("-e", code, folded, &options)
+        }
+        (Command::Test, Input::Repl) => run_tests(".", &options),
+        (Command::Test, Input::File(path)) => run_tests(path, &options),
+        (Command::Test, Input::Snippet(code)) => {
+            let mut summary = Summary::default();
+            let source_map = Rc::new(RefCell::new(SourceMap::new()));
+            test_runner::run_file(
+                "-e",
+                code,
+                &source_map,
+                options.warnings_as_errors,
+                &mut summary,
+            );
+            report_tests(&summary, &source_map.borrow(), &options);
+        }
+        (Command::Run, Input::Repl) => run_repl(&options),
+        (Command::Run, Input::File(path)) => run_file(path, &options),
+        (Command::Run, Input::Snippet(code)) => run_snippet(code, &options),
     }
 }
 
-fn run_file(path: &str) {
-    let file_content = fs::read_to_string(path).unwrap_or_else(|err| {
-        println!("Error while opening {}: {}", path, err);
+fn serve_lsp() {
+    match lsp::run() {
+        Ok(code) => process::exit(code),
+        Err(err) => {
+            eprintln!("Error while talking to the client: {err}");
+            process::exit(exitcodes::IOERR);
+        }
+    }
+}
+
+fn serve_dap() {
+    match debugger::serve_dap() {
+        Ok(code) => process::exit(code),
+        Err(err) => {
+            eprintln!("Error while talking to the client: {err}");
+            process::exit(exitcodes::IOERR);
+        }
+    }
+}
+
+fn explain(code: &str) {
+    match ErrorCode::from_code(code) {
+        Some(error_code) => {
+            println!(
+                "{error_code}: {}\n\n{}",
+                error_code.title(),
+                error_code.explanation()
+            )
+        }
+        None => {
+            println!("Unknown error code `{code}`");
+            process::exit(exitcodes::USAGE);
+        }
+    }
+}
+
+fn print_ast(format: AstFormat, path: &str, code: &str, options: &Options) {
+    let mut source_map = SourceMap::new();
+    let file = source_map.add(path, code);
+    let stmts = Scanner::new(code.to_string(), file)
+        .scan_tokens()
+        .and_then(|tokens| Parser::new(tokens).parse());
+
+    match stmts {
+        Ok(stmts) => println!("{}", format.render(&stmts)),
+        Err(error) => {
+            report(&error, &source_map, options.error_format);
+            process::exit(exitcodes::DATAERR);
+        }
+    }
+}
+
+fn format_file(path: &str, check: bool, options: &Options) {
+    let code = read_script(path);
+    let formatted = format_or_exit(path, &code, options);
+
+    if formatted == code {
+        return;
+    }
+    if check {
+        println!("{path} is not formatted");
+        process::exit(exitcodes::DATAERR);
+    }
+    fs::write(path, formatted).unwrap_or_else(|err| {
+        println!("Error while writing {}: {}", path, err);
         process::exit(exitcodes::IOERR);
     });
+}
 
-    match eval(file_content.clone()) {
-        Ok(_result) => {}
-        Err(error) => {
-            println!("{error}");
-            println!("{}", error.details(path, &file_content));
+fn format_snippet(code: &str, check: bool, options: &Options) {
+    let formatted = format_or_exit("-e", code, options);
+
+    if !check {
+        print!("{formatted}");
+    } else if formatted.trim_end() != code.trim_end() {
+        println!("-e is not formatted");
+        process::exit(exitcodes::DATAERR);
+    }
+}
+
+fn format_or_exit(path: &str, code: &str, options: &Options) -> String {
+    let mut source_map = SourceMap::new();
+    let file = source_map.add(path, code);
+
+    formatter::format(code, file).unwrap_or_else(|error| {
+        report(&error, &source_map, options.error_format);
+        process::exit(exitcodes::DATAERR);
+    })
+}
+
+fn lint(path: &str, code: &str, config_path: Option<&str>, options: &Options) {
+    let config = match config_path {
+        Some(config_path) => Some(read_script(config_path)),
+        None => fs::read_to_string(LintConfig::FILE_NAME).ok(),
+    };
+    let config = match config {
+        Some(config) => LintConfig::parse(&config).unwrap_or_else(|err| {
+            let config_path = config_path.unwrap_or(LintConfig::FILE_NAME);
+            println!("Invalid lint config {config_path}: {err}");
             process::exit(exitcodes::DATAERR);
+        }),
+        None => LintConfig::default(),
+    };
+
+    let mut source_map = SourceMap::new();
+    let file = source_map.add(path, code);
+    let mut scanner = Scanner::new(code.to_string(), file);
+    let stmts = scanner
+        .scan_tokens()
+        .and_then(|tokens| Parser::new(tokens).parse());
+    let stmts = stmts.unwrap_or_else(|error| {
+        report(&error, &source_map, options.error_format);
+        process::exit(exitcodes::DATAERR);
+    });
+
+    let warnings = linter::lint(&stmts, scanner.comments(), &config);
+    if warnings.is_empty() {
+        return;
+    }
+
+    let mut warnings = RutoxError::Multiple(warnings);
+    if options.warnings_as_errors {
+        warnings = warnings.promoted();
+    }
+    report(&warnings, &source_map, options.error_format);
+    if warnings
+        .errors()
+        .iter()
+        .any(|warning| warning.severity() == Severity::Error)
+    {
+        process::exit(exitcodes::DATAERR);
+    }
+}
+
+fn debug(path: &str, code: &str, options: &Options) {
+    let source_map = Rc::new(RefCell::new(SourceMap::new()));
+    let file = source_map.borrow_mut().add(path, code);
+    let mut interpreter = Interpreter::for_file(path, source_map.clone());
+    interpreter.set_hook(Box::new(debugger::Debugger::new(file)));
+
+    println!("Paused before the first line. Type `help` for a list of commands.");
+    let result = load(code.to_string(), file, &source_map, options)
+        .and_then(|stmts| interpreter.interpret(stmts));
+    if let Err(error) = result {
+        report(&error, &source_map.borrow(), options.error_format);
+        process::exit(exitcodes::DATAERR);
+    }
+}
+
+fn profile(path: &str, code: &str, folded_path: &str, options: &Options) {
+    let source_map = Rc::new(RefCell::new(SourceMap::new()));
+    let file = source_map.borrow_mut().add(path, code);
+    let mut interpreter = Interpreter::for_file(path, source_map.clone());
+    let profiler = Profiler::new();
+    interpreter.set_hook(Box::new(profiler.clone()));
+
+    let result = load(code.to_string(), file, &source_map, options)
+        .and_then(|stmts| interpreter.interpret(stmts));
+
+    eprint!("\n{}", profiler.report(&source_map.borrow()));
+    fs::write(folded_path, profiler.folded(&source_map.borrow())).unwrap_or_else(|err| {
+        println!("Error while writing {}: {}", folded_path, err);
+        process::exit(exitcodes::IOERR);
+    });
+    eprintln!("\nFolded stacks written to {folded_path}");
+
+    if let Err(error) = result {
+        report(&error, &source_map.borrow(), options.error_format);
+        process::exit(exitcodes::DATAERR);
+    }
+}
+
+fn run_tests(path: &str, options: &Options) {
+    let files = test_runner::discover(Path::new(path)).unwrap_or_else(|err| {
+        println!("Error while opening {}: {}", path, err);
+        process::exit(exitcodes::IOERR);
+    });
+
+    let mut summary = Summary::default();
+    let source_map = Rc::new(RefCell::new(SourceMap::new()));
+    for file in files {
+        let path = file.display().to_string();
+        let code = read_script(&path);
+        test_runner::run_file(
+            &path,
+            &code,
+            &source_map,
+            options.warnings_as_errors,
+            &mut summary,
+        );
+    }
+
+    report_tests(&summary, &source_map.borrow(), options);
+}
+
+fn report_tests(summary: &Summary, source_map: &SourceMap, options: &Options) {
+    for warning in &summary.warnings {
+        report(warning, source_map, options.error_format);
+    }
+
+    if !summary.failures.is_empty() {
+        println!("\nFailures:");
+        for (name, error) in &summary.failures {
+            println!("\n{name}");
+            report(error, source_map, options.error_format);
         }
     }
+
+    let result = match summary.failures.is_empty() {
+        true => "ok".green(),
+        false => "FAILED".red().bold(),
+    };
+    println!(
+        "\nTest result: {result}. {} passed; {} failed",
+        summary.passed,
+        summary.failures.len()
+    );
+
+    if !summary.failures.is_empty() {
+        process::exit(exitcodes::DATAERR);
+    }
 }
 
-fn run_repl() {
+fn read_script(path: &str) -> String {
+    fs::read_to_string(path).unwrap_or_else(|err| {
+        println!("Error while opening {}: {}", path, err);
+        process::exit(exitcodes::IOERR);
+    })
+}
+
+fn run_file(path: &str, options: &Options) {
+    let file_content = read_script(path);
+
+    let source_map = Rc::new(RefCell::new(SourceMap::new()));
+    let file = source_map.borrow_mut().add(path, &file_content);
+
+    run(file_content, file, Some(path), &source_map, options);
+}
+
+fn run_snippet(code: &str, options: &Options) {
+    let source_map = Rc::new(RefCell::new(SourceMap::new()));
+    let file = source_map.borrow_mut().add("-e", code);
+
+    run(code.to_string(), file, None, &source_map, options);
+}
+
+/// Runs `code` on the chosen backend, resolving imports relative to `path`
+/// if it comes from a script.
+fn run(
+    code: String,
+    file: FileId,
+    path: Option<&str>,
+    source_map: &Rc<RefCell<SourceMap>>,
+    options: &Options,
+) {
+    let coverage = Coverage::new();
+    let result = load(code, file, source_map, options).and_then(|stmts| match options.backend {
+        Backend::Tree => {
+            let mut interpreter = match path {
+                Some(path) => Interpreter::for_file(path, source_map.clone()),
+                None => Interpreter::new(source_map.clone()),
+            };
+            if options.trace {
+                interpreter.set_hook(Box::new(Tracer::new(Some(file))));
+            } else if options.coverage.is_some() {
+                interpreter.set_hook(Box::new(coverage.clone()));
+            }
+
+            interpreter.interpret(stmts)
+        }
+        Backend::Vm => {
+            let mut vm = match path {
+                Some(path) => Vm::for_file(path, source_map.clone()),
+                None => Vm::new(source_map.clone()),
+            };
+
+            vm.interpret(stmts)
+        }
+    });
+
+    if let Some(path) = &options.coverage {
+        fs::write(path, coverage.lcov(&source_map.borrow())).unwrap_or_else(|err| {
+            println!("Error while writing {}: {}", path, err);
+            process::exit(exitcodes::IOERR);
+        });
+        eprintln!("{}\nCoverage written to {path}", coverage.summary());
+    }
+
+    if let Err(error) = result {
+        report(&error, &source_map.borrow(), options.error_format);
+        process::exit(exitcodes::DATAERR);
+    }
+}
+
+fn run_repl(options: &Options) {
     use rustyline::error::ReadlineError;
     use rustyline::Editor;
 
     let mut rl = Editor::<()>::new();
+    let source_map = Rc::new(RefCell::new(SourceMap::new()));
+    // Each backend keeps its globals from one line to the next
+    let mut interpret: Box<dyn FnMut(Vec<Stmt>) -> Result<(), RutoxError>> = match options.backend {
+        Backend::Tree => {
+            let mut interpreter = Interpreter::new(source_map.clone());
+            if options.trace {
+                interpreter.set_hook(Box::new(Tracer::new(None)));
+            }
+            Box::new(move |stmts| interpreter.interpret(stmts))
+        }
+        Backend::Vm => {
+            let mut vm = Vm::new(source_map.clone());
+            Box::new(move |stmts| vm.interpret(stmts))
+        }
+    };
 
     loop {
         let readline = rl.readline("rutox> ");
@@ -52,12 +422,10 @@ fn run_repl() {
                 _ => {
                     rl.add_history_entry(line.as_str());
 
-                    match eval(line.clone()) {
+                    let file = source_map.borrow_mut().add("repl", &line);
+                    match load(line.clone(), file, &source_map, options).and_then(&mut interpret) {
                         Ok(_) => {}
-                        Err(error) => {
-                            println!("{error}");
-                            println!("{}", error.details("repl", &line));
-                        }
+                        Err(error) => report(&error, &source_map.borrow(), options.error_format),
                     }
                 }
             },
@@ -73,9 +441,35 @@ fn run_repl() {
     }
 }
 
-fn eval(source: String) -> Result<(), rutox_error::RutoxError> {
-    Scanner::new(source)
+fn report(error: &RutoxError, source_map: &SourceMap, format: ErrorFormat) {
+    match (format, error.severity()) {
+        (ErrorFormat::Human, Severity::Error) => println!("{}", format.render(error, source_map)),
+        // Kept apart from the script's own output so it can be parsed, and so
+        // warnings don't mix with what the script prints
+        _ => eprintln!("{}", format.render(error, source_map)),
+    }
+}
+
+/// Parses `source` and reports the resolver's warnings, failing instead if
+/// they are treated as errors.
+fn load(
+    source: String,
+    file: FileId,
+    source_map: &RefCell<SourceMap>,
+    options: &Options,
+) -> Result<Vec<Stmt>, RutoxError> {
+    let stmts = Scanner::new(source, file)
         .scan_tokens()
-        .and_then(|tokens| Parser::new(tokens).parse())
-        .and_then(|stmts| Interpreter::new().interpret(stmts))
+        .and_then(|tokens| Parser::new(tokens).parse())?;
+
+    let warnings = Resolver::new().resolve(&stmts);
+    if !warnings.is_empty() {
+        let warnings = RutoxError::Multiple(warnings);
+        if options.warnings_as_errors {
+            return Err(warnings.promoted());
+        }
+        report(&warnings, &source_map.borrow(), options.error_format);
+    }
+
+    Ok(stmts)
 }
@@ -1,16 +1,79 @@
+use crate::rutox_error::RutoxError;
 use crate::scanner::{
     token::{Token, TokenKind},
     SrcLocation,
 };
+use std::rc::Rc;
 
-// TODO: add location
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Stmt {
     Print(Expr, SrcLocation),
     Expr(Expr, SrcLocation),
     Var(Token, Option<Expr>, SrcLocation),
     Block(Vec<Stmt>, SrcLocation),
     If(Expr, Box<Stmt>, Option<Box<Stmt>>, SrcLocation),
+    While(Expr, Box<Stmt>, SrcLocation),
+    Break(SrcLocation),
+    Continue(SrcLocation),
+    Function(Rc<FunctionData>),
+    Return(Option<Expr>, SrcLocation),
+    Throw(Expr, SrcLocation),
+    Try(TryData),
+    Import(ImportData),
+    Test(TestData),
+}
+
+impl Stmt {
+    pub fn location(&self) -> SrcLocation {
+        match self {
+            Stmt::Print(_, location)
+            | Stmt::Expr(_, location)
+            | Stmt::Var(_, _, location)
+            | Stmt::Block(_, location)
+            | Stmt::If(_, _, _, location)
+            | Stmt::While(_, _, location)
+            | Stmt::Break(location)
+            | Stmt::Continue(location)
+            | Stmt::Return(_, location)
+            | Stmt::Throw(_, location) => location.clone(),
+            Stmt::Function(function) => function.location.clone(),
+            Stmt::Try(try_data) => try_data.location.clone(),
+            Stmt::Import(import) => import.location.clone(),
+            Stmt::Test(test) => test.location.clone(),
+        }
+    }
+}
+
+/// `import "lib/math.lox";` binds the module to `math`, while
+/// `import m from "lib/math.lox";` binds it to `m`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ImportData {
+    pub name: String,
+    pub path: String,
+    pub location: SrcLocation,
+}
+
+/// `test "name" { ... }`, which only runs under `rutox test`. Only allowed
+/// at the top level of a file.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TestData {
+    pub name: String,
+    pub body: Vec<Stmt>,
+    pub location: SrcLocation,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TryData {
+    pub body: Vec<Stmt>,
+    pub catch: Option<CatchData>,
+    pub finally: Option<Vec<Stmt>>,
+    pub location: SrcLocation,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CatchData {
+    pub name: Token,
+    pub body: Vec<Stmt>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -22,6 +85,9 @@ pub enum Expr {
     Literal(LiteralData),
     Variable(Token, SrcLocation),
     Assign(Token, Box<Expr>, SrcLocation),
+    Call(CallData),
+    Lambda(Rc<FunctionData>),
+    Get(Box<Expr>, Token, SrcLocation),
 }
 
 impl Expr {
@@ -34,6 +100,25 @@ impl Expr {
             Expr::Variable(_token, location) => location.clone(),
             Expr::Assign(_token, _expr, location) => location.clone(),
             Expr::Logical(_left, _op, _right, location) => location.clone(),
+            Expr::Call(call) => call.location.clone(),
+            Expr::Lambda(function) => function.location.clone(),
+            Expr::Get(_object, _name, location) => location.clone(),
+        }
+    }
+
+    /// The source code covered by the whole expression, e.g. all of `a + b`
+    /// rather than just the `+` that `location` points at.
+    pub fn span(&self) -> SrcLocation {
+        match self {
+            Expr::Binary(binary) => binary.span(),
+            Expr::Unary(unary) => unary.span(),
+            Expr::Logical(left, _op, right, _location) => left.span().to(&right.span()),
+            Expr::Assign(name, value, _location) => name.location.to(&value.span()),
+            Expr::Call(call) => call.callee.span().to(&call.location),
+            Expr::Get(object, name, _location) => object.span().to(&name.location),
+            Expr::Grouping(_, _) | Expr::Literal(_) | Expr::Variable(_, _) | Expr::Lambda(_) => {
+                self.location()
+            }
         }
     }
 }
@@ -75,12 +160,27 @@ pub struct UnaryData {
     pub location: SrcLocation,
 }
 
+impl UnaryData {
+    pub fn span(&self) -> SrcLocation {
+        self.location.to(&self.expr.span())
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum LogicalOp {
     Or(SrcLocation),
     And(SrcLocation),
 }
 
+impl std::fmt::Display for LogicalOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LogicalOp::Or(_) => write!(f, "or"),
+            LogicalOp::And(_) => write!(f, "and"),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum UnaryOp {
     Bang(SrcLocation),
@@ -96,6 +196,24 @@ impl std::fmt::Display for UnaryOp {
     }
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub struct CallData {
+    pub callee: Box<Expr>,
+    pub args: Vec<Expr>,
+    /// From the opening to the closing parenthesis.
+    pub location: SrcLocation,
+}
+
+/// A function declaration (`fun name(a, b) { ... }`) or a lambda
+/// (`fun (a, b) { ... }`), which has no name.
+#[derive(Debug, PartialEq)]
+pub struct FunctionData {
+    pub name: Option<Token>,
+    pub params: Vec<Token>,
+    pub body: Vec<Stmt>,
+    pub location: SrcLocation,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct BinaryData {
     pub operator: BinaryOp,
@@ -104,6 +222,12 @@ pub struct BinaryData {
     pub location: SrcLocation,
 }
 
+impl BinaryData {
+    pub fn span(&self) -> SrcLocation {
+        self.left.span().to(&self.right.span())
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum BinaryOp {
     BangEqual(SrcLocation),
@@ -118,20 +242,25 @@ pub enum BinaryOp {
     Mul(SrcLocation),
 }
 
-impl From<Token> for BinaryOp {
-    fn from(item: Token) -> Self {
+impl TryFrom<Token> for BinaryOp {
+    type Error = RutoxError;
+
+    fn try_from(item: Token) -> Result<Self, Self::Error> {
         match item.kind {
-            TokenKind::BangEqual => BinaryOp::BangEqual(item.location),
-            TokenKind::EqualEqual => BinaryOp::EqualEqual(item.location),
-            TokenKind::Greater => BinaryOp::Greater(item.location),
-            TokenKind::GreaterEqual => BinaryOp::GreaterEqual(item.location),
-            TokenKind::Less => BinaryOp::Less(item.location),
-            TokenKind::LessEqual => BinaryOp::LessEqual(item.location),
-            TokenKind::Plus => BinaryOp::Plus(item.location),
-            TokenKind::Minus => BinaryOp::Minus(item.location),
-            TokenKind::Slash => BinaryOp::Div(item.location),
-            TokenKind::Star => BinaryOp::Mul(item.location),
-            _ => panic!("Cannot convert `{}` to BinaryOp", item.kind),
+            TokenKind::BangEqual => Ok(BinaryOp::BangEqual(item.location)),
+            TokenKind::EqualEqual => Ok(BinaryOp::EqualEqual(item.location)),
+            TokenKind::Greater => Ok(BinaryOp::Greater(item.location)),
+            TokenKind::GreaterEqual => Ok(BinaryOp::GreaterEqual(item.location)),
+            TokenKind::Less => Ok(BinaryOp::Less(item.location)),
+            TokenKind::LessEqual => Ok(BinaryOp::LessEqual(item.location)),
+            TokenKind::Plus => Ok(BinaryOp::Plus(item.location)),
+            TokenKind::Minus => Ok(BinaryOp::Minus(item.location)),
+            TokenKind::Slash => Ok(BinaryOp::Div(item.location)),
+            TokenKind::Star => Ok(BinaryOp::Mul(item.location)),
+            _ => Err(RutoxError::Programmer(
+                format!("Cannot convert `{}` to BinaryOp", item.kind),
+                item.location,
+            )),
         }
     }
 }
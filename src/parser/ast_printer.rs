@@ -1,43 +1,598 @@
 use super::ast::*;
-use super::visitor::ExprVisitor;
+use super::visitors::{ExprVisitor, StmtVisitor};
+use crate::error_format::json_span;
 use crate::rutox_error::RutoxError;
+use crate::scanner::{token::Token, SrcLocation};
+use serde_json::{json, Value};
+use std::rc::Rc;
+use std::str::FromStr;
 
-pub struct AstPrinter {}
+/// How `rutox ast` prints a parse tree, chosen with `--format`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AstFormat {
+    /// One node per object, with its location, for tools.
+    Json,
+    /// Lisp-style `(+ 1 2)` forms, for people.
+    Sexpr,
+}
+
+impl FromStr for AstFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "json" => Ok(AstFormat::Json),
+            "sexpr" => Ok(AstFormat::Sexpr),
+            other => Err(format!(
+                "Unknown AST format `{other}`, expected `json` or `sexpr`"
+            )),
+        }
+    }
+}
+
+impl AstFormat {
+    pub fn render(&self, stmts: &[Stmt]) -> String {
+        match self {
+            AstFormat::Json => {
+                let mut printer = JsonPrinter {};
+                let stmts: Vec<Value> = stmts.iter().map(|stmt| printer.stmt(stmt)).collect();
+
+                serde_json::to_string_pretty(&stmts).expect("The AST should be valid JSON")
+            }
+            AstFormat::Sexpr => {
+                let mut printer = SexprPrinter {};
+
+                stmts
+                    .iter()
+                    .map(|stmt| printer.stmt(stmt))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+        }
+    }
+}
+
+/// Prints a tree as S-expressions, e.g. `(print (+ 1 (group (* 2 3))))`.
+pub struct SexprPrinter {}
+
+impl SexprPrinter {
+    pub fn stmt(&mut self, stmt: &Stmt) -> String {
+        self.visit_stmt(stmt)
+            .unwrap_or_else(|_| unreachable!("Printing an AST never fails"))
+    }
+
+    pub fn expr(&mut self, expr: &Expr) -> String {
+        self.visit_expr(expr)
+            .unwrap_or_else(|_| unreachable!("Printing an AST never fails"))
+    }
+
+    /// `(name part part ...)`, leaving out empty parts.
+    fn form(name: &str, parts: &[String]) -> String {
+        let mut form = format!("({name}");
+        for part in parts.iter().filter(|part| !part.is_empty()) {
+            form.push(' ');
+            form.push_str(part);
+        }
+        form.push(')');
+
+        form
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) -> Vec<String> {
+        stmts.iter().map(|stmt| self.stmt(stmt)).collect()
+    }
+
+    fn function(&mut self, function: &FunctionData) -> String {
+        let params: Vec<String> = function
+            .params
+            .iter()
+            .map(|param| param.lexeme.clone())
+            .collect();
+        let mut parts = vec![];
+        if let Some(name) = &function.name {
+            parts.push(name.lexeme.clone());
+        }
+        parts.push(format!("({})", params.join(" ")));
+        parts.extend(self.stmts(&function.body));
+
+        Self::form("fun", &parts)
+    }
+}
+
+impl StmtVisitor<String> for SexprPrinter {
+    fn visit_print_stmt(
+        &mut self,
+        expr: &Expr,
+        _location: &SrcLocation,
+    ) -> Result<String, RutoxError> {
+        Ok(Self::form("print", &[self.expr(expr)]))
+    }
+
+    fn visit_expr_stmt(
+        &mut self,
+        expr: &Expr,
+        _location: &SrcLocation,
+    ) -> Result<String, RutoxError> {
+        Ok(Self::form("expr", &[self.expr(expr)]))
+    }
+
+    fn visit_var_stmt(
+        &mut self,
+        name: &Token,
+        initializer: &Option<Expr>,
+        _location: &SrcLocation,
+    ) -> Result<String, RutoxError> {
+        let initializer = match initializer {
+            Some(initializer) => self.expr(initializer),
+            None => String::new(),
+        };
+
+        Ok(Self::form("var", &[name.lexeme.clone(), initializer]))
+    }
 
-impl AstPrinter {
-    pub fn print(expr: &Expr) {
-        println!("{}", Self {}.visit_expr(expr).ok().unwrap());
+    fn visit_block_stmt(
+        &mut self,
+        stmts: &[Stmt],
+        _location: &SrcLocation,
+    ) -> Result<String, RutoxError> {
+        Ok(Self::form("block", &self.stmts(stmts)))
+    }
+
+    fn visit_if_stmt(
+        &mut self,
+        cond: &Expr,
+        then_branch: &Stmt,
+        else_branch: &Option<Box<Stmt>>,
+        _location: &SrcLocation,
+    ) -> Result<String, RutoxError> {
+        let else_branch = match else_branch {
+            Some(else_branch) => self.stmt(else_branch),
+            None => String::new(),
+        };
+
+        Ok(Self::form(
+            "if",
+            &[self.expr(cond), self.stmt(then_branch), else_branch],
+        ))
+    }
+
+    fn visit_while_stmt(
+        &mut self,
+        cond: &Expr,
+        body: &Stmt,
+        _location: &SrcLocation,
+    ) -> Result<String, RutoxError> {
+        Ok(Self::form("while", &[self.expr(cond), self.stmt(body)]))
+    }
+
+    fn visit_break_stmt(&mut self, _location: &SrcLocation) -> Result<String, RutoxError> {
+        Ok(Self::form("break", &[]))
+    }
+
+    fn visit_continue_stmt(&mut self, _location: &SrcLocation) -> Result<String, RutoxError> {
+        Ok(Self::form("continue", &[]))
+    }
+
+    fn visit_function_stmt(&mut self, function: &Rc<FunctionData>) -> Result<String, RutoxError> {
+        Ok(self.function(function))
+    }
+
+    fn visit_return_stmt(
+        &mut self,
+        value: &Option<Expr>,
+        _location: &SrcLocation,
+    ) -> Result<String, RutoxError> {
+        let value = match value {
+            Some(value) => self.expr(value),
+            None => String::new(),
+        };
+
+        Ok(Self::form("return", &[value]))
+    }
+
+    fn visit_throw_stmt(
+        &mut self,
+        value: &Expr,
+        _location: &SrcLocation,
+    ) -> Result<String, RutoxError> {
+        Ok(Self::form("throw", &[self.expr(value)]))
+    }
+
+    fn visit_try_stmt(&mut self, try_data: &TryData) -> Result<String, RutoxError> {
+        let mut parts = vec![Self::form("block", &self.stmts(&try_data.body))];
+        if let Some(catch) = &try_data.catch {
+            let mut catch_parts = vec![catch.name.lexeme.clone()];
+            catch_parts.extend(self.stmts(&catch.body));
+            parts.push(Self::form("catch", &catch_parts));
+        }
+        if let Some(finally) = &try_data.finally {
+            parts.push(Self::form("finally", &self.stmts(finally)));
+        }
+
+        Ok(Self::form("try", &parts))
+    }
+
+    fn visit_import_stmt(&mut self, import: &ImportData) -> Result<String, RutoxError> {
+        Ok(Self::form(
+            "import",
+            &[import.name.clone(), format!("{:?}", import.path)],
+        ))
+    }
+
+    fn visit_test_stmt(&mut self, test: &TestData) -> Result<String, RutoxError> {
+        let mut parts = vec![format!("{:?}", test.name)];
+        parts.extend(self.stmts(&test.body));
+
+        Ok(Self::form("test", &parts))
     }
 }
 
-impl ExprVisitor<String> for AstPrinter {
-    fn visit_literal_expr(&self, literal: &LiteralData) -> Result<String, RutoxError> {
+impl ExprVisitor<String> for SexprPrinter {
+    fn visit_literal_expr(&mut self, literal: &LiteralData) -> Result<String, RutoxError> {
         match literal {
-            LiteralData::String(s, _) => Ok(format!("\"{s}\"")),
+            LiteralData::String(s, _) => Ok(format!("{s:?}")),
             LiteralData::Number(n, _) => Ok(format!("{n}")),
-            LiteralData::Bool(b, _) => Ok(format!("{}", b)),
+            LiteralData::Bool(b, _) => Ok(format!("{b}")),
             LiteralData::Nil(_) => Ok("nil".to_string()),
         }
     }
 
-    fn visit_unary_expr(&self, unary: &UnaryData) -> Result<String, RutoxError> {
-        Ok(format!(
-            "({} {})",
-            unary.operator,
-            self.visit_expr(&unary.expr)?
+    fn visit_unary_expr(&mut self, unary: &UnaryData) -> Result<String, RutoxError> {
+        Ok(Self::form(
+            &unary.operator.to_string(),
+            &[self.expr(&unary.expr)],
         ))
     }
 
-    fn visit_binary_expr(&self, binary: &BinaryData) -> Result<String, RutoxError> {
-        Ok(format!(
-            "({} {} {})",
-            binary.operator,
-            self.visit_expr(&binary.left)?,
-            self.visit_expr(&binary.right)?,
+    fn visit_binary_expr(&mut self, binary: &BinaryData) -> Result<String, RutoxError> {
+        Ok(Self::form(
+            &binary.operator.to_string(),
+            &[self.expr(&binary.left), self.expr(&binary.right)],
         ))
     }
 
-    fn visit_grouping_expr(&self, expr: &Expr) -> Result<String, RutoxError> {
-        Ok(format!("(group {} )", self.visit_expr(expr)?))
+    fn visit_grouping_expr(&mut self, expr: &Expr) -> Result<String, RutoxError> {
+        Ok(Self::form("group", &[self.expr(expr)]))
+    }
+
+    fn visit_variable_expr(
+        &mut self,
+        name: &Token,
+        _location: &SrcLocation,
+    ) -> Result<String, RutoxError> {
+        Ok(name.lexeme.clone())
+    }
+
+    fn visit_assign_expr(
+        &mut self,
+        name: &Token,
+        value: &Expr,
+        _location: &SrcLocation,
+    ) -> Result<String, RutoxError> {
+        Ok(Self::form("=", &[name.lexeme.clone(), self.expr(value)]))
+    }
+
+    fn visit_logical_expr(
+        &mut self,
+        left: &Expr,
+        op: &LogicalOp,
+        right: &Expr,
+        _location: &SrcLocation,
+    ) -> Result<String, RutoxError> {
+        Ok(Self::form(
+            &op.to_string(),
+            &[self.expr(left), self.expr(right)],
+        ))
+    }
+
+    fn visit_call_expr(&mut self, call: &CallData) -> Result<String, RutoxError> {
+        let mut parts = vec![self.expr(&call.callee)];
+        parts.extend(call.args.iter().map(|arg| self.expr(arg)));
+
+        Ok(Self::form("call", &parts))
+    }
+
+    fn visit_lambda_expr(&mut self, function: &Rc<FunctionData>) -> Result<String, RutoxError> {
+        Ok(self.function(function))
+    }
+
+    fn visit_get_expr(
+        &mut self,
+        object: &Expr,
+        name: &Token,
+        _location: &SrcLocation,
+    ) -> Result<String, RutoxError> {
+        Ok(Self::form(".", &[self.expr(object), name.lexeme.clone()]))
+    }
+}
+
+/// Prints a tree as JSON. Every node has a `type` and a `location`.
+pub struct JsonPrinter {}
+
+impl JsonPrinter {
+    fn stmt(&mut self, stmt: &Stmt) -> Value {
+        self.visit_stmt(stmt)
+            .unwrap_or_else(|_| unreachable!("Printing an AST never fails"))
+    }
+
+    /// Expressions are located by their whole span, e.g. all of `a + b`.
+    fn expr(&mut self, expr: &Expr) -> Value {
+        let mut node = self
+            .visit_expr(expr)
+            .unwrap_or_else(|_| unreachable!("Printing an AST never fails"));
+        node["location"] = json_span(&expr.span());
+
+        node
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) -> Vec<Value> {
+        stmts.iter().map(|stmt| self.stmt(stmt)).collect()
+    }
+
+    fn function(&mut self, function: &FunctionData) -> Value {
+        json!({
+            "type": "Function",
+            "name": function.name.as_ref().map(|name| name.lexeme.clone()),
+            "params": function.params.iter().map(|param| json!({
+                "name": param.lexeme,
+                "location": json_span(&param.location),
+            })).collect::<Vec<_>>(),
+            "body": self.stmts(&function.body),
+            "location": json_span(&function.location),
+        })
+    }
+}
+
+impl StmtVisitor<Value> for JsonPrinter {
+    fn visit_print_stmt(
+        &mut self,
+        expr: &Expr,
+        location: &SrcLocation,
+    ) -> Result<Value, RutoxError> {
+        Ok(json!({
+            "type": "Print",
+            "expression": self.expr(expr),
+            "location": json_span(location),
+        }))
+    }
+
+    fn visit_expr_stmt(
+        &mut self,
+        expr: &Expr,
+        location: &SrcLocation,
+    ) -> Result<Value, RutoxError> {
+        Ok(json!({
+            "type": "Expression",
+            "expression": self.expr(expr),
+            "location": json_span(location),
+        }))
+    }
+
+    fn visit_var_stmt(
+        &mut self,
+        name: &Token,
+        initializer: &Option<Expr>,
+        location: &SrcLocation,
+    ) -> Result<Value, RutoxError> {
+        Ok(json!({
+            "type": "Var",
+            "name": name.lexeme,
+            "initializer": initializer.as_ref().map(|initializer| self.expr(initializer)),
+            "location": json_span(location),
+        }))
+    }
+
+    fn visit_block_stmt(
+        &mut self,
+        stmts: &[Stmt],
+        location: &SrcLocation,
+    ) -> Result<Value, RutoxError> {
+        Ok(json!({
+            "type": "Block",
+            "body": self.stmts(stmts),
+            "location": json_span(location),
+        }))
+    }
+
+    fn visit_if_stmt(
+        &mut self,
+        cond: &Expr,
+        then_branch: &Stmt,
+        else_branch: &Option<Box<Stmt>>,
+        location: &SrcLocation,
+    ) -> Result<Value, RutoxError> {
+        Ok(json!({
+            "type": "If",
+            "condition": self.expr(cond),
+            "then": self.stmt(then_branch),
+            "else": else_branch.as_ref().map(|else_branch| self.stmt(else_branch)),
+            "location": json_span(location),
+        }))
+    }
+
+    fn visit_while_stmt(
+        &mut self,
+        cond: &Expr,
+        body: &Stmt,
+        location: &SrcLocation,
+    ) -> Result<Value, RutoxError> {
+        Ok(json!({
+            "type": "While",
+            "condition": self.expr(cond),
+            "body": self.stmt(body),
+            "location": json_span(location),
+        }))
+    }
+
+    fn visit_break_stmt(&mut self, location: &SrcLocation) -> Result<Value, RutoxError> {
+        Ok(json!({ "type": "Break", "location": json_span(location) }))
+    }
+
+    fn visit_continue_stmt(&mut self, location: &SrcLocation) -> Result<Value, RutoxError> {
+        Ok(json!({ "type": "Continue", "location": json_span(location) }))
+    }
+
+    fn visit_function_stmt(&mut self, function: &Rc<FunctionData>) -> Result<Value, RutoxError> {
+        Ok(self.function(function))
+    }
+
+    fn visit_return_stmt(
+        &mut self,
+        value: &Option<Expr>,
+        location: &SrcLocation,
+    ) -> Result<Value, RutoxError> {
+        Ok(json!({
+            "type": "Return",
+            "value": value.as_ref().map(|value| self.expr(value)),
+            "location": json_span(location),
+        }))
+    }
+
+    fn visit_throw_stmt(
+        &mut self,
+        value: &Expr,
+        location: &SrcLocation,
+    ) -> Result<Value, RutoxError> {
+        Ok(json!({
+            "type": "Throw",
+            "value": self.expr(value),
+            "location": json_span(location),
+        }))
+    }
+
+    fn visit_try_stmt(&mut self, try_data: &TryData) -> Result<Value, RutoxError> {
+        Ok(json!({
+            "type": "Try",
+            "body": self.stmts(&try_data.body),
+            "catch": try_data.catch.as_ref().map(|catch| json!({
+                "name": catch.name.lexeme,
+                "body": self.stmts(&catch.body),
+                "location": json_span(&catch.name.location),
+            })),
+            "finally": try_data.finally.as_ref().map(|finally| self.stmts(finally)),
+            "location": json_span(&try_data.location),
+        }))
+    }
+
+    fn visit_import_stmt(&mut self, import: &ImportData) -> Result<Value, RutoxError> {
+        Ok(json!({
+            "type": "Import",
+            "name": import.name,
+            "path": import.path,
+            "location": json_span(&import.location),
+        }))
+    }
+
+    fn visit_test_stmt(&mut self, test: &TestData) -> Result<Value, RutoxError> {
+        Ok(json!({
+            "type": "Test",
+            "name": test.name,
+            "body": self.stmts(&test.body),
+            "location": json_span(&test.location),
+        }))
+    }
+}
+
+impl ExprVisitor<Value> for JsonPrinter {
+    fn visit_literal_expr(&mut self, literal: &LiteralData) -> Result<Value, RutoxError> {
+        let value = match literal {
+            LiteralData::String(s, _) => json!(s),
+            LiteralData::Number(n, _) => json!(n),
+            LiteralData::Bool(b, _) => json!(b),
+            LiteralData::Nil(_) => Value::Null,
+        };
+
+        Ok(json!({
+            "type": "Literal",
+            "value": value,
+        }))
+    }
+
+    fn visit_unary_expr(&mut self, unary: &UnaryData) -> Result<Value, RutoxError> {
+        Ok(json!({
+            "type": "Unary",
+            "operator": unary.operator.to_string(),
+            "operand": self.expr(&unary.expr),
+        }))
+    }
+
+    fn visit_binary_expr(&mut self, binary: &BinaryData) -> Result<Value, RutoxError> {
+        Ok(json!({
+            "type": "Binary",
+            "operator": binary.operator.to_string(),
+            "left": self.expr(&binary.left),
+            "right": self.expr(&binary.right),
+        }))
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &Expr) -> Result<Value, RutoxError> {
+        Ok(json!({
+            "type": "Grouping",
+            "expression": self.expr(expr),
+        }))
+    }
+
+    fn visit_variable_expr(
+        &mut self,
+        name: &Token,
+        _location: &SrcLocation,
+    ) -> Result<Value, RutoxError> {
+        Ok(json!({
+            "type": "Variable",
+            "name": name.lexeme,
+        }))
+    }
+
+    fn visit_assign_expr(
+        &mut self,
+        name: &Token,
+        value: &Expr,
+        _location: &SrcLocation,
+    ) -> Result<Value, RutoxError> {
+        Ok(json!({
+            "type": "Assign",
+            "name": name.lexeme,
+            "value": self.expr(value),
+        }))
+    }
+
+    fn visit_logical_expr(
+        &mut self,
+        left: &Expr,
+        op: &LogicalOp,
+        right: &Expr,
+        _location: &SrcLocation,
+    ) -> Result<Value, RutoxError> {
+        Ok(json!({
+            "type": "Logical",
+            "operator": op.to_string(),
+            "left": self.expr(left),
+            "right": self.expr(right),
+        }))
+    }
+
+    fn visit_call_expr(&mut self, call: &CallData) -> Result<Value, RutoxError> {
+        Ok(json!({
+            "type": "Call",
+            "callee": self.expr(&call.callee),
+            "arguments": call.args.iter().map(|arg| self.expr(arg)).collect::<Vec<_>>(),
+        }))
+    }
+
+    fn visit_lambda_expr(&mut self, function: &Rc<FunctionData>) -> Result<Value, RutoxError> {
+        Ok(self.function(function))
+    }
+
+    fn visit_get_expr(
+        &mut self,
+        object: &Expr,
+        name: &Token,
+        _location: &SrcLocation,
+    ) -> Result<Value, RutoxError> {
+        Ok(json!({
+            "type": "Get",
+            "object": self.expr(object),
+            "name": name.lexeme,
+        }))
     }
 }
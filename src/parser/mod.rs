@@ -1,51 +1,154 @@
 pub mod ast;
+pub mod ast_printer;
 pub mod visitors;
+use crate::error_code::ErrorCode;
 use crate::rutox_error::RutoxError;
 use crate::scanner::{
     token::{Token, TokenKind},
-    SrcLocation,
+    FileId, SrcLocation,
 };
-use ast::{BinaryData, Expr, LiteralData, LogicalOp, Stmt, UnaryData, UnaryOp};
+use crate::suggestion;
+use ast::{
+    BinaryData, CallData, CatchData, Expr, FunctionData, ImportData, LiteralData, LogicalOp, Stmt,
+    TestData, TryData, UnaryData, UnaryOp,
+};
+use std::path::Path;
+use std::rc::Rc;
+
+/// How deeply expressions and statements can nest before parsing gives up,
+/// instead of overflowing the stack while parsing or running them.
+const MAX_NESTING: usize = 200;
 
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    loop_depth: usize,
+    function_depth: usize,
+    block_depth: usize,
+    nesting: usize,
+    /// Errors in statements that were skipped to keep parsing.
+    errors: Vec<RutoxError>,
+    /// Where each block still open at the end of the input starts.
+    unclosed_blocks: Vec<SrcLocation>,
 }
 
 impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Parser {
-        Parser { tokens, current: 0 }
+    pub fn new(mut tokens: Vec<Token>) -> Parser {
+        // The scanner always ends with one, but tokens may come from elsewhere
+        if tokens.last().map(|token| &token.kind) != Some(&TokenKind::Eof) {
+            let location = match tokens.last() {
+                Some(token) => token.location.clone(),
+                None => SrcLocation::new(1, 1, FileId::default()),
+            };
+            tokens.push(Token {
+                kind: TokenKind::Eof,
+                lexeme: String::new(),
+                location,
+            });
+        }
+
+        Parser {
+            tokens,
+            current: 0,
+            loop_depth: 0,
+            function_depth: 0,
+            block_depth: 0,
+            nesting: 0,
+            errors: vec![],
+            unclosed_blocks: vec![],
+        }
     }
 
     pub fn parse(&mut self) -> Result<Vec<Stmt>, RutoxError> {
         let mut stmts = vec![];
-        let mut errors = vec![];
 
         while !self.is_at_end() {
-            match self.declaration() {
+            let start = self.current;
+            match self.top_level_declaration() {
                 Ok(stmt) => stmts.push(stmt),
-                Err(err) => {
-                    errors.push(err);
-                    self.synchronize();
-                }
+                Err(error) => self.recover(error, start),
             }
         }
 
-        if errors.is_empty() {
+        if !self.unclosed_blocks.is_empty() {
+            self.report_unclosed_blocks();
+        }
+
+        if self.errors.is_empty() {
             Ok(stmts)
         } else {
-            Err(RutoxError::Multiple(errors))
+            Err(RutoxError::Multiple(std::mem::take(&mut self.errors)))
         }
     }
 
+    fn top_level_declaration(&mut self) -> Result<Stmt, RutoxError> {
+        // `test` is only special here, so it is not a reserved word
+        if self.check_test_declaration() {
+            let test_keyword = self.advance();
+            let TokenKind::String(name) = self.advance().kind else {
+                unreachable!("A test declaration should have a name");
+            };
+            self.expect(TokenKind::LBrace, "Expect `{` before test body")?;
+
+            return Ok(Stmt::Test(TestData {
+                name,
+                body: self.block()?,
+                location: test_keyword.location,
+            }));
+        }
+
+        self.declaration()
+    }
+
     fn declaration(&mut self) -> Result<Stmt, RutoxError> {
         if self.match_any(&[TokenKind::Var]) {
             return self.var_declaration();
         }
+        if self.match_any(&[TokenKind::Import]) {
+            return self.import_declaration();
+        }
+        // `fun (` starts a lambda, which is parsed as an expression statement
+        if self.check(&TokenKind::Fun) && self.check_next_identifier() {
+            self.advance();
+            let name = self.advance();
+
+            return Ok(Stmt::Function(Rc::new(self.function(Some(name))?)));
+        }
 
         self.statement()
     }
 
+    fn function(&mut self, name: Option<Token>) -> Result<FunctionData, RutoxError> {
+        let location = self.previous_location();
+        self.expect(TokenKind::LParen, "Expect `(` before parameters")?;
+
+        let mut params = vec![];
+        if !self.check(&TokenKind::RParen) {
+            loop {
+                params.push(self.expect_identifier("Expect parameter name")?);
+
+                if !self.match_any(&[TokenKind::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.expect(TokenKind::RParen, "Expect `)` after parameters")?;
+        self.expect(TokenKind::LBrace, "Expect `{` before function body")?;
+
+        let enclosing_loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        self.function_depth += 1;
+        let body = self.block();
+        self.function_depth -= 1;
+        self.loop_depth = enclosing_loop_depth;
+
+        Ok(FunctionData {
+            name,
+            params,
+            body: body?,
+            location,
+        })
+    }
+
     fn var_declaration(&mut self) -> Result<Stmt, RutoxError> {
         let var_keyword_location = self.previous_location();
 
@@ -67,18 +170,82 @@ impl Parser {
                     Ok(Stmt::Var(token.clone(), initializer, location))
                 }
                 _ => Err(RutoxError::Syntax(
+                    ErrorCode::ExpectedIdentifier,
                     format!("Expect variable name, got {token}"),
                     self.previous_location(),
                 )),
             }
         } else {
             Err(RutoxError::Syntax(
+                ErrorCode::ExpectedIdentifier,
                 "Expect variable name, got EOF".to_string(),
                 self.previous_location(),
             ))
         }
     }
 
+    fn import_declaration(&mut self) -> Result<Stmt, RutoxError> {
+        let import_keyword_location = self.previous_location();
+
+        // `from` is only special here, so it is not a reserved word
+        let alias = if self.check_next_identifier_named("from") {
+            let alias = self.expect_identifier("Expect module name")?;
+            self.advance();
+
+            Some(alias)
+        } else {
+            None
+        };
+
+        let path = match self.peek().map(|token| token.kind.clone()) {
+            Some(TokenKind::String(path)) => {
+                self.advance();
+                path
+            }
+            _ => {
+                return Err(RutoxError::Syntax(
+                    ErrorCode::InvalidImport,
+                    "Expect module path string after `import`".to_string(),
+                    self.current_location(),
+                ))
+            }
+        };
+        self.expect(TokenKind::Semicolon, "Expect `;` after import")?;
+
+        let name = match alias {
+            Some(alias) => alias.lexeme,
+            None => Self::module_name(&path).ok_or_else(|| {
+                RutoxError::Syntax(
+                    ErrorCode::InvalidImport,
+                    format!("Cannot name module `{path}`, use `import name from \"{path}\";`"),
+                    import_keyword_location.clone(),
+                )
+            })?,
+        };
+
+        Ok(Stmt::Import(ImportData {
+            name,
+            path,
+            location: import_keyword_location,
+        }))
+    }
+
+    /// The name a module is bound to when imported without `from`, which is its
+    /// file name without the extension (`lib/math.lox` is bound to `math`).
+    pub fn module_name(path: &str) -> Option<String> {
+        let stem = Path::new(path).file_stem()?.to_str()?;
+        let starts_like_identifier = stem
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+
+        if starts_like_identifier && stem.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            Some(stem.to_string())
+        } else {
+            None
+        }
+    }
+
     fn statement(&mut self) -> Result<Stmt, RutoxError> {
         if self.match_any(&[TokenKind::If]) {
             return self.if_statement();
@@ -86,6 +253,24 @@ impl Parser {
         if self.match_any(&[TokenKind::Print]) {
             return self.print_statement();
         }
+        if self.match_any(&[TokenKind::While]) {
+            return self.while_statement();
+        }
+        if self.match_any(&[TokenKind::Break]) {
+            return self.break_statement();
+        }
+        if self.match_any(&[TokenKind::Continue]) {
+            return self.continue_statement();
+        }
+        if self.match_any(&[TokenKind::Return]) {
+            return self.return_statement();
+        }
+        if self.match_any(&[TokenKind::Throw]) {
+            return self.throw_statement();
+        }
+        if self.match_any(&[TokenKind::Try]) {
+            return self.try_statement();
+        }
         if self.match_any(&[TokenKind::LBrace]) {
             return Ok(Stmt::Block(self.block()?, self.previous_location()));
         }
@@ -99,9 +284,9 @@ impl Parser {
         let condition = self.expression()?;
         self.expect(TokenKind::RParen, "Expect `)` after if condition")?;
 
-        let then = self.statement()?;
+        let then = self.nested(Self::statement)?;
         let else_branch = if self.match_any(&[TokenKind::Else]) {
-            Some(Box::new(self.statement()?))
+            Some(Box::new(self.nested(Self::statement)?))
         } else {
             None
         };
@@ -114,34 +299,203 @@ impl Parser {
         ))
     }
 
+    fn while_statement(&mut self) -> Result<Stmt, RutoxError> {
+        let while_keyword_location = self.previous_location();
+        self.expect(TokenKind::LParen, "Expect `(` after `while`")?;
+        let condition = self.expression()?;
+        self.expect(TokenKind::RParen, "Expect `)` after while condition")?;
+
+        self.loop_depth += 1;
+        let body = self.nested(Self::statement);
+        self.loop_depth -= 1;
+
+        Ok(Stmt::While(
+            condition,
+            Box::new(body?),
+            while_keyword_location,
+        ))
+    }
+
+    fn break_statement(&mut self) -> Result<Stmt, RutoxError> {
+        let break_keyword_location = self.previous_location();
+        if self.loop_depth == 0 {
+            return Err(RutoxError::Syntax(
+                ErrorCode::LoopControlOutsideLoop,
+                "Cannot use `break` outside of a loop".to_string(),
+                break_keyword_location,
+            ));
+        }
+        self.expect(TokenKind::Semicolon, "Expect `;` after `break`")?;
+
+        Ok(Stmt::Break(break_keyword_location))
+    }
+
+    fn continue_statement(&mut self) -> Result<Stmt, RutoxError> {
+        let continue_keyword_location = self.previous_location();
+        if self.loop_depth == 0 {
+            return Err(RutoxError::Syntax(
+                ErrorCode::LoopControlOutsideLoop,
+                "Cannot use `continue` outside of a loop".to_string(),
+                continue_keyword_location,
+            ));
+        }
+        self.expect(TokenKind::Semicolon, "Expect `;` after `continue`")?;
+
+        Ok(Stmt::Continue(continue_keyword_location))
+    }
+
+    fn return_statement(&mut self) -> Result<Stmt, RutoxError> {
+        let return_keyword_location = self.previous_location();
+        if self.function_depth == 0 {
+            return Err(RutoxError::Syntax(
+                ErrorCode::ReturnOutsideFunction,
+                "Cannot use `return` outside of a function".to_string(),
+                return_keyword_location,
+            ));
+        }
+
+        let value = if self.check(&TokenKind::Semicolon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.expect(TokenKind::Semicolon, "Expect `;` after return value")?;
+
+        Ok(Stmt::Return(value, return_keyword_location))
+    }
+
+    fn throw_statement(&mut self) -> Result<Stmt, RutoxError> {
+        let throw_keyword_location = self.previous_location();
+        let value = self.expression()?;
+        self.expect(TokenKind::Semicolon, "Expect `;` after thrown value")?;
+
+        Ok(Stmt::Throw(value, throw_keyword_location))
+    }
+
+    fn try_statement(&mut self) -> Result<Stmt, RutoxError> {
+        let try_keyword_location = self.previous_location();
+        self.expect(TokenKind::LBrace, "Expect `{` after `try`")?;
+        let body = self.block()?;
+
+        let catch = if self.match_any(&[TokenKind::Catch]) {
+            self.expect(TokenKind::LParen, "Expect `(` after `catch`")?;
+            let name = self.expect_identifier("Expect error variable name")?;
+            self.expect(TokenKind::RParen, "Expect `)` after error variable name")?;
+            self.expect(TokenKind::LBrace, "Expect `{` after catch clause")?;
+
+            Some(CatchData {
+                name,
+                body: self.block()?,
+            })
+        } else {
+            None
+        };
+
+        let finally = if self.match_any(&[TokenKind::Finally]) {
+            self.expect(TokenKind::LBrace, "Expect `{` after `finally`")?;
+
+            Some(self.block()?)
+        } else {
+            None
+        };
+
+        if catch.is_none() && finally.is_none() {
+            return Err(RutoxError::Syntax(
+                ErrorCode::TryWithoutHandler,
+                "Expect `catch` or `finally` after try block".to_string(),
+                self.current_location(),
+            ));
+        }
+
+        Ok(Stmt::Try(TryData {
+            body,
+            catch,
+            finally,
+            location: try_keyword_location,
+        }))
+    }
+
+    /// The statements up to the closing `}`, for a block whose `{` was just
+    /// consumed. Errors in the statements are recorded so parsing can go on
+    /// with the rest of the block.
     fn block(&mut self) -> Result<Vec<Stmt>, RutoxError> {
+        let opening_brace_location = self.previous_location();
         let mut stmts = vec![];
 
+        self.block_depth += 1;
         while !self.check(&TokenKind::RBrace) && !self.is_at_end() {
-            stmts.push(self.declaration()?);
+            let start = self.current;
+            match self.nested(Self::declaration) {
+                Ok(stmt) => stmts.push(stmt),
+                Err(error) => self.recover(error, start),
+            }
         }
+        self.block_depth -= 1;
 
-        self.expect(TokenKind::RBrace, "Expect `}` after block")?;
+        if self.is_at_end() {
+            // Reported once for all open blocks, when parsing is done
+            self.unclosed_blocks.push(opening_brace_location);
+        } else {
+            self.advance();
+        }
 
         Ok(stmts)
     }
 
+    fn report_unclosed_blocks(&mut self) {
+        let error = RutoxError::Syntax(
+            ErrorCode::MissingToken,
+            "Expect `}` after block".to_string(),
+            self.current_location(),
+        );
+        let error = std::mem::take(&mut self.unclosed_blocks).into_iter().fold(
+            error,
+            |error, opening_brace_location| {
+                error.with_label(
+                    "this `{` is never closed".to_string(),
+                    opening_brace_location,
+                )
+            },
+        );
+
+        self.errors.push(error);
+    }
+
     fn print_statement(&mut self) -> Result<Stmt, RutoxError> {
+        let print_keyword_location = self.previous_location();
         let value = self.expression()?;
         self.expect(TokenKind::Semicolon, "Expect `;` after print value")?;
 
-        Ok(Stmt::Print(value, self.previous_location()))
+        Ok(Stmt::Print(value, print_keyword_location))
     }
 
     fn expression_statement(&mut self) -> Result<Stmt, RutoxError> {
         let expr = self.expression()?;
-        self.expect(TokenKind::Semicolon, "Expect `;` after expression")?;
+        self.expect(TokenKind::Semicolon, "Expect `;` after expression")
+            .map_err(|error| Self::with_keyword_help(error, &expr))?;
 
-        Ok(Stmt::Expr(expr.clone(), expr.location()))
+        Ok(Stmt::Expr(expr.clone(), expr.span()))
+    }
+
+    /// Statements like `retrun x;` or `funtion f() {}` parse as an expression
+    /// that starts with a misspelled keyword, so suggest the keyword.
+    fn with_keyword_help(error: RutoxError, expr: &Expr) -> RutoxError {
+        let head = match expr {
+            Expr::Call(call) => &*call.callee,
+            other => other,
+        };
+
+        match head {
+            Expr::Variable(name, _) => match suggestion::keyword_for(&name.lexeme) {
+                Some(keyword) => error.with_help(format!("did you mean the keyword `{keyword}`?")),
+                None => error,
+            },
+            _ => error,
+        }
     }
 
     fn expression(&mut self) -> Result<Expr, RutoxError> {
-        self.assignment()
+        self.nested(Self::assignment)
     }
 
     fn assignment(&mut self) -> Result<Expr, RutoxError> {
@@ -149,7 +503,7 @@ impl Parser {
 
         if self.match_any(&[TokenKind::Equal]) {
             let operator = self.previous();
-            let value = self.assignment()?;
+            let value = self.nested(Self::assignment)?;
 
             match &expr {
                 Expr::Variable(name, _location) => {
@@ -159,6 +513,7 @@ impl Parser {
                 }
                 _ => {
                     return Err(RutoxError::Syntax(
+                        ErrorCode::InvalidAssignmentTarget,
                         format!("Expect assignment target to be a variable, got {:?}", expr),
                         expr.location(),
                     ))
@@ -170,122 +525,95 @@ impl Parser {
     }
 
     fn or(&mut self) -> Result<Expr, RutoxError> {
-        let mut expr = self.and()?;
-
-        while self.match_any(&[TokenKind::Or]) {
-            let operator = self.previous();
-            let right = self.and()?;
-
-            expr = Expr::Logical(
-                Box::new(expr),
-                LogicalOp::Or(operator.location.clone()),
-                Box::new(right),
-                operator.location,
-            );
-        }
-
-        Ok(expr)
+        self.logical(Self::and, TokenKind::Or)
     }
 
     fn and(&mut self) -> Result<Expr, RutoxError> {
-        let mut expr = self.equality()?;
-
-        while self.match_any(&[TokenKind::And]) {
-            let operator = self.previous();
-            let right = self.equality()?;
-
-            expr = Expr::Logical(
-                Box::new(expr),
-                LogicalOp::And(operator.location.clone()),
-                Box::new(right),
-                operator.location,
-            );
-        }
-
-        Ok(expr)
+        self.logical(Self::equality, TokenKind::And)
     }
 
     fn equality(&mut self) -> Result<Expr, RutoxError> {
-        let mut expr = self.comparison()?;
-
-        while self.match_any(&[TokenKind::BangEqual, TokenKind::EqualEqual]) {
-            let operator = self.previous();
-            let right = self.comparison()?;
-
-            expr = Expr::Binary(BinaryData {
-                left: Box::new(expr),
-                location: operator.location.clone(),
-                operator: operator.into(),
-                right: Box::new(right),
-            });
-        }
-
-        Ok(expr)
+        self.binary(
+            Self::comparison,
+            &[TokenKind::BangEqual, TokenKind::EqualEqual],
+        )
     }
 
     fn comparison(&mut self) -> Result<Expr, RutoxError> {
-        let mut expr = self.term()?;
-
-        while self.match_any(&[
-            TokenKind::Greater,
-            TokenKind::GreaterEqual,
-            TokenKind::Less,
-            TokenKind::LessEqual,
-        ]) {
-            let operator = self.previous();
-            let right = self.term()?;
-
-            expr = Expr::Binary(BinaryData {
-                left: Box::new(expr),
-                location: operator.location.clone(),
-                operator: operator.into(),
-                right: Box::new(right),
-            });
-        }
-
-        Ok(expr)
+        self.binary(
+            Self::term,
+            &[
+                TokenKind::Greater,
+                TokenKind::GreaterEqual,
+                TokenKind::Less,
+                TokenKind::LessEqual,
+            ],
+        )
     }
 
     fn term(&mut self) -> Result<Expr, RutoxError> {
-        let mut expr = self.factor()?;
-
-        while self.match_any(&[TokenKind::Minus, TokenKind::Plus]) {
-            let operator = self.previous();
-            let right = self.factor()?;
-
-            expr = Expr::Binary(BinaryData {
-                left: Box::new(expr),
-                location: operator.location.clone(),
-                operator: operator.into(),
-                right: Box::new(right),
-            });
-        }
-
-        Ok(expr)
+        self.binary(Self::factor, &[TokenKind::Minus, TokenKind::Plus])
     }
 
     fn factor(&mut self) -> Result<Expr, RutoxError> {
-        let mut expr = self.unary()?;
+        self.binary(Self::unary, &[TokenKind::Slash, TokenKind::Star])
+    }
 
-        while self.match_any(&[TokenKind::Slash, TokenKind::Star]) {
-            let operator = self.previous();
-            let right = self.unary()?;
+    /// Parses `operand`s joined by `operator`, like `a or b or c`.
+    fn logical(
+        &mut self,
+        operand: fn(&mut Self) -> Result<Expr, RutoxError>,
+        operator: TokenKind,
+    ) -> Result<Expr, RutoxError> {
+        self.chain(|parser| {
+            let mut expr = operand(parser)?;
+
+            while parser.match_any(std::slice::from_ref(&operator)) {
+                let operator = parser.previous();
+                parser.link()?;
+                let right = operand(parser)?;
+
+                let op = match operator.kind {
+                    TokenKind::And => LogicalOp::And(operator.location.clone()),
+                    _ => LogicalOp::Or(operator.location.clone()),
+                };
+                expr = Expr::Logical(Box::new(expr), op, Box::new(right), operator.location);
+            }
 
-            expr = Expr::Binary(BinaryData {
-                left: Box::new(expr),
-                location: operator.location.clone(),
-                operator: operator.into(),
-                right: Box::new(right),
-            });
-        }
+            Ok(expr)
+        })
+    }
 
-        Ok(expr)
+    /// Parses `operand`s joined by any of `operators`, like `a + b - c`.
+    fn binary(
+        &mut self,
+        operand: fn(&mut Self) -> Result<Expr, RutoxError>,
+        operators: &[TokenKind],
+    ) -> Result<Expr, RutoxError> {
+        self.chain(|parser| {
+            let mut expr = operand(parser)?;
+
+            while parser.match_any(operators) {
+                let operator = parser.previous();
+                parser.link()?;
+                let right = operand(parser)?;
+
+                expr = Expr::Binary(BinaryData {
+                    left: Box::new(expr),
+                    location: operator.location.clone(),
+                    operator: operator.try_into()?,
+                    right: Box::new(right),
+                });
+            }
+
+            Ok(expr)
+        })
     }
 
     fn unary(&mut self) -> Result<Expr, RutoxError> {
         if self.match_any(&[TokenKind::Bang, TokenKind::Minus]) {
             let operator = self.previous();
-            let expr = self.unary()?;
+            let expr = self.nested(Self::unary)?;
             let op = if operator.kind == TokenKind::Bang {
                 UnaryOp::Bang(operator.location.clone())
             } else {
@@ -299,7 +627,52 @@ impl Parser {
             }));
         }
 
-        self.primary()
+        self.call()
+    }
+
+    fn call(&mut self) -> Result<Expr, RutoxError> {
+        self.chain(|parser| {
+            let mut expr = parser.primary()?;
+
+            loop {
+                if parser.match_any(&[TokenKind::LParen]) {
+                    parser.link()?;
+                    expr = parser.finish_call(expr)?;
+                } else if parser.match_any(&[TokenKind::Dot]) {
+                    parser.link()?;
+                    let location = parser.previous_location();
+                    let name = parser.expect_identifier("Expect property name after `.`")?;
+
+                    expr = Expr::Get(Box::new(expr), name, location);
+                } else {
+                    break;
+                }
+            }
+
+            Ok(expr)
+        })
+    }
+
+    fn finish_call(&mut self, callee: Expr) -> Result<Expr, RutoxError> {
+        let location = self.previous_location();
+        let mut args = vec![];
+
+        if !self.check(&TokenKind::RParen) {
+            loop {
+                args.push(self.expression()?);
+
+                if !self.match_any(&[TokenKind::Comma]) {
+                    break;
+                }
+            }
+        }
+        let closing_paren = self.expect(TokenKind::RParen, "Expect `)` after arguments")?;
+
+        Ok(Expr::Call(CallData {
+            callee: Box::new(callee),
+            args,
+            location: location.to(&closing_paren.location),
+        }))
     }
 
     fn primary(&mut self) -> Result<Expr, RutoxError> {
@@ -322,23 +695,52 @@ impl Parser {
             TokenKind::Identifier(_) => Ok(Expr::Variable(token.clone(), token.location.clone())),
             TokenKind::LParen => {
                 let expr = self.expression()?;
-                self.expect(TokenKind::RParen, "Expect `)` after expression")?;
+                let closing_paren =
+                    self.expect(TokenKind::RParen, "Expect `)` after expression")?;
 
-                Ok(Expr::Grouping(Box::new(expr), token.location.clone()))
+                Ok(Expr::Grouping(
+                    Box::new(expr),
+                    token.location.to(&closing_paren.location),
+                ))
+            }
+            TokenKind::Fun => Ok(Expr::Lambda(Rc::new(self.function(None)?))),
+            _ => {
+                // Leave the token for `synchronize`, as it may close a block
+                self.current -= 1;
+
+                Err(RutoxError::Syntax(
+                    ErrorCode::ExpectedExpression,
+                    format!("Expect expression, got `{}`", token),
+                    token.location.clone(),
+                ))
             }
-            _ => Err(RutoxError::Syntax(
-                format!("Expect expression, got `{}`", token),
-                token.location.clone(),
-            )),
         }
     }
 
-    #[allow(dead_code)]
-    fn synchronize(&mut self) {
-        self.advance();
+    /// Records an error in the statement that started at token `start` and
+    /// moves on to the next one.
+    fn recover(&mut self, error: RutoxError, start: usize) {
+        self.errors.push(error);
+        self.synchronize(start);
+    }
+
+    /// Skips tokens until the start of the next statement. Inside a block, a
+    /// `}` is left alone, so the block still ends where it should.
+    fn synchronize(&mut self, start: usize) {
+        let closes_block =
+            |parser: &Parser| parser.block_depth > 0 && parser.check(&TokenKind::RBrace);
+
+        if closes_block(self) {
+            return;
+        }
+        // The token that failed may start the next statement, e.g. the `print`
+        // after `print a` with no `;`. Only skip it if nothing else was.
+        if self.current == start {
+            self.advance();
+        }
 
         while !self.is_at_end() {
-            if self.previous().kind == TokenKind::Semicolon {
+            if self.previous().kind == TokenKind::Semicolon || closes_block(self) {
                 return;
             }
 
@@ -350,7 +752,12 @@ impl Parser {
                 | TokenKind::If
                 | TokenKind::While
                 | TokenKind::Print
-                | TokenKind::Return => return,
+                | TokenKind::Return
+                | TokenKind::Break
+                | TokenKind::Continue
+                | TokenKind::Throw
+                | TokenKind::Try
+                | TokenKind::Import => return,
                 _ => self.advance(),
             };
         }
@@ -376,6 +783,46 @@ impl Parser {
         }
     }
 
+    fn check_next_identifier(&self) -> bool {
+        matches!(
+            self.tokens.get(self.current + 1),
+            Some(Token {
+                kind: TokenKind::Identifier(_),
+                ..
+            })
+        )
+    }
+
+    /// Whether a `test "name"` declaration starts here.
+    fn check_test_declaration(&self) -> bool {
+        let is_test = matches!(
+            self.peek(),
+            Some(Token {
+                kind: TokenKind::Identifier(identifier),
+                ..
+            }) if identifier == "test"
+        );
+        let has_name = matches!(
+            self.tokens.get(self.current + 1),
+            Some(Token {
+                kind: TokenKind::String(_),
+                ..
+            })
+        );
+
+        is_test && has_name
+    }
+
+    fn check_next_identifier_named(&self, name: &str) -> bool {
+        matches!(
+            self.tokens.get(self.current + 1),
+            Some(Token {
+                kind: TokenKind::Identifier(identifier),
+                ..
+            }) if identifier == name
+        )
+    }
+
     fn peek(&self) -> Option<&Token> {
         self.tokens.get(self.current)
     }
@@ -383,6 +830,7 @@ impl Parser {
     fn try_advance(&mut self) -> Result<Token, RutoxError> {
         if self.is_at_end() {
             Err(RutoxError::Syntax(
+                ErrorCode::ExpectedExpression,
                 "Unexpected end of input".to_string(),
                 self.previous_location(),
             ))
@@ -392,8 +840,49 @@ impl Parser {
         }
     }
 
+    /// Parses with `parse` one level deeper, failing if that is too deep.
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, RutoxError>,
+    ) -> Result<T, RutoxError> {
+        self.chain(|parser| {
+            parser.link()?;
+            parse(parser)
+        })
+    }
+
+    /// Parses a chain like `a + b + c` or `f(a)(b).c` with `parse`, which
+    /// calls `link` for each link. The chain nests as deeply as it is long,
+    /// so its links count towards the nesting until it ends.
+    fn chain<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, RutoxError>,
+    ) -> Result<T, RutoxError> {
+        let nesting = self.nesting;
+        let result = parse(self);
+        self.nesting = nesting;
+
+        result
+    }
+
+    /// Goes one level deeper, failing if that is too deep.
+    fn link(&mut self) -> Result<(), RutoxError> {
+        if self.nesting >= MAX_NESTING {
+            return Err(RutoxError::Syntax(
+                ErrorCode::TooDeeplyNested,
+                "Code is nested too deeply".to_string(),
+                self.current_location(),
+            ));
+        }
+
+        self.nesting += 1;
+
+        Ok(())
+    }
+
+    /// The last token consumed, or the first one if none was.
     fn previous(&self) -> Token {
-        self.tokens.get(self.current - 1).unwrap().clone()
+        self.tokens[self.current.saturating_sub(1)].clone()
     }
 
     fn is_at_end(&self) -> bool {
@@ -413,12 +902,27 @@ impl Parser {
             Ok(self.advance())
         } else {
             Err(RutoxError::Syntax(
+                ErrorCode::MissingToken,
                 message.to_string(),
                 self.current_location(),
             ))
         }
     }
 
+    fn expect_identifier(&mut self, message: &str) -> Result<Token, RutoxError> {
+        match self.peek() {
+            Some(Token {
+                kind: TokenKind::Identifier(_),
+                ..
+            }) => Ok(self.advance()),
+            _ => Err(RutoxError::Syntax(
+                ErrorCode::ExpectedIdentifier,
+                message.to_string(),
+                self.current_location(),
+            )),
+        }
+    }
+
     fn current_location(&self) -> SrcLocation {
         match self.peek() {
             Some(token) => token.location.clone(),
@@ -3,33 +3,45 @@ use crate::{
     rutox_error::RutoxError,
     scanner::{token::Token, SrcLocation},
 };
+use std::rc::Rc;
 
 pub trait ExprVisitor<T> {
     fn visit_expr(&mut self, expr: &Expr) -> Result<T, RutoxError> {
+        self.walk_expr(expr)
+    }
+
+    /// Dispatches `expr` to its `visit_*` method. Visitors that override
+    /// `visit_expr` to do something around every expression call this.
+    fn walk_expr(&mut self, expr: &Expr) -> Result<T, RutoxError> {
         match expr {
             Expr::Literal(literal) => self.visit_literal_expr(literal),
             Expr::Unary(args) => self.visit_unary_expr(args),
             Expr::Binary(args) => self.visit_binary_expr(args),
             Expr::Grouping(grouped_expr, _) => self.visit_grouping_expr(grouped_expr),
             Expr::Variable(name, location) => self.visit_variable_expr(name, location),
-            Expr::Assign(name, value, location) => {
-                self.visit_assign_expr(name, (*value).clone(), location)
-            }
+            Expr::Assign(name, value, location) => self.visit_assign_expr(name, value, location),
             Expr::Logical(left, op, right, location) => {
                 self.visit_logical_expr(left, op, right, location)
             }
+            Expr::Call(call) => self.visit_call_expr(call),
+            Expr::Lambda(function) => self.visit_lambda_expr(function),
+            Expr::Get(object, name, location) => self.visit_get_expr(object, name, location),
         }
     }
 
-    fn visit_literal_expr(&self, literal: &LiteralData) -> Result<T, RutoxError>;
+    fn visit_literal_expr(&mut self, literal: &LiteralData) -> Result<T, RutoxError>;
     fn visit_unary_expr(&mut self, unary: &UnaryData) -> Result<T, RutoxError>;
     fn visit_binary_expr(&mut self, binary: &BinaryData) -> Result<T, RutoxError>;
     fn visit_grouping_expr(&mut self, expr: &Expr) -> Result<T, RutoxError>;
-    fn visit_variable_expr(&self, name: &Token, location: &SrcLocation) -> Result<T, RutoxError>;
+    fn visit_variable_expr(
+        &mut self,
+        name: &Token,
+        location: &SrcLocation,
+    ) -> Result<T, RutoxError>;
     fn visit_assign_expr(
         &mut self,
         name: &Token,
-        value: Box<Expr>,
+        value: &Expr,
         location: &SrcLocation,
     ) -> Result<T, RutoxError>;
     fn visit_logical_expr(
@@ -39,10 +51,24 @@ pub trait ExprVisitor<T> {
         right: &Expr,
         location: &SrcLocation,
     ) -> Result<T, RutoxError>;
+    fn visit_call_expr(&mut self, call: &CallData) -> Result<T, RutoxError>;
+    fn visit_lambda_expr(&mut self, function: &Rc<FunctionData>) -> Result<T, RutoxError>;
+    fn visit_get_expr(
+        &mut self,
+        object: &Expr,
+        name: &Token,
+        location: &SrcLocation,
+    ) -> Result<T, RutoxError>;
 }
 
 pub trait StmtVisitor<T> {
     fn visit_stmt(&mut self, stmt: &Stmt) -> Result<T, RutoxError> {
+        self.walk_stmt(stmt)
+    }
+
+    /// Dispatches `stmt` to its `visit_*` method. Visitors that override
+    /// `visit_stmt` to do something around every statement call this.
+    fn walk_stmt(&mut self, stmt: &Stmt) -> Result<T, RutoxError> {
         match stmt {
             Stmt::Print(expr, location) => self.visit_print_stmt(expr, location),
             Stmt::Expr(expr, location) => self.visit_expr_stmt(expr, location),
@@ -53,6 +79,15 @@ pub trait StmtVisitor<T> {
             Stmt::If(cond, then_branch, else_branch, location) => {
                 self.visit_if_stmt(cond, then_branch, else_branch, location)
             }
+            Stmt::While(cond, body, location) => self.visit_while_stmt(cond, body, location),
+            Stmt::Break(location) => self.visit_break_stmt(location),
+            Stmt::Continue(location) => self.visit_continue_stmt(location),
+            Stmt::Function(function) => self.visit_function_stmt(function),
+            Stmt::Return(value, location) => self.visit_return_stmt(value, location),
+            Stmt::Throw(value, location) => self.visit_throw_stmt(value, location),
+            Stmt::Try(try_data) => self.visit_try_stmt(try_data),
+            Stmt::Import(import) => self.visit_import_stmt(import),
+            Stmt::Test(test) => self.visit_test_stmt(test),
         }
     }
 
@@ -69,8 +104,26 @@ pub trait StmtVisitor<T> {
     fn visit_if_stmt(
         &mut self,
         cond: &Expr,
-        then_branch: &Box<Stmt>,
+        then_branch: &Stmt,
         else_branch: &Option<Box<Stmt>>,
         location: &SrcLocation,
     ) -> Result<T, RutoxError>;
+    fn visit_while_stmt(
+        &mut self,
+        cond: &Expr,
+        body: &Stmt,
+        location: &SrcLocation,
+    ) -> Result<T, RutoxError>;
+    fn visit_break_stmt(&mut self, location: &SrcLocation) -> Result<T, RutoxError>;
+    fn visit_continue_stmt(&mut self, location: &SrcLocation) -> Result<T, RutoxError>;
+    fn visit_function_stmt(&mut self, function: &Rc<FunctionData>) -> Result<T, RutoxError>;
+    fn visit_return_stmt(
+        &mut self,
+        value: &Option<Expr>,
+        location: &SrcLocation,
+    ) -> Result<T, RutoxError>;
+    fn visit_throw_stmt(&mut self, value: &Expr, location: &SrcLocation) -> Result<T, RutoxError>;
+    fn visit_try_stmt(&mut self, try_data: &TryData) -> Result<T, RutoxError>;
+    fn visit_import_stmt(&mut self, import: &ImportData) -> Result<T, RutoxError>;
+    fn visit_test_stmt(&mut self, test: &TestData) -> Result<T, RutoxError>;
 }
@@ -0,0 +1,210 @@
+use crate::interpreter::{Hook, Interpreter, LoxFunction};
+use crate::parser::ast::Stmt;
+use crate::rutox_error::RutoxError;
+use crate::scanner::{FileId, SourceMap, SrcLocation};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Times every statement for `rutox profile`. Clones share their
+/// measurements, so one can be given to the interpreter and the other
+/// kept for the report.
+#[derive(Clone, Default)]
+pub struct Profiler {
+    profile: Rc<RefCell<Profile>>,
+}
+
+#[derive(Default)]
+struct Profile {
+    /// The statements being run, innermost last.
+    running: Vec<Running>,
+    /// The functions being run, innermost last.
+    functions: Vec<String>,
+    lines: HashMap<(FileId, usize), LineStats>,
+    /// The time each top-level statement took, in the order they ran.
+    top_level: Vec<(SrcLocation, Duration)>,
+    /// The time spent in each line itself, by the functions being run.
+    folded: HashMap<(Vec<String>, FileId, usize), Duration>,
+}
+
+struct Running {
+    location: SrcLocation,
+    /// How many functions were being run when it started.
+    call_depth: usize,
+    start: Instant,
+    /// Time spent in the statements nested in this one.
+    nested: Duration,
+}
+
+#[derive(Default)]
+struct LineStats {
+    count: usize,
+    /// Including the statements nested in the line's and the functions
+    /// they call.
+    total: Duration,
+    /// Excluding them.
+    own: Duration,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lines sorted by total time, then the top-level statements in order.
+    pub fn report(&self, source_map: &SourceMap) -> String {
+        let profile = self.profile.borrow();
+        let code = |file: FileId, line: usize| {
+            source_map
+                .get(file)
+                .content
+                .lines()
+                .nth(line - 1)
+                .unwrap_or_default()
+                .trim()
+                .to_string()
+        };
+        let place = |file: FileId, line: usize| format!("{}:{line}", source_map.get(file).path);
+
+        let mut lines: Vec<_> = profile.lines.iter().collect();
+        lines.sort_by(|(a_line, a), (b_line, b)| {
+            b.total.cmp(&a.total).then(a_line.1.cmp(&b_line.1))
+        });
+
+        let mut report = format!(
+            "{:<20} {:>8} {:>12} {:>12}  code\n",
+            "line", "count", "total (ms)", "self (ms)"
+        );
+        for ((file, line), stats) in lines {
+            report.push_str(&format!(
+                "{:<20} {:>8} {:>12.3} {:>12.3}  {}\n",
+                place(*file, *line),
+                stats.count,
+                millis(stats.total),
+                millis(stats.own),
+                code(*file, *line),
+            ));
+        }
+
+        report.push_str(&format!(
+            "\n{:<20} {:>12}  code\n",
+            "top-level", "total (ms)"
+        ));
+        for (location, time) in &profile.top_level {
+            report.push_str(&format!(
+                "{:<20} {:>12.3}  {}\n",
+                place(location.file, location.line),
+                millis(*time),
+                code(location.file, location.line),
+            ));
+        }
+
+        report
+    }
+
+    /// The time spent in each line as folded stacks, e.g.
+    /// `<script>;fib;fib.lox:3 1250`, with times in nanoseconds. This is
+    /// what `flamegraph.pl` and `inferno-flamegraph` read.
+    pub fn folded(&self, source_map: &SourceMap) -> String {
+        let profile = self.profile.borrow();
+        let mut stacks: Vec<String> = profile
+            .folded
+            .iter()
+            .map(|((functions, file, line), time)| {
+                let mut frames = vec!["<script>".to_string()];
+                frames.extend(functions.iter().cloned());
+                frames.push(format!("{}:{line}", source_map.get(*file).path));
+
+                format!("{} {}\n", frames.join(";"), time.as_nanos())
+            })
+            .collect();
+        stacks.sort();
+
+        stacks.concat()
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+impl Hook for Profiler {
+    fn before_stmt(
+        &mut self,
+        _interpreter: &mut Interpreter,
+        stmt: &Stmt,
+    ) -> Result<(), RutoxError> {
+        // Blocks are located at their `}`, so their time is left to the
+        // statements in them
+        if let Stmt::Block(_, _) = stmt {
+            return Ok(());
+        }
+
+        let mut profile = self.profile.borrow_mut();
+        let call_depth = profile.functions.len();
+        profile.running.push(Running {
+            location: stmt.location(),
+            call_depth,
+            start: Instant::now(),
+            nested: Duration::ZERO,
+        });
+
+        Ok(())
+    }
+
+    fn after_stmt(&mut self, _interpreter: &Interpreter, stmt: &Stmt) {
+        if let Stmt::Block(_, _) = stmt {
+            return;
+        }
+
+        let mut profile = self.profile.borrow_mut();
+        let Some(running) = profile.running.pop() else {
+            return;
+        };
+        let total = running.start.elapsed();
+        let own = total.saturating_sub(running.nested);
+        let location = running.location;
+
+        let same_line = |outer: &&Running| {
+            outer.location.file == location.file && outer.location.line == location.line
+        };
+        // Statements nested in one line, e.g. `if (a) return b;`, run it once
+        let nested_in_line = profile
+            .running
+            .iter()
+            .filter(|outer| outer.call_depth == running.call_depth)
+            .any(|outer| same_line(&outer));
+        // And recursive calls would count its time again
+        let nested_in_call = profile.running.iter().any(|outer| same_line(&outer));
+
+        let stats = profile
+            .lines
+            .entry((location.file, location.line))
+            .or_default();
+        if !nested_in_line {
+            stats.count += 1;
+        }
+        if !nested_in_call {
+            stats.total += total;
+        }
+        stats.own += own;
+
+        let stack = (profile.functions.clone(), location.file, location.line);
+        *profile.folded.entry(stack).or_default() += own;
+
+        match profile.running.last_mut() {
+            Some(parent) => parent.nested += total,
+            None => profile.top_level.push((location, total)),
+        }
+    }
+
+    fn enter_function(&mut self, _interpreter: &Interpreter, function: &LoxFunction) {
+        let name = function.name().unwrap_or("<lambda>").to_string();
+        self.profile.borrow_mut().functions.push(name);
+    }
+
+    fn exit_function(&mut self, _interpreter: &Interpreter, _function: &LoxFunction) {
+        self.profile.borrow_mut().functions.pop();
+    }
+}
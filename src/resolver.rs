@@ -0,0 +1,544 @@
+use crate::error_code::ErrorCode;
+use crate::parser::{
+    ast::{
+        BinaryData, BinaryOp, CallData, Expr, FunctionData, ImportData, LiteralData, LogicalOp,
+        Stmt, TestData, TryData, UnaryData,
+    },
+    visitors::{ExprVisitor, StmtVisitor},
+};
+use crate::rutox_error::{RutoxError, Severity};
+use crate::scanner::{token::Token, SrcLocation};
+use std::rc::Rc;
+
+/// Walks the program before it runs, tracking which names each scope
+/// declares, and collects warnings about code that is probably a mistake.
+pub struct Resolver {
+    /// The innermost scope is last. The first one holds the globals.
+    scopes: Vec<Vec<Local>>,
+    warnings: Vec<RutoxError>,
+    symbols: Vec<Symbol>,
+    references: Vec<Reference>,
+    /// Names read before any declaration was seen, e.g. a function using a
+    /// global declared after it. Matched against the globals at the end.
+    unresolved: Vec<(String, SrcLocation)>,
+    /// The names of the functions being resolved, innermost last.
+    functions: Vec<String>,
+}
+
+struct Local {
+    name: String,
+    location: SrcLocation,
+    kind: LocalKind,
+    used: bool,
+    symbol: usize,
+}
+
+/// A declared name, for editor features like go-to-definition.
+pub struct Symbol {
+    pub name: String,
+    pub kind: LocalKind,
+    /// Where the name itself is written in the declaration.
+    pub location: SrcLocation,
+    /// The function the symbol is declared in, if any.
+    pub container: Option<String>,
+}
+
+/// A place where a symbol is read or assigned.
+pub struct Reference {
+    pub location: SrcLocation,
+    pub symbol: usize,
+}
+
+/// Every symbol of a program and where each one is used.
+pub struct SymbolIndex {
+    pub symbols: Vec<Symbol>,
+    pub references: Vec<Reference>,
+}
+
+impl SymbolIndex {
+    /// The symbol declared or used at `line` and `col`.
+    pub fn symbol_at(&self, line: usize, col: usize) -> Option<usize> {
+        let contains = |location: &SrcLocation| {
+            location.line == line && (location.col..=location.end_col).contains(&col)
+        };
+
+        self.references
+            .iter()
+            .find(|reference| contains(&reference.location))
+            .map(|reference| reference.symbol)
+            .or_else(|| {
+                self.symbols
+                    .iter()
+                    .position(|symbol| contains(&symbol.location))
+            })
+    }
+
+    /// Where `symbol` is used, not counting its declaration.
+    pub fn references_to(&self, symbol: usize) -> impl Iterator<Item = &SrcLocation> {
+        self.references
+            .iter()
+            .filter(move |reference| reference.symbol == symbol)
+            .map(|reference| &reference.location)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LocalKind {
+    Variable,
+    Function,
+    /// Function parameters and `catch` bindings, which often go unused on
+    /// purpose.
+    Binding,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self {
+            scopes: vec![vec![]],
+            warnings: vec![],
+            symbols: vec![],
+            references: vec![],
+            unresolved: vec![],
+            functions: vec![],
+        }
+    }
+
+    pub fn resolve(mut self, stmts: &[Stmt]) -> Vec<RutoxError> {
+        self.resolve_stmts(stmts);
+        self.warnings.sort_by_key(|warning| {
+            let location = warning.location();
+
+            (location.line, location.col)
+        });
+
+        self.warnings
+    }
+
+    /// Resolves `stmts` for the declarations and references in them rather
+    /// than for warnings.
+    pub fn index(mut self, stmts: &[Stmt]) -> SymbolIndex {
+        self.resolve_stmts(stmts);
+
+        let globals = &self.scopes[0];
+        for (name, location) in std::mem::take(&mut self.unresolved) {
+            if let Some(global) = globals.iter().find(|global| global.name == name) {
+                self.references.push(Reference {
+                    location,
+                    symbol: global.symbol,
+                });
+            }
+        }
+
+        self.references
+            .sort_by_key(|reference| (reference.location.line, reference.location.col));
+
+        SymbolIndex {
+            symbols: self.symbols,
+            references: self.references,
+        }
+    }
+
+    fn resolve_stmts(&mut self, stmts: &[Stmt]) {
+        let mut exit: Option<SrcLocation> = None;
+
+        for stmt in stmts {
+            if let Some(exit_location) = exit.take() {
+                self.warn(
+                    RutoxError::Diagnostic(
+                        Severity::Warning,
+                        ErrorCode::UnreachableCode,
+                        "Unreachable code".to_string(),
+                        stmt.location(),
+                    )
+                    .with_label(
+                        "any code after this is unreachable".to_string(),
+                        exit_location,
+                    ),
+                );
+            }
+            if let Stmt::Break(location)
+            | Stmt::Continue(location)
+            | Stmt::Return(_, location)
+            | Stmt::Throw(_, location) = stmt
+            {
+                exit = Some(location.clone());
+            }
+
+            // Nothing is fatal here, so the result is always `Ok`
+            let _ = self.visit_stmt(stmt);
+        }
+    }
+
+    fn resolve_function(&mut self, function: &FunctionData) {
+        self.begin_scope();
+        for param in &function.params {
+            self.declare(&param.lexeme, &param.location, LocalKind::Binding);
+        }
+        let name = function
+            .name
+            .as_ref()
+            .map_or("<lambda>".to_string(), |name| name.lexeme.clone());
+        self.functions.push(name);
+        self.resolve_stmts(&function.body);
+        self.functions.pop();
+        self.end_scope();
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(vec![]);
+    }
+
+    fn end_scope(&mut self) {
+        let scope = self.scopes.pop().expect("There should be a scope to end");
+
+        for local in scope {
+            if local.used || local.kind == LocalKind::Binding || local.name.starts_with('_') {
+                continue;
+            }
+
+            let kind = match local.kind {
+                LocalKind::Function => "function",
+                _ => "variable",
+            };
+            self.warn(
+                RutoxError::Diagnostic(
+                    Severity::Warning,
+                    ErrorCode::UnusedVariable,
+                    format!("Unused {kind} `{}`", local.name),
+                    local.location,
+                )
+                .with_help(format!(
+                    "if this is intentional, prefix it with an underscore: `_{}`",
+                    local.name
+                )),
+            );
+        }
+    }
+
+    /// Adds `name` to the innermost scope. Globals are never reported as
+    /// unused, since code that runs later (in the REPL or an importer) may
+    /// use them.
+    fn declare(&mut self, name: &str, location: &SrcLocation, kind: LocalKind) {
+        let is_local = self.scopes.len() > 1;
+        let shadowed = self.scopes[..self.scopes.len() - 1]
+            .iter()
+            .skip(1)
+            .rev()
+            .find_map(|scope| scope.iter().find(|local| local.name == name));
+
+        if let (true, Some(shadowed)) = (is_local, shadowed) {
+            let warning = RutoxError::Diagnostic(
+                Severity::Warning,
+                ErrorCode::ShadowedVariable,
+                format!("`{name}` shadows a variable from an outer scope"),
+                location.clone(),
+            )
+            .with_label(
+                "the outer variable is declared here".to_string(),
+                shadowed.location.clone(),
+            );
+            self.warn(warning);
+        }
+
+        self.symbols.push(Symbol {
+            name: name.to_string(),
+            kind,
+            location: location.clone(),
+            container: self.functions.last().cloned(),
+        });
+        let scope = self.scopes.last_mut().expect("There should be a scope");
+        scope.push(Local {
+            name: name.to_string(),
+            location: location.clone(),
+            kind,
+            used: false,
+            symbol: self.symbols.len() - 1,
+        });
+    }
+
+    fn mark_used(&mut self, name: &Token) {
+        if let Some(local) = self.reference(name) {
+            local.used = true;
+        }
+    }
+
+    /// Records that `name` refers to the closest declaration in scope.
+    fn reference(&mut self, name: &Token) -> Option<&mut Local> {
+        let local = self.scopes.iter_mut().rev().find_map(|scope| {
+            scope
+                .iter_mut()
+                .rev()
+                .find(|local| local.name == name.lexeme)
+        });
+
+        match local {
+            Some(local) => {
+                self.references.push(Reference {
+                    location: name.location.clone(),
+                    symbol: local.symbol,
+                });
+
+                Some(local)
+            }
+            None => {
+                self.unresolved
+                    .push((name.lexeme.clone(), name.location.clone()));
+
+                None
+            }
+        }
+    }
+
+    fn warn(&mut self, warning: RutoxError) {
+        self.warnings.push(warning);
+    }
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StmtVisitor<()> for Resolver {
+    fn visit_print_stmt(&mut self, expr: &Expr, _location: &SrcLocation) -> Result<(), RutoxError> {
+        self.visit_expr(expr)
+    }
+
+    fn visit_expr_stmt(&mut self, expr: &Expr, _location: &SrcLocation) -> Result<(), RutoxError> {
+        self.visit_expr(expr)
+    }
+
+    fn visit_var_stmt(
+        &mut self,
+        name: &Token,
+        initializer: &Option<Expr>,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        if let Some(initializer) = initializer {
+            self.visit_expr(initializer)?;
+        }
+        self.declare(&name.lexeme, &name.location, LocalKind::Variable);
+
+        Ok(())
+    }
+
+    fn visit_block_stmt(
+        &mut self,
+        stmts: &[Stmt],
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        self.begin_scope();
+        self.resolve_stmts(stmts);
+        self.end_scope();
+
+        Ok(())
+    }
+
+    fn visit_if_stmt(
+        &mut self,
+        cond: &Expr,
+        then_branch: &Stmt,
+        else_branch: &Option<Box<Stmt>>,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        let mut literal = cond;
+        while let Expr::Grouping(inner, _) = literal {
+            literal = inner;
+        }
+        if let Expr::Literal(literal) = literal {
+            let always = match literal {
+                LiteralData::Nil(_) | LiteralData::Bool(false, _) => "false",
+                _ => "true",
+            };
+            self.warn(RutoxError::Diagnostic(
+                Severity::Warning,
+                ErrorCode::ConstantCondition,
+                format!("This condition is always {always}"),
+                cond.span(),
+            ));
+        }
+
+        self.visit_expr(cond)?;
+        self.visit_stmt(then_branch)?;
+        if let Some(else_branch) = else_branch {
+            self.visit_stmt(else_branch)?;
+        }
+
+        Ok(())
+    }
+
+    fn visit_while_stmt(
+        &mut self,
+        cond: &Expr,
+        body: &Stmt,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        self.visit_expr(cond)?;
+        self.visit_stmt(body)
+    }
+
+    fn visit_break_stmt(&mut self, _location: &SrcLocation) -> Result<(), RutoxError> {
+        Ok(())
+    }
+
+    fn visit_continue_stmt(&mut self, _location: &SrcLocation) -> Result<(), RutoxError> {
+        Ok(())
+    }
+
+    fn visit_function_stmt(&mut self, function: &Rc<FunctionData>) -> Result<(), RutoxError> {
+        let name = function
+            .name
+            .as_ref()
+            .expect("A function statement should always have a name");
+
+        // Declared first, so the function can call itself
+        self.declare(&name.lexeme, &name.location, LocalKind::Function);
+        self.resolve_function(function);
+
+        Ok(())
+    }
+
+    fn visit_return_stmt(
+        &mut self,
+        value: &Option<Expr>,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        match value {
+            Some(value) => self.visit_expr(value),
+            None => Ok(()),
+        }
+    }
+
+    fn visit_throw_stmt(
+        &mut self,
+        value: &Expr,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        self.visit_expr(value)
+    }
+
+    fn visit_try_stmt(&mut self, try_data: &TryData) -> Result<(), RutoxError> {
+        self.visit_block_stmt(&try_data.body, &try_data.location)?;
+        if let Some(catch) = &try_data.catch {
+            self.begin_scope();
+            self.declare(&catch.name.lexeme, &catch.name.location, LocalKind::Binding);
+            self.resolve_stmts(&catch.body);
+            self.end_scope();
+        }
+        if let Some(finally) = &try_data.finally {
+            self.visit_block_stmt(finally, &try_data.location)?;
+        }
+
+        Ok(())
+    }
+
+    fn visit_import_stmt(&mut self, import: &ImportData) -> Result<(), RutoxError> {
+        self.declare(&import.name, &import.location, LocalKind::Variable);
+
+        Ok(())
+    }
+
+    fn visit_test_stmt(&mut self, test: &TestData) -> Result<(), RutoxError> {
+        self.visit_block_stmt(&test.body, &test.location)
+    }
+}
+
+impl ExprVisitor<()> for Resolver {
+    fn visit_literal_expr(&mut self, _literal: &LiteralData) -> Result<(), RutoxError> {
+        Ok(())
+    }
+
+    fn visit_unary_expr(&mut self, unary: &UnaryData) -> Result<(), RutoxError> {
+        self.visit_expr(&unary.expr)
+    }
+
+    fn visit_binary_expr(&mut self, binary: &BinaryData) -> Result<(), RutoxError> {
+        let is_nil = |expr: &Expr| matches!(expr, Expr::Literal(LiteralData::Nil(_)));
+
+        if let BinaryOp::EqualEqual(_) | BinaryOp::BangEqual(_) = binary.operator {
+            if is_nil(&binary.left) || is_nil(&binary.right) {
+                let test = match binary.operator {
+                    BinaryOp::EqualEqual(_) => "`!value`",
+                    _ => "`value`",
+                };
+                self.warn(
+                    RutoxError::Diagnostic(
+                        Severity::Warning,
+                        ErrorCode::NilComparison,
+                        format!("Comparison with `nil` using `{}`", binary.operator),
+                        binary.span(),
+                    )
+                    .with_help(format!(
+                        "if the value can't be `false`, test its truthiness instead: {test}"
+                    )),
+                );
+            }
+        }
+
+        self.visit_expr(&binary.left)?;
+        self.visit_expr(&binary.right)
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &Expr) -> Result<(), RutoxError> {
+        self.visit_expr(expr)
+    }
+
+    fn visit_variable_expr(
+        &mut self,
+        name: &Token,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        self.mark_used(name);
+
+        Ok(())
+    }
+
+    fn visit_assign_expr(
+        &mut self,
+        name: &Token,
+        value: &Expr,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        self.visit_expr(value)?;
+        // Assigning to a variable is not using it
+        self.reference(name);
+
+        Ok(())
+    }
+
+    fn visit_logical_expr(
+        &mut self,
+        left: &Expr,
+        _op: &LogicalOp,
+        right: &Expr,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        self.visit_expr(left)?;
+        self.visit_expr(right)
+    }
+
+    fn visit_call_expr(&mut self, call: &CallData) -> Result<(), RutoxError> {
+        self.visit_expr(&call.callee)?;
+        for arg in &call.args {
+            self.visit_expr(arg)?;
+        }
+
+        Ok(())
+    }
+
+    fn visit_lambda_expr(&mut self, function: &Rc<FunctionData>) -> Result<(), RutoxError> {
+        self.resolve_function(function);
+
+        Ok(())
+    }
+
+    fn visit_get_expr(
+        &mut self,
+        object: &Expr,
+        _name: &Token,
+        _location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        self.visit_expr(object)
+    }
+}
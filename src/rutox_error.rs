@@ -1,30 +1,84 @@
-use super::scanner::src_location::SrcLocation;
+use super::error_code::ErrorCode;
+use super::interpreter::LoxObj;
+use super::scanner::{src_location::SrcLocation, SourceMap};
 use colored::*;
 
 pub enum RutoxError {
     Programmer(String, SrcLocation),
-    Syntax(String, SrcLocation),
-    Runtime(String, SrcLocation),
+    Syntax(ErrorCode, String, SrcLocation),
+    Runtime(ErrorCode, String, SrcLocation),
+    /// A problem found by looking at the code before running it. Warnings do
+    /// not stop the program unless promoted with `-W error`.
+    Diagnostic(Severity, ErrorCode, String, SrcLocation),
+    /// A value thrown with `throw` that was not caught.
+    Thrown(LoxObj, SrcLocation),
+    /// An error that escaped one or more function calls. Frames are ordered
+    /// from the innermost call outwards.
+    Traced(Box<RutoxError>, Vec<StackFrame>),
+    /// An error with notes pointing at other relevant code.
+    Labeled(Box<RutoxError>, Vec<Label>),
+    /// An error with hints on how to fix it, e.g. "did you mean `count`?".
+    Helped(Box<RutoxError>, Vec<String>),
     Multiple(Vec<RutoxError>),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct StackFrame {
+    pub function_name: String,
+    pub call_location: SrcLocation,
+}
+
+/// A secondary note shown under the code it points at, e.g. "left operand is
+/// a string here".
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub message: String,
+    pub location: SrcLocation,
+}
+
+const MAX_TRACEBACK_FRAMES: usize = 10;
+
 impl std::fmt::Display for RutoxError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            RutoxError::Syntax(msg, _) => {
-                let error_type = "SyntaxError".red();
+            RutoxError::Syntax(code, msg, _) => {
+                let error_type = format!("SyntaxError[{code}]").red();
                 let msg = format!("{error_type}: {msg}.").bold();
 
                 write!(f, "{}", msg)
             }
-            RutoxError::Runtime(msg, _) => {
-                let error_type = "RuntimeError".red();
+            RutoxError::Runtime(code, msg, _) => {
+                let error_type = format!("RuntimeError[{code}]").red();
                 let msg = format!("{error_type}: {msg}.").bold();
 
                 write!(f, "{}", msg)
             }
+            RutoxError::Diagnostic(Severity::Warning, code, msg, _) => {
+                let error_type = format!("Warning[{code}]").yellow();
+                let msg = format!("{error_type}: {msg}.").bold();
+
+                write!(f, "{}", msg)
+            }
+            RutoxError::Diagnostic(Severity::Error, code, msg, _) => {
+                let error_type = format!("Error[{code}]").red();
+                let msg = format!("{error_type}: {msg}.").bold();
+
+                write!(f, "{}", msg)
+            }
+            RutoxError::Thrown(value, _) => {
+                let error_type = format!("RuntimeError[{}]", ErrorCode::UncaughtException).red();
+                let msg = format!("{error_type}: Uncaught {value:?}.").bold();
+
+                write!(f, "{}", msg)
+            }
             RutoxError::Programmer(msg, _) => {
-                let error_type = "ProgrammerError".red();
+                let error_type = format!("ProgrammerError[{}]", ErrorCode::Internal).red();
                 let msg = format!(
                     "{error_type}: {msg}.\nThis is a bug in rutox. Please report it at https://github.com/MatheusRich/rutox."
                 )
@@ -32,6 +86,9 @@ impl std::fmt::Display for RutoxError {
 
                 write!(f, "{}", msg)
             }
+            RutoxError::Traced(error, _) => write!(f, "{error}"),
+            RutoxError::Labeled(error, _) => write!(f, "{error}"),
+            RutoxError::Helped(error, _) => write!(f, "{error}"),
             RutoxError::Multiple(errors) => {
                 let mut error_string = String::new();
 
@@ -48,9 +105,14 @@ impl std::fmt::Display for RutoxError {
 impl RutoxError {
     pub fn location(&self) -> SrcLocation {
         match self {
-            RutoxError::Syntax(_, location) => location.clone(),
+            RutoxError::Syntax(_, _, location) => location.clone(),
             RutoxError::Programmer(_, location) => location.clone(),
-            RutoxError::Runtime(_, location) => location.clone(),
+            RutoxError::Runtime(_, _, location) => location.clone(),
+            RutoxError::Diagnostic(_, _, _, location) => location.clone(),
+            RutoxError::Thrown(_, location) => location.clone(),
+            RutoxError::Traced(error, _) => error.location(),
+            RutoxError::Labeled(error, _) => error.location(),
+            RutoxError::Helped(error, _) => error.location(),
             RutoxError::Multiple(errors) => errors
                 .first()
                 .expect("There should be at least one error")
@@ -59,14 +121,230 @@ impl RutoxError {
         }
     }
 
-    pub fn details(&self, source_file_path: &str, source_file_content: &str) -> String {
+    /// Records that this error escaped a call to a function, so it can be
+    /// reported with a traceback. Only runtime errors are traced.
+    pub fn traced(self, frame: StackFrame) -> RutoxError {
+        match self {
+            RutoxError::Traced(error, mut frames) => {
+                frames.push(frame);
+
+                RutoxError::Traced(error, frames)
+            }
+            RutoxError::Runtime(_, _, _)
+            | RutoxError::Thrown(_, _)
+            | RutoxError::Labeled(_, _)
+            | RutoxError::Helped(_, _) => RutoxError::Traced(Box::new(self), vec![frame]),
+            other => other,
+        }
+    }
+
+    pub fn with_label(self, message: String, location: SrcLocation) -> RutoxError {
+        let label = Label { message, location };
+
+        match self {
+            RutoxError::Labeled(error, mut labels) => {
+                labels.push(label);
+
+                RutoxError::Labeled(error, labels)
+            }
+            other => RutoxError::Labeled(Box::new(other), vec![label]),
+        }
+    }
+
+    pub fn with_help(self, help: String) -> RutoxError {
+        match self {
+            RutoxError::Helped(error, mut helps) => {
+                helps.push(help);
+
+                RutoxError::Helped(error, helps)
+            }
+            other => RutoxError::Helped(Box::new(other), vec![help]),
+        }
+    }
+
+    /// Turns warnings into errors, for `-W error`.
+    pub fn promoted(self) -> RutoxError {
+        match self {
+            RutoxError::Diagnostic(_, code, msg, location) => {
+                RutoxError::Diagnostic(Severity::Error, code, msg, location)
+            }
+            RutoxError::Labeled(error, labels) => {
+                RutoxError::Labeled(Box::new(error.promoted()), labels)
+            }
+            RutoxError::Helped(error, helps) => {
+                RutoxError::Helped(Box::new(error.promoted()), helps)
+            }
+            RutoxError::Multiple(errors) => {
+                RutoxError::Multiple(errors.into_iter().map(|e| e.promoted()).collect())
+            }
+            other => other,
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self.untraced() {
+            RutoxError::Diagnostic(severity, _, _, _) => *severity,
+            RutoxError::Multiple(errors) => {
+                if errors
+                    .iter()
+                    .all(|error| error.severity() == Severity::Warning)
+                {
+                    Severity::Warning
+                } else {
+                    Severity::Error
+                }
+            }
+            _ => Severity::Error,
+        }
+    }
+
+    /// The error itself, without the calls it went through, its labels or
+    /// its help notes.
+    pub fn untraced(&self) -> &RutoxError {
+        match self {
+            RutoxError::Traced(error, _) => error.untraced(),
+            RutoxError::Labeled(error, _) => error.untraced(),
+            RutoxError::Helped(error, _) => error.untraced(),
+            other => other,
+        }
+    }
+
+    /// The kind of error, as shown before its message.
+    pub fn kind(&self) -> &'static str {
+        match self.untraced() {
+            RutoxError::Syntax(_, _, _) => "SyntaxError",
+            RutoxError::Diagnostic(Severity::Warning, _, _, _) => "Warning",
+            RutoxError::Diagnostic(Severity::Error, _, _, _) => "Error",
+            RutoxError::Runtime(_, _, _) | RutoxError::Thrown(_, _) => "RuntimeError",
+            RutoxError::Programmer(_, _) => "ProgrammerError",
+            RutoxError::Multiple(errors) => errors
+                .first()
+                .expect("There should be at least one error")
+                .kind(),
+            RutoxError::Traced(_, _) | RutoxError::Labeled(_, _) | RutoxError::Helped(_, _) => {
+                unreachable!()
+            }
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self.untraced() {
+            RutoxError::Syntax(code, _, _)
+            | RutoxError::Runtime(code, _, _)
+            | RutoxError::Diagnostic(_, code, _, _) => *code,
+            RutoxError::Thrown(_, _) => ErrorCode::UncaughtException,
+            RutoxError::Programmer(_, _) => ErrorCode::Internal,
+            RutoxError::Multiple(errors) => errors
+                .first()
+                .expect("There should be at least one error")
+                .code(),
+            RutoxError::Traced(_, _) | RutoxError::Labeled(_, _) | RutoxError::Helped(_, _) => {
+                unreachable!()
+            }
+        }
+    }
+
+    /// The error message, without any styling.
+    pub fn message(&self) -> String {
+        match self.untraced() {
+            RutoxError::Syntax(_, msg, _)
+            | RutoxError::Runtime(_, msg, _)
+            | RutoxError::Diagnostic(_, _, msg, _)
+            | RutoxError::Programmer(msg, _) => msg.clone(),
+            RutoxError::Thrown(value, _) => format!("Uncaught {value:?}"),
+            RutoxError::Multiple(errors) => errors
+                .iter()
+                .map(|error| error.message())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            RutoxError::Traced(_, _) | RutoxError::Labeled(_, _) | RutoxError::Helped(_, _) => {
+                unreachable!()
+            }
+        }
+    }
+
+    /// Each individual error, with `Multiple` flattened.
+    pub fn errors(&self) -> Vec<&RutoxError> {
+        match self {
+            RutoxError::Multiple(errors) => errors.iter().flat_map(|e| e.errors()).collect(),
+            other => vec![other],
+        }
+    }
+
+    /// The calls this error went through, innermost first.
+    pub fn frames(&self) -> &[StackFrame] {
+        match self {
+            RutoxError::Traced(_, frames) => frames,
+            RutoxError::Labeled(error, _) => error.frames(),
+            RutoxError::Helped(error, _) => error.frames(),
+            _ => &[],
+        }
+    }
+
+    pub fn labels(&self) -> &[Label] {
+        match self {
+            RutoxError::Traced(error, _) => error.labels(),
+            RutoxError::Labeled(_, labels) => labels,
+            RutoxError::Helped(error, _) => error.labels(),
+            _ => &[],
+        }
+    }
+
+    pub fn helps(&self) -> &[String] {
+        match self {
+            RutoxError::Traced(error, _) => error.helps(),
+            RutoxError::Labeled(error, _) => error.helps(),
+            RutoxError::Helped(_, helps) => helps,
+            RutoxError::Multiple(errors) => errors
+                .first()
+                .expect("There should be at least one error")
+                .helps(),
+            _ => &[],
+        }
+    }
+
+    pub fn details(&self, source_map: &SourceMap) -> String {
+        let source_file = source_map.get(self.location().file);
+
         format!(
-            "{}\n{}",
-            self.source_location(source_file_path),
-            self.code_excerpt(source_file_content)
+            "{}{}\n{}{}",
+            self.traceback(source_map),
+            self.source_location(&source_file.path),
+            self.code_excerpt(&source_file.content),
+            self.help_notes()
         )
     }
 
+    fn help_notes(&self) -> String {
+        self.helps()
+            .iter()
+            .map(|help| format!("\n{} {}: {}", "  =".blue().bold(), "help".bold(), help))
+            .collect()
+    }
+
+    fn traceback(&self, source_map: &SourceMap) -> String {
+        let frames = self.frames();
+        if frames.is_empty() {
+            return String::new();
+        }
+
+        let mut traceback = format!("{}\n", "Traceback (most recent call last):".bold());
+        if frames.len() > MAX_TRACEBACK_FRAMES {
+            let omitted = frames.len() - MAX_TRACEBACK_FRAMES;
+            traceback.push_str(&format!("  ... {omitted} earlier calls omitted\n"));
+        }
+        for frame in frames.iter().take(MAX_TRACEBACK_FRAMES).rev() {
+            traceback.push_str(&format!(
+                "  {}:{} in call to {}\n",
+                source_map.get(frame.call_location.file).path,
+                frame.call_location,
+                frame.function_name.bold()
+            ));
+        }
+
+        traceback
+    }
+
     fn source_location(&self, source_file_path: &str) -> String {
         let arrow = "  -->".blue().bold();
 
@@ -83,9 +361,21 @@ impl RutoxError {
             .0
             .to_string()
             .len();
+        let location = self.location();
+        let labels: Vec<&Label> = self
+            .labels()
+            .iter()
+            .filter(|label| label.location.file == location.file)
+            .collect();
 
         let mut code_excerpt = String::new();
+        let mut previous_line = None;
         for (line, code, should_highlight) in error_line_and_surroundings {
+            if previous_line.is_some_and(|previous| line > previous + 1) {
+                code_excerpt.push_str(&format!("{}\n", "...".blue().bold()));
+            }
+            previous_line = Some(line);
+
             code_excerpt.push_str(
                 &self
                     .code_column(line, biggest_line_num_digit_count)
@@ -96,11 +386,19 @@ impl RutoxError {
             } else {
                 code_excerpt.push_str(&code);
             }
-            if line == self.location().line as i64 {
+            if line == location.line as i64 {
                 code_excerpt.push('\n');
                 code_excerpt.push_str(
                     &self
-                        .error_indication(biggest_line_num_digit_count)
+                        .error_indication(biggest_line_num_digit_count, &code)
+                        .to_string(),
+                );
+            }
+            for label in labels.iter().filter(|l| l.location.line as i64 == line) {
+                code_excerpt.push('\n');
+                code_excerpt.push_str(
+                    &self
+                        .label_indication(label, biggest_line_num_digit_count, &code)
                         .to_string(),
                 );
             }
@@ -126,47 +424,74 @@ impl RutoxError {
         format!(" {} | ", padding).blue().bold()
     }
 
-    fn error_indication(&self, biggest_line_num: usize) -> ColoredString {
-        let error_col = if self.location().col == 0 {
-            0
-        } else {
-            self.location().col - 1
+    fn error_indication(&self, biggest_line_num: usize, code: &str) -> ColoredString {
+        let message = match self.severity() {
+            Severity::Error => "the error occurred here",
+            Severity::Warning => "",
         };
 
         format!(
-            "{}{}{}",
+            "{}{}",
             self.empty_column(biggest_line_num),
-            " ".repeat(error_col),
-            "^ the error occurred here"
+            Self::underline(&self.location(), code, '^', message).trim_end()
         )
         .yellow()
         .bold()
     }
 
+    fn label_indication(
+        &self,
+        label: &Label,
+        biggest_line_num: usize,
+        code: &str,
+    ) -> ColoredString {
+        format!(
+            "{}{}",
+            self.empty_column(biggest_line_num),
+            Self::underline(&label.location, code, '-', &label.message)
+        )
+        .blue()
+        .bold()
+    }
+
+    /// Marks `location` on its first line, `code`. Spans that go on to other
+    /// lines are marked up to the end of the first one.
+    fn underline(location: &SrcLocation, code: &str, marker: char, message: &str) -> String {
+        let start = location.col.saturating_sub(1);
+        let width = if location.end_line == location.line && location.end_col >= location.col {
+            location.end_col - location.col + 1
+        } else {
+            code.chars().count().saturating_sub(start).max(1)
+        };
+
+        format!(
+            "{}{} {}",
+            " ".repeat(start),
+            marker.to_string().repeat(width),
+            message
+        )
+    }
+
+    /// The error line, the lines around it and any lines with labels.
     fn extract_error_line_and_surroundings(
         &self,
         source_file_content: &str,
     ) -> Vec<(i64, String, bool)> {
-        let error_line_index = self.location().line as i64 - 1;
-        let line_before_error_index = error_line_index - 1;
-        let line_after_error_index = error_line_index + 1;
+        let location = self.location();
+        let error_line = location.line as i64;
+        let label_lines: Vec<i64> = self
+            .labels()
+            .iter()
+            .filter(|label| label.location.file == location.file)
+            .map(|label| label.location.line as i64)
+            .collect();
 
         source_file_content
             .split('\n')
-            .map(|l| l.to_string())
             .enumerate()
-            .map(|(i, line)| match i {
-                v if v as i64 == line_before_error_index => {
-                    (Some(line_before_error_index + 1), line, false)
-                }
-                v if v as i64 == error_line_index => (Some(error_line_index + 1), line, true),
-                v if v as i64 == line_after_error_index => {
-                    (Some(line_after_error_index + 1), line, false)
-                }
-                _ => (None, "".to_string(), false),
-            })
-            .filter(|(i, _, _)| i.is_some())
-            .map(|(i, line, should_highlight)| (i.unwrap(), line, should_highlight))
+            .map(|(i, line)| (i as i64 + 1, line.to_string()))
+            .filter(|(line, _)| (line - error_line).abs() <= 1 || label_lines.contains(line))
+            .map(|(line, code)| (line, code, line == error_line))
             .collect()
     }
 }
@@ -1,45 +1,91 @@
+pub mod source_map;
 pub mod src_location;
 pub mod token;
+use super::error_code::ErrorCode;
 use super::rutox_error::RutoxError;
+pub use source_map::{FileId, SourceMap};
 pub use src_location::SrcLocation;
-use token::{Token, TokenKind};
+use token::{Comment, Token, TokenKind};
+
+/// Every reserved word and the token it scans to.
+pub const KEYWORDS: [(&str, TokenKind); 23] = [
+    ("and", TokenKind::And),
+    ("break", TokenKind::Break),
+    ("catch", TokenKind::Catch),
+    ("class", TokenKind::Class),
+    ("continue", TokenKind::Continue),
+    ("else", TokenKind::Else),
+    ("false", TokenKind::False),
+    ("finally", TokenKind::Finally),
+    ("for", TokenKind::For),
+    ("fun", TokenKind::Fun),
+    ("if", TokenKind::If),
+    ("import", TokenKind::Import),
+    ("nil", TokenKind::Nil),
+    ("or", TokenKind::Or),
+    ("print", TokenKind::Print),
+    ("return", TokenKind::Return),
+    ("super", TokenKind::Super),
+    ("this", TokenKind::This),
+    ("throw", TokenKind::Throw),
+    ("true", TokenKind::True),
+    ("try", TokenKind::Try),
+    ("var", TokenKind::Var),
+    ("while", TokenKind::While),
+];
 
 pub struct Scanner {
-    source: String,
+    /// Indexed by character rather than byte, so positions in it are columns.
+    source: Vec<char>,
     tokens: Vec<Token>,
+    comments: Vec<Comment>,
     start: usize,
     current: usize,
     current_line: usize,
     current_column: usize,
+    start_line: usize,
+    start_column: usize,
+    file: FileId,
 }
 
 impl Scanner {
-    pub fn new(source: String) -> Scanner {
+    pub fn new(source: String, file: FileId) -> Scanner {
         Scanner {
-            source,
+            source: source.chars().collect(),
             tokens: Vec::new(),
+            comments: Vec::new(),
             start: 0,
             current: 0,
             current_line: 1,
             current_column: 0,
+            start_line: 1,
+            start_column: 1,
+            file,
         }
     }
 
     pub fn scan_tokens(&mut self) -> Result<Vec<Token>, RutoxError> {
         while !self.is_at_end() {
             self.start = self.current;
+            self.start_line = self.current_line;
+            self.start_column = self.current_column + 1;
             self.scan_token()?
         }
 
         self.tokens.push(Token {
             kind: TokenKind::Eof,
             lexeme: "".to_string(),
-            location: SrcLocation::new(self.current_line, self.current_column),
+            location: SrcLocation::new(self.current_line, self.current_column + 1, self.file),
         });
 
         Ok(self.tokens.clone())
     }
 
+    /// The comments found by `scan_tokens`, in order.
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+
     fn scan_token(&mut self) -> Result<(), RutoxError> {
         match self.advance() {
             '(' => self.add_token(TokenKind::LParen),
@@ -74,7 +120,7 @@ impl Scanner {
             }
             '/' => {
                 if self.matches('/') {
-                    self.skip_comment();
+                    self.consume_comment();
                 } else {
                     self.add_token(TokenKind::Slash);
                 }
@@ -85,12 +131,13 @@ impl Scanner {
                 self.current_line += 1;
                 self.current_column = 0;
             }
-            c if c.is_digit(10) => self.consume_number()?,
+            c if c.is_ascii_digit() => self.consume_number()?,
             c if c.is_ascii_alphabetic() || c == '_' => self.consume_identifier(),
             c => {
                 return Err(RutoxError::Syntax(
+                    ErrorCode::UnexpectedCharacter,
                     format!("Unexpected character: `{c}`"),
-                    self.current_location(),
+                    self.token_location(),
                 ))
             }
         }
@@ -99,16 +146,16 @@ impl Scanner {
     }
 
     fn consume_number(&mut self) -> Result<(), RutoxError> {
-        self.consume_while(|c| c.is_digit(10));
+        self.consume_while(|c| c.is_ascii_digit());
 
         if let (Some('.'), Some(next)) = (self.peek(), self.peek_next()) {
-            if next.is_digit(10) {
+            if next.is_ascii_digit() {
                 self.advance();
-                self.consume_while(|c| c.is_digit(10));
+                self.consume_while(|c| c.is_ascii_digit());
             }
         }
 
-        let n_string = &self.source[self.start..self.current];
+        let n_string = self.current_token_string();
         let n = n_string.parse::<f64>().map_err(|_| {
             RutoxError::Programmer(
                 format!("Could not parse number `{n_string}`"),
@@ -127,16 +174,17 @@ impl Scanner {
             if ch == '"' {
                 break;
             }
+            self.advance();
+
             if ch == '\n' {
                 self.current_line += 1;
                 self.current_column = 0;
             }
-
-            self.advance();
         }
 
         if self.is_at_end() {
             return Err(RutoxError::Syntax(
+                ErrorCode::UnterminatedString,
                 "Unterminated string".into(),
                 opening_quote_location,
             ));
@@ -145,7 +193,9 @@ impl Scanner {
         self.expect('"')?;
 
         // Trim the surrounding quotes
-        let value = self.source[self.start + 1..self.current - 1].to_string();
+        let value = self.source[self.start + 1..self.current - 1]
+            .iter()
+            .collect();
         self.add_token(TokenKind::String(value));
 
         Ok(())
@@ -161,18 +211,27 @@ impl Scanner {
         self.add_token(kind);
     }
 
-    fn skip_comment(&mut self) {
-        self.consume_while(|c| c != '\n');
+    fn consume_comment(&mut self) {
+        let text: String = self.consume_while(|c| c != '\n').into_iter().collect();
+        let trailing = self
+            .tokens
+            .last()
+            .is_some_and(|token| token.location.end_line == self.start_line);
+
+        self.comments.push(Comment {
+            text: format!("//{}", text.trim_end()),
+            location: self.token_location(),
+            trailing,
+        });
     }
 
+    /// Consumes the next character. Callers check there is one first.
     fn advance(&mut self) -> char {
+        let ch = self.source[self.current];
         self.current += 1;
         self.current_column += 1;
 
-        self.source
-            .chars()
-            .nth(self.current - 1)
-            .expect("Called advance, but scanner is at end of source")
+        ch
     }
 
     fn matches(&mut self, expected: char) -> bool {
@@ -222,23 +281,20 @@ impl Scanner {
     }
 
     fn peek(&self) -> Option<char> {
-        self.source.chars().nth(self.current)
+        self.source.get(self.current).copied()
     }
 
     fn peek_next(&self) -> Option<char> {
-        self.source.chars().nth(self.current + 1)
+        self.source.get(self.current + 1).copied()
     }
 
     fn expect(&mut self, expected: char) -> Result<char, RutoxError> {
-        let current = self.advance();
-
-        if current == expected {
-            Ok(current)
-        } else {
-            Err(RutoxError::Programmer(
-                format!("Expected `{expected}`, found `{current}`"),
+        match self.peek() {
+            Some(current) if current == expected => Ok(self.advance()),
+            current => Err(RutoxError::Programmer(
+                format!("Expected `{expected}`, found {current:?}"),
                 self.current_location(),
-            ))
+            )),
         }
     }
 
@@ -248,7 +304,7 @@ impl Scanner {
         self.tokens.push(Token {
             kind,
             lexeme: text,
-            location: self.current_location(),
+            location: self.token_location(),
         });
     }
 
@@ -257,32 +313,28 @@ impl Scanner {
     }
 
     fn current_token_string(&self) -> String {
-        self.source[self.start..self.current].to_string()
+        self.source[self.start..self.current].iter().collect()
     }
 
     fn current_location(&self) -> SrcLocation {
-        SrcLocation::new(self.current_line, self.current_column)
+        SrcLocation::new(self.current_line, self.current_column, self.file)
+    }
+
+    /// The span of the token being scanned.
+    fn token_location(&self) -> SrcLocation {
+        SrcLocation::span(
+            self.start_line,
+            self.start_column,
+            self.current_line,
+            self.current_column,
+            self.file,
+        )
     }
 
     fn keyword_to_token_kind(&self, kw: &str) -> Option<TokenKind> {
-        match kw {
-            "and" => Some(TokenKind::And),
-            "class" => Some(TokenKind::Class),
-            "else" => Some(TokenKind::Else),
-            "false" => Some(TokenKind::False),
-            "for" => Some(TokenKind::For),
-            "fun" => Some(TokenKind::Fun),
-            "if" => Some(TokenKind::If),
-            "nil" => Some(TokenKind::Nil),
-            "or" => Some(TokenKind::Or),
-            "print" => Some(TokenKind::Print),
-            "return" => Some(TokenKind::Return),
-            "super" => Some(TokenKind::Super),
-            "this" => Some(TokenKind::This),
-            "true" => Some(TokenKind::True),
-            "var" => Some(TokenKind::Var),
-            "while" => Some(TokenKind::While),
-            _ => None,
-        }
+        KEYWORDS
+            .iter()
+            .find(|(keyword, _)| *keyword == kw)
+            .map(|(_, kind)| kind.clone())
     }
 }
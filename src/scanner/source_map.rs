@@ -0,0 +1,37 @@
+/// Identifies a source file registered in a `SourceMap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct FileId(usize);
+
+pub struct SourceFile {
+    pub path: String,
+    pub content: String,
+}
+
+/// Every piece of source code rutox has seen (script files, imports, REPL
+/// entries, `-e` snippets), so errors can show the right path and excerpt
+/// from just a `SrcLocation`.
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, path: &str, content: &str) -> FileId {
+        self.files.push(SourceFile {
+            path: path.to_string(),
+            content: content.to_string(),
+        });
+
+        FileId(self.files.len() - 1)
+    }
+
+    pub fn get(&self, file: FileId) -> &SourceFile {
+        self.files
+            .get(file.0)
+            .expect("Every FileId should come from this SourceMap")
+    }
+}
@@ -1,14 +1,42 @@
+use super::source_map::FileId;
 use std::fmt;
 
-#[derive(Debug, Clone, PartialEq)]
+/// A span of source code, from `line:col` to `end_line:end_col` (both
+/// inclusive). Columns start at 1.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct SrcLocation {
     pub line: usize,
     pub col: usize,
+    pub end_line: usize,
+    pub end_col: usize,
+    pub file: FileId,
 }
 
 impl SrcLocation {
-    pub fn new(line: usize, col: usize) -> Self {
-        Self { line, col }
+    /// A location covering a single character.
+    pub fn new(line: usize, col: usize, file: FileId) -> Self {
+        Self::span(line, col, line, col, file)
+    }
+
+    pub fn span(line: usize, col: usize, end_line: usize, end_col: usize, file: FileId) -> Self {
+        Self {
+            line,
+            col,
+            end_line,
+            end_col,
+            file,
+        }
+    }
+
+    /// The span from the start of `self` to the end of `other`.
+    pub fn to(&self, other: &SrcLocation) -> SrcLocation {
+        Self::span(
+            self.line,
+            self.col,
+            other.end_line,
+            other.end_col,
+            self.file,
+        )
     }
 }
 
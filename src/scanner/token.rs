@@ -8,6 +8,17 @@ pub struct Token {
     pub location: SrcLocation,
 }
 
+/// A `// ...` comment. Comments are not tokens, but are kept so tools like
+/// the formatter can put them back.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    /// The whole comment, including the leading `//`.
+    pub text: String,
+    pub location: SrcLocation,
+    /// Whether the comment follows code on the same line.
+    pub trailing: bool,
+}
+
 impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.kind {
@@ -55,19 +66,26 @@ pub enum TokenKind {
 
     // Keywords.
     And,
+    Break,
+    Catch,
     Class,
+    Continue,
     Else,
     False,
+    Finally,
     Fun,
     For,
     If,
+    Import,
     Nil,
     Or,
     Print,
     Return,
     Super,
     This,
+    Throw,
     True,
+    Try,
     Var,
     While,
 
@@ -80,19 +98,26 @@ impl fmt::Display for TokenKind {
 
         match self {
             TokenKind::And
+            | TokenKind::Break
+            | TokenKind::Catch
             | TokenKind::Class
+            | TokenKind::Continue
             | TokenKind::Else
             | TokenKind::False
+            | TokenKind::Finally
             | TokenKind::For
             | TokenKind::Fun
             | TokenKind::If
+            | TokenKind::Import
             | TokenKind::Nil
             | TokenKind::Or
             | TokenKind::Print
             | TokenKind::Return
             | TokenKind::Super
             | TokenKind::This
+            | TokenKind::Throw
             | TokenKind::True
+            | TokenKind::Try
             | TokenKind::Var
             | TokenKind::While => {
                 let str = format!("{:?}", self).to_lowercase().purple();
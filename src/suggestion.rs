@@ -0,0 +1,79 @@
+use crate::scanner::KEYWORDS;
+
+/// Words people reach for from other languages, and the Lox keyword that
+/// does the same job.
+const KEYWORD_ALIASES: [(&str, &str); 9] = [
+    ("function", "fun"),
+    ("func", "fun"),
+    ("fn", "fun"),
+    ("def", "fun"),
+    ("let", "var"),
+    ("const", "var"),
+    ("null", "nil"),
+    ("elif", "else if"),
+    ("elsif", "else if"),
+];
+
+/// The candidate closest to `name`, if any is close enough to be a likely
+/// typo.
+pub fn did_you_mean<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// The keyword an identifier like `retrun` or `funtion` was meant to be.
+pub fn keyword_for(name: &str) -> Option<&'static str> {
+    let alias_keyword = |alias: &str| {
+        KEYWORD_ALIASES
+            .iter()
+            .find(|(other, _)| *other == alias)
+            .map(|(_, keyword)| *keyword)
+    };
+
+    alias_keyword(name)
+        .or_else(|| did_you_mean(name, KEYWORDS.iter().map(|(keyword, _)| *keyword)))
+        .or_else(|| {
+            did_you_mean(name, KEYWORD_ALIASES.iter().map(|(alias, _)| *alias))
+                .and_then(alias_keyword)
+        })
+}
+
+/// The number of insertions, deletions, substitutions and swaps of adjacent
+/// characters needed to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+
+            distances[i][j] = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distances[i][j] = distances[i][j].min(distances[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    distances[a.len()][b.len()]
+}
@@ -0,0 +1,173 @@
+use crate::error_code::ErrorCode;
+use crate::interpreter::{Interpreter, LoxObj, NativeFunction};
+use crate::parser::{
+    ast::{Stmt, TestData},
+    Parser,
+};
+use crate::resolver::Resolver;
+use crate::rutox_error::RutoxError;
+use crate::scanner::{Scanner, SourceMap, SrcLocation};
+use colored::*;
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// Files whose name ends with this are test files.
+pub const SUFFIX: &str = "_test.lox";
+
+/// What `rutox test` found.
+#[derive(Default)]
+pub struct Summary {
+    pub passed: usize,
+    /// The name of each test that failed, and why.
+    pub failures: Vec<(String, RutoxError)>,
+    pub warnings: Vec<RutoxError>,
+}
+
+/// The test files under `path`, sorted, or `path` itself if it is a file.
+/// Hidden directories are skipped.
+pub fn discover(path: &Path) -> io::Result<Vec<PathBuf>> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files = vec![];
+    let mut entries: Vec<PathBuf> = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    entries.sort();
+
+    for entry in entries {
+        let name = entry
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+
+        if entry.is_dir() && !name.starts_with('.') {
+            files.extend(discover(&entry)?);
+        } else if entry.is_file() && name.ends_with(SUFFIX) {
+            files.push(entry);
+        }
+    }
+
+    Ok(files)
+}
+
+/// Runs the `test` blocks of the file at `path`, or the whole file if it
+/// has none, printing whether each passed as it goes.
+pub fn run_file(
+    path: &str,
+    code: &str,
+    source_map: &Rc<RefCell<SourceMap>>,
+    warnings_as_errors: bool,
+    summary: &mut Summary,
+) {
+    let file = source_map.borrow_mut().add(path, code);
+    let stmts = Scanner::new(code.to_string(), file)
+        .scan_tokens()
+        .and_then(|tokens| Parser::new(tokens).parse())
+        .and_then(|stmts| {
+            let warnings = Resolver::new().resolve(&stmts);
+            if warnings_as_errors && !warnings.is_empty() {
+                return Err(RutoxError::Multiple(warnings).promoted());
+            }
+            summary.warnings.extend(warnings);
+
+            Ok(stmts)
+        });
+    let stmts = match stmts {
+        Ok(stmts) => stmts,
+        Err(error) => return record(summary, path.to_string(), Err(error)),
+    };
+
+    let tests: Vec<&TestData> = stmts
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Stmt::Test(test) => Some(test),
+            _ => None,
+        })
+        .collect();
+
+    if tests.is_empty() {
+        let result = fresh_interpreter(path, source_map).interpret(stmts.clone());
+        return record(summary, path.to_string(), result);
+    }
+
+    for test in tests {
+        // The rest of the file sets each test up, e.g. with imports and
+        // helper functions. Tests themselves don't run when interpreted.
+        let mut interpreter = fresh_interpreter(path, source_map);
+        let result = interpreter.interpret(stmts.clone()).and_then(|_| {
+            interpreter.interpret(vec![Stmt::Block(test.body.clone(), test.location.clone())])
+        });
+
+        record(summary, format!("{path}: {}", test.name), result);
+    }
+}
+
+fn record(summary: &mut Summary, name: String, result: Result<(), RutoxError>) {
+    match result {
+        Ok(()) => {
+            println!("{}     {name}", "ok".green());
+            summary.passed += 1;
+        }
+        Err(error) => {
+            println!("{} {name}", "FAILED".red().bold());
+            summary.failures.push((name, error));
+        }
+    }
+}
+
+fn fresh_interpreter(path: &str, source_map: &Rc<RefCell<SourceMap>>) -> Interpreter {
+    let mut interpreter = Interpreter::for_file(path, source_map.clone());
+    interpreter.define_native(NativeFunction {
+        name: "assert",
+        arity: 1,
+        function: assert,
+    });
+    interpreter.define_native(NativeFunction {
+        name: "assertEqual",
+        arity: 2,
+        function: assert_equal,
+    });
+
+    interpreter
+}
+
+/// `assert(value)` fails unless `value` is truthy.
+fn assert(
+    interpreter: &mut Interpreter,
+    args: Vec<LoxObj>,
+    location: &SrcLocation,
+) -> Result<LoxObj, RutoxError> {
+    let value = &args[0];
+    if interpreter.is_truthy(value) {
+        return Ok(LoxObj::Nil(location.clone()));
+    }
+
+    Err(RutoxError::Runtime(
+        ErrorCode::AssertionFailed,
+        format!("Assertion failed: expected a truthy value, got {value:?}"),
+        location.clone(),
+    ))
+}
+
+/// `assertEqual(actual, expected)` fails unless both are equal, as with `==`.
+fn assert_equal(
+    interpreter: &mut Interpreter,
+    args: Vec<LoxObj>,
+    location: &SrcLocation,
+) -> Result<LoxObj, RutoxError> {
+    let [actual, expected] = [&args[0], &args[1]];
+    if interpreter.is_equal(actual.clone(), expected.clone()) {
+        return Ok(LoxObj::Nil(location.clone()));
+    }
+
+    Err(RutoxError::Runtime(
+        ErrorCode::AssertionFailed,
+        format!("Assertion failed: expected {expected:?}, got {actual:?}"),
+        location.clone(),
+    ))
+}
@@ -0,0 +1,102 @@
+use crate::interpreter::{Hook, Interpreter, LoxObj};
+use crate::parser::ast::{Expr, LiteralData, Stmt};
+use crate::rutox_error::RutoxError;
+use crate::scanner::{FileId, SrcLocation};
+
+/// Logs each statement before it runs, and each expression with its value,
+/// to stderr for `--trace`. Lines are indented by call depth, e.g.
+///
+/// ```text
+/// 3:1 Print
+/// 3:7 Binary(+) -> number 7
+/// ```
+pub struct Tracer {
+    /// The script being run. Locations in other files (i.e. imports) are
+    /// prefixed with their path.
+    file: Option<FileId>,
+}
+
+impl Tracer {
+    pub fn new(file: Option<FileId>) -> Self {
+        Self { file }
+    }
+
+    fn log(&self, interpreter: &Interpreter, location: &SrcLocation, event: String) {
+        let indent = "  ".repeat(interpreter.call_depth());
+        let path = match self.file {
+            Some(file) if file != location.file => {
+                format!("{}:", interpreter.source_map().get(location.file).path)
+            }
+            _ => String::new(),
+        };
+
+        eprintln!("{indent}{path}{}:{} {event}", location.line, location.col);
+    }
+}
+
+impl Hook for Tracer {
+    fn before_stmt(
+        &mut self,
+        interpreter: &mut Interpreter,
+        stmt: &Stmt,
+    ) -> Result<(), RutoxError> {
+        let location = match stmt {
+            // Blocks are located at their `}`, which reads oddly before the block runs
+            Stmt::Block(stmts, location) => stmts.first().map_or(location.clone(), Stmt::location),
+            _ => stmt.location(),
+        };
+        self.log(interpreter, &location, describe_stmt(stmt));
+
+        Ok(())
+    }
+
+    fn after_expr(&mut self, interpreter: &Interpreter, expr: &Expr, value: &LoxObj) {
+        self.log(
+            interpreter,
+            &expr.span(),
+            format!("{} -> {value:?}", describe_expr(expr)),
+        );
+    }
+}
+
+fn describe_stmt(stmt: &Stmt) -> String {
+    match stmt {
+        Stmt::Print(_, _) => "Print".to_string(),
+        Stmt::Expr(_, _) => "Expr".to_string(),
+        Stmt::Var(name, _, _) => format!("Var({})", name.lexeme),
+        Stmt::Block(_, _) => "Block".to_string(),
+        Stmt::If(_, _, _, _) => "If".to_string(),
+        Stmt::While(_, _, _) => "While".to_string(),
+        Stmt::Break(_) => "Break".to_string(),
+        Stmt::Continue(_) => "Continue".to_string(),
+        Stmt::Function(function) => match &function.name {
+            Some(name) => format!("Fun({})", name.lexeme),
+            None => "Fun".to_string(),
+        },
+        Stmt::Return(_, _) => "Return".to_string(),
+        Stmt::Throw(_, _) => "Throw".to_string(),
+        Stmt::Try(_) => "Try".to_string(),
+        Stmt::Import(import) => format!("Import({})", import.name),
+        Stmt::Test(test) => format!("Test({:?})", test.name),
+    }
+}
+
+fn describe_expr(expr: &Expr) -> String {
+    match expr {
+        Expr::Binary(binary) => format!("Binary({})", binary.operator),
+        Expr::Grouping(_, _) => "Grouping".to_string(),
+        Expr::Unary(unary) => format!("Unary({})", unary.operator),
+        Expr::Logical(_, op, _, _) => format!("Logical({op})"),
+        Expr::Literal(literal) => match literal {
+            LiteralData::String(_, _) => "Literal(string)".to_string(),
+            LiteralData::Number(_, _) => "Literal(number)".to_string(),
+            LiteralData::Bool(_, _) => "Literal(boolean)".to_string(),
+            LiteralData::Nil(_) => "Literal(nil)".to_string(),
+        },
+        Expr::Variable(name, _) => format!("Variable({})", name.lexeme),
+        Expr::Assign(name, _, _) => format!("Assign({})", name.lexeme),
+        Expr::Call(_) => "Call".to_string(),
+        Expr::Lambda(_) => "Lambda".to_string(),
+        Expr::Get(_, name, _) => format!("Get({})", name.lexeme),
+    }
+}
@@ -0,0 +1,138 @@
+use crate::interpreter::{operators::Operator, LoxObj};
+use crate::parser::ast::ImportData;
+use crate::scanner::SrcLocation;
+use std::rc::Rc;
+
+/// A single instruction. Operands index into the tables of its chunk or the
+/// frame's stack slots, and jump targets are instruction indices.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    /// Pushes a constant.
+    Constant(u32),
+    Nil,
+    Pop,
+    GetLocal(u32),
+    /// Assigns the value on top of the stack, leaving it there.
+    SetLocal(u32),
+    GetUpvalue(u32),
+    SetUpvalue(u32),
+    /// Reads the global of a reference.
+    GetGlobal(u32),
+    SetGlobal(u32),
+    /// Pops a value and binds it to a name as a global.
+    DefineGlobal(u32),
+    /// Replaces the module on top of the stack with its binding of a name.
+    GetMember(u32),
+    Binary(Operator),
+    Not,
+    Negate,
+    Print,
+    Jump(u32),
+    /// Jumps if the value on top of the stack is falsy, leaving it there.
+    JumpIfFalse(u32),
+    JumpIfTrue(u32),
+    /// Calls the function below its arguments.
+    Call(u32),
+    /// Pushes a closure over a function of the chunk.
+    Closure(u32),
+    /// Drops the locals from a slot up, closing any upvalues over them.
+    EndScope(u32),
+    Return,
+    Throw,
+    /// Starts a `try`: until it is popped, errors unwind to the target.
+    PushHandler(u32),
+    PopHandler,
+    /// Pushes what a `catch` clause binds for the pending error, or jumps to
+    /// the target if it doesn't catch it.
+    Catch(u32),
+    /// Raises the pending error again.
+    Rethrow,
+    /// Forgets the pending error, when a `finally` jumps out instead of
+    /// raising it again.
+    Discard,
+    /// Pushes the module of an import, running its file first.
+    Import(u32),
+}
+
+/// Where an instruction comes from, for the values it creates and the
+/// errors it reports.
+#[derive(Debug, Clone)]
+pub struct Origin {
+    /// Where values it creates are located, e.g. the operator of a binary
+    /// expression.
+    pub location: SrcLocation,
+    /// All of its expression, which errors point at.
+    pub span: SrcLocation,
+    /// Each operand, for labels on the ones at fault.
+    pub operands: Vec<SrcLocation>,
+}
+
+/// A global read or assigned, with the locals in scope there to suggest one
+/// of if it isn't defined.
+#[derive(Debug)]
+pub struct Reference {
+    pub name: String,
+    pub locals: Vec<String>,
+}
+
+/// The bytecode of a function or script and the tables its instructions
+/// refer to.
+#[derive(Default)]
+pub struct Chunk {
+    pub code: Vec<Op>,
+    /// The origin of each instruction, as an index into `origins`.
+    origin_indices: Vec<u32>,
+    origins: Vec<Origin>,
+    pub constants: Vec<LoxObj>,
+    pub names: Vec<String>,
+    pub references: Vec<Reference>,
+    pub functions: Vec<Rc<Function>>,
+    pub imports: Vec<ImportData>,
+}
+
+impl Chunk {
+    /// Appends `op`, returning its index.
+    pub fn push(&mut self, op: Op, origin: u32) -> usize {
+        self.code.push(op);
+        self.origin_indices.push(origin);
+
+        self.code.len() - 1
+    }
+
+    /// Adds an origin for instructions to share.
+    pub fn add_origin(&mut self, origin: Origin) -> u32 {
+        self.origins.push(origin);
+
+        (self.origins.len() - 1) as u32
+    }
+
+    pub fn origin(&self, ip: usize) -> &Origin {
+        &self.origins[self.origin_indices[ip] as usize]
+    }
+}
+
+/// A compiled function, or the top level of a script.
+pub struct Function {
+    pub name: Option<String>,
+    pub arity: usize,
+    pub chunk: Chunk,
+    /// What each upvalue of a closure over it captures, in order.
+    pub captures: Vec<Capture>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Capture {
+    /// A local slot of the function creating the closure.
+    Local(u32),
+    /// An upvalue of the function creating the closure.
+    Upvalue(u32),
+}
+
+impl std::fmt::Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "<fn {name}>"),
+            None => write!(f, "<fn>"),
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use super::Function;
+use crate::interpreter::{Env, LoxObj};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// A compiled function together with the variables it captured, so it can
+/// see the variables around it when called.
+pub struct Closure {
+    pub function: Rc<Function>,
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    /// The globals of the script or module it was created in.
+    pub globals: Rc<RefCell<Env>>,
+}
+
+impl std::fmt::Display for Closure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.function)
+    }
+}
+
+/// A captured variable. It stays on the stack while the scope declaring it
+/// runs, and moves into the upvalue when that scope ends.
+pub enum Upvalue {
+    Open(usize),
+    Closed(LoxObj),
+}
+
+impl PartialEq for Closure {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
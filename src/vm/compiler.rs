@@ -0,0 +1,740 @@
+use super::chunk::{Capture, Chunk, Function, Op, Origin, Reference};
+use crate::interpreter::LoxObj;
+use crate::parser::{
+    ast::{
+        BinaryData, CallData, Expr, FunctionData, ImportData, LiteralData, LogicalOp, Stmt,
+        TestData, TryData, UnaryData, UnaryOp,
+    },
+    visitors::{ExprVisitor, StmtVisitor},
+};
+use crate::rutox_error::RutoxError;
+use crate::scanner::{token::Token, FileId, SrcLocation};
+use std::rc::Rc;
+
+/// Compiles a program to bytecode for the VM. Variables declared at the top
+/// level are globals, looked up by name when used. All others live in stack
+/// slots, which are resolved here.
+pub struct Compiler {
+    /// The functions being compiled, innermost last. The first is the script.
+    functions: Vec<FunctionState>,
+}
+
+struct FunctionState {
+    function: Function,
+    /// The locals in scope, by slot. Slot 0 holds the function itself, and
+    /// unnamed locals hold values the compiler keeps around.
+    locals: Vec<Local>,
+    scope_depth: usize,
+    /// The loops and `try` statements around the code being compiled,
+    /// innermost last, for what `break`, `continue` and `return` undo.
+    blocks: Vec<Block>,
+}
+
+struct Local {
+    name: String,
+    depth: usize,
+}
+
+enum Block {
+    Loop {
+        start: usize,
+        /// How many locals were in scope before the body.
+        locals: usize,
+        breaks: Vec<usize>,
+    },
+    /// The body or `catch` clause of a `try` while its handler is active,
+    /// with the `finally` to run when jumping out of it.
+    Try(Option<Vec<Stmt>>),
+    /// The `finally` run before an uncaught error is raised again.
+    Pending,
+}
+
+enum Variable {
+    Local(u32),
+    Upvalue(u32),
+    Global,
+}
+
+impl Compiler {
+    /// The script `stmts` make up, as a function without parameters.
+    pub fn compile(stmts: &[Stmt]) -> Result<Function, RutoxError> {
+        let mut compiler = Compiler {
+            // The script's own scope holds the globals
+            functions: vec![FunctionState::new(None, 0, 0)],
+        };
+
+        for stmt in stmts {
+            compiler.visit_stmt(stmt)?;
+        }
+        let end = match stmts.last() {
+            Some(stmt) => stmt.location(),
+            None => SrcLocation::new(0, 0, FileId::default()),
+        };
+        compiler.finish_function(&end);
+
+        let state = compiler
+            .functions
+            .pop()
+            .expect("The script is always compiled");
+        Ok(state.function)
+    }
+
+    fn state(&mut self) -> &mut FunctionState {
+        self.functions
+            .last_mut()
+            .expect("The script is always compiled")
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.state().function.chunk
+    }
+
+    fn origin(&mut self, location: &SrcLocation) -> u32 {
+        self.chunk().add_origin(Origin {
+            location: location.clone(),
+            span: location.clone(),
+            operands: vec![],
+        })
+    }
+
+    fn emit(&mut self, op: Op, origin: u32) -> usize {
+        self.chunk().push(op, origin)
+    }
+
+    /// Points the jump at `index` to the next instruction.
+    fn patch(&mut self, index: usize) {
+        let chunk = self.chunk();
+        let target = chunk.code.len() as u32;
+
+        match &mut chunk.code[index] {
+            Op::Jump(to)
+            | Op::JumpIfFalse(to)
+            | Op::JumpIfTrue(to)
+            | Op::PushHandler(to)
+            | Op::Catch(to) => *to = target,
+            op => unreachable!("Only jumps are patched, not {op:?}"),
+        }
+    }
+
+    fn name(&mut self, name: &str) -> u32 {
+        let names = &mut self.chunk().names;
+        names.push(name.to_string());
+
+        (names.len() - 1) as u32
+    }
+
+    fn begin_scope(&mut self) {
+        self.state().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self, location: &SrcLocation) {
+        let state = self.state();
+        state.scope_depth -= 1;
+        let depth = state.scope_depth;
+        let start = state
+            .locals
+            .iter()
+            .rposition(|local| local.depth <= depth)
+            .map_or(0, |slot| slot + 1);
+
+        if start < state.locals.len() {
+            state.locals.truncate(start);
+            let origin = self.origin(location);
+            self.emit(Op::EndScope(start as u32), origin);
+        }
+    }
+
+    fn block(&mut self, stmts: &[Stmt], location: &SrcLocation) -> Result<(), RutoxError> {
+        self.begin_scope();
+        for stmt in stmts {
+            self.visit_stmt(stmt)?;
+        }
+        self.end_scope(location);
+
+        Ok(())
+    }
+
+    /// Makes the value on top of the stack a local called `name`.
+    fn declare_local(&mut self, name: &str) {
+        let state = self.state();
+        let depth = state.scope_depth;
+        state.locals.push(Local {
+            name: name.to_string(),
+            depth,
+        });
+    }
+
+    /// Binds the value on top of the stack to `name` in the current scope.
+    fn define(&mut self, name: &str, origin: u32) {
+        if self.state().scope_depth == 0 {
+            let name = self.name(name);
+            self.emit(Op::DefineGlobal(name), origin);
+        } else {
+            self.declare_local(name);
+        }
+    }
+
+    fn resolve(&mut self, name: &str) -> Variable {
+        let level = self.functions.len() - 1;
+
+        if let Some(slot) = Self::resolve_local(&self.functions[level], name) {
+            return Variable::Local(slot);
+        }
+        match self.resolve_upvalue(level, name) {
+            Some(index) => Variable::Upvalue(index),
+            None => Variable::Global,
+        }
+    }
+
+    fn resolve_local(state: &FunctionState, name: &str) -> Option<u32> {
+        state
+            .locals
+            .iter()
+            .rposition(|local| local.name == name)
+            .map(|slot| slot as u32)
+    }
+
+    /// The upvalue of the function at `level` capturing `name` from the
+    /// functions around it, added if it has none yet.
+    fn resolve_upvalue(&mut self, level: usize, name: &str) -> Option<u32> {
+        if level == 0 {
+            return None;
+        }
+
+        let capture = match Self::resolve_local(&self.functions[level - 1], name) {
+            Some(slot) => Capture::Local(slot),
+            None => Capture::Upvalue(self.resolve_upvalue(level - 1, name)?),
+        };
+        let captures = &mut self.functions[level].function.captures;
+        let index = match captures.iter().position(|c| *c == capture) {
+            Some(index) => index,
+            None => {
+                captures.push(capture);
+                captures.len() - 1
+            }
+        };
+
+        Some(index as u32)
+    }
+
+    fn reference(&mut self, name: &str) -> u32 {
+        let locals = self
+            .functions
+            .iter()
+            .rev()
+            .flat_map(|state| state.locals.iter().rev())
+            .filter(|local| !local.name.is_empty())
+            .map(|local| local.name.clone())
+            .collect();
+
+        let references = &mut self.chunk().references;
+        references.push(Reference {
+            name: name.to_string(),
+            locals,
+        });
+
+        (references.len() - 1) as u32
+    }
+
+    /// Compiles `declaration` and pushes a closure over it.
+    fn function(&mut self, declaration: &FunctionData, origin: u32) -> Result<(), RutoxError> {
+        let name = declaration.name.as_ref().map(|name| name.lexeme.clone());
+        self.functions
+            .push(FunctionState::new(name, declaration.params.len(), 1));
+
+        for param in &declaration.params {
+            self.declare_local(&param.lexeme);
+        }
+        for stmt in &declaration.body {
+            self.visit_stmt(stmt)?;
+        }
+        self.finish_function(&declaration.location);
+
+        let state = self.functions.pop().expect("The function was just pushed");
+        let functions = &mut self.chunk().functions;
+        functions.push(Rc::new(state.function));
+        let index = (functions.len() - 1) as u32;
+        self.emit(Op::Closure(index), origin);
+
+        Ok(())
+    }
+
+    fn finish_function(&mut self, location: &SrcLocation) {
+        let origin = self.origin(location);
+        self.emit(Op::Nil, origin);
+        self.emit(Op::Return, origin);
+    }
+
+    /// Undoes the blocks from `until` inwards before jumping out of them:
+    /// their handlers are popped and their `finally` clauses run.
+    fn exit_blocks(&mut self, until: usize, location: &SrcLocation) -> Result<(), RutoxError> {
+        let origin = self.origin(location);
+
+        for i in (until..self.state().blocks.len()).rev() {
+            match &self.state().blocks[i] {
+                Block::Loop { .. } => {}
+                Block::Try(finally) => {
+                    let finally = finally.clone();
+                    self.emit(Op::PopHandler, origin);
+
+                    if let Some(finally) = finally {
+                        // The `finally` runs outside the blocks it is in
+                        let inner = self.state().blocks.split_off(i);
+                        self.block(&finally, location)?;
+                        self.state().blocks.extend(inner);
+                    }
+                }
+                Block::Pending => {
+                    self.emit(Op::Discard, origin);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Jumps out of the blocks up to the innermost loop, returning its index.
+    fn exit_to_loop(&mut self, location: &SrcLocation) -> Result<usize, RutoxError> {
+        let Some(index) = self
+            .state()
+            .blocks
+            .iter()
+            .rposition(|block| matches!(block, Block::Loop { .. }))
+        else {
+            return Err(RutoxError::Programmer(
+                "Jumps out of a loop are only parsed inside one".to_string(),
+                location.clone(),
+            ));
+        };
+
+        self.exit_blocks(index + 1, location)?;
+
+        let Block::Loop { locals, .. } = self.state().blocks[index] else {
+            unreachable!("The index was just found to be a loop");
+        };
+        if locals < self.state().locals.len() {
+            let origin = self.origin(location);
+            self.emit(Op::EndScope(locals as u32), origin);
+        }
+
+        Ok(index)
+    }
+}
+
+impl FunctionState {
+    fn new(name: Option<String>, arity: usize, scope_depth: usize) -> Self {
+        FunctionState {
+            scope_depth,
+            function: Function {
+                name,
+                arity,
+                chunk: Chunk::default(),
+                captures: vec![],
+            },
+            locals: vec![Local {
+                name: String::new(),
+                depth: 0,
+            }],
+            blocks: vec![],
+        }
+    }
+}
+
+impl StmtVisitor<()> for Compiler {
+    fn visit_print_stmt(&mut self, expr: &Expr, location: &SrcLocation) -> Result<(), RutoxError> {
+        self.visit_expr(expr)?;
+        let origin = self.origin(location);
+        self.emit(Op::Print, origin);
+
+        Ok(())
+    }
+
+    fn visit_expr_stmt(&mut self, expr: &Expr, location: &SrcLocation) -> Result<(), RutoxError> {
+        self.visit_expr(expr)?;
+        let origin = self.origin(location);
+        self.emit(Op::Pop, origin);
+
+        Ok(())
+    }
+
+    fn visit_var_stmt(
+        &mut self,
+        name: &Token,
+        initializer: &Option<Expr>,
+        location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        let origin = self.origin(location);
+        match initializer {
+            Some(initializer) => self.visit_expr(initializer)?,
+            None => {
+                self.emit(Op::Nil, origin);
+            }
+        }
+        self.define(&name.lexeme, origin);
+
+        Ok(())
+    }
+
+    fn visit_block_stmt(
+        &mut self,
+        stmts: &[Stmt],
+        location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        self.block(stmts, location)
+    }
+
+    fn visit_if_stmt(
+        &mut self,
+        cond: &Expr,
+        then_branch: &Stmt,
+        else_branch: &Option<Box<Stmt>>,
+        location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        self.visit_expr(cond)?;
+        let origin = self.origin(location);
+        let else_jump = self.emit(Op::JumpIfFalse(0), origin);
+        self.emit(Op::Pop, origin);
+        self.visit_stmt(then_branch)?;
+        let end_jump = self.emit(Op::Jump(0), origin);
+
+        self.patch(else_jump);
+        self.emit(Op::Pop, origin);
+        if let Some(else_branch) = else_branch {
+            self.visit_stmt(else_branch)?;
+        }
+        self.patch(end_jump);
+
+        Ok(())
+    }
+
+    fn visit_while_stmt(
+        &mut self,
+        cond: &Expr,
+        body: &Stmt,
+        location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        let start = self.chunk().code.len();
+        self.visit_expr(cond)?;
+        let origin = self.origin(location);
+        let exit_jump = self.emit(Op::JumpIfFalse(0), origin);
+        self.emit(Op::Pop, origin);
+
+        let locals = self.state().locals.len();
+        self.state().blocks.push(Block::Loop {
+            start,
+            locals,
+            breaks: vec![],
+        });
+        self.visit_stmt(body)?;
+        let Some(Block::Loop { breaks, .. }) = self.state().blocks.pop() else {
+            unreachable!("The loop's block was pushed above");
+        };
+        self.emit(Op::Jump(start as u32), origin);
+
+        self.patch(exit_jump);
+        self.emit(Op::Pop, origin);
+        for jump in breaks {
+            self.patch(jump);
+        }
+
+        Ok(())
+    }
+
+    fn visit_break_stmt(&mut self, location: &SrcLocation) -> Result<(), RutoxError> {
+        let index = self.exit_to_loop(location)?;
+        let origin = self.origin(location);
+        let jump = self.emit(Op::Jump(0), origin);
+
+        if let Block::Loop { breaks, .. } = &mut self.state().blocks[index] {
+            breaks.push(jump);
+        }
+
+        Ok(())
+    }
+
+    fn visit_continue_stmt(&mut self, location: &SrcLocation) -> Result<(), RutoxError> {
+        let index = self.exit_to_loop(location)?;
+        let Block::Loop { start, .. } = self.state().blocks[index] else {
+            unreachable!("`exit_to_loop` returns the index of a loop");
+        };
+        let origin = self.origin(location);
+        self.emit(Op::Jump(start as u32), origin);
+
+        Ok(())
+    }
+
+    fn visit_function_stmt(&mut self, function: &Rc<FunctionData>) -> Result<(), RutoxError> {
+        let name = function
+            .name
+            .as_ref()
+            .expect("A function statement should always have a name");
+        let origin = self.origin(&function.location);
+
+        if self.state().scope_depth == 0 {
+            self.function(function, origin)?;
+            self.define(&name.lexeme, origin);
+        } else {
+            // Declared first, so the function can call itself
+            self.declare_local(&name.lexeme);
+            self.function(function, origin)?;
+        }
+
+        Ok(())
+    }
+
+    fn visit_return_stmt(
+        &mut self,
+        value: &Option<Expr>,
+        location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        let origin = self.origin(location);
+        match value {
+            Some(value) => self.visit_expr(value)?,
+            None => {
+                self.emit(Op::Nil, origin);
+            }
+        }
+
+        // The value is kept in a local while `finally` clauses run
+        self.declare_local("");
+        self.exit_blocks(0, location)?;
+        self.state().locals.pop();
+        self.emit(Op::Return, origin);
+
+        Ok(())
+    }
+
+    fn visit_throw_stmt(&mut self, value: &Expr, location: &SrcLocation) -> Result<(), RutoxError> {
+        self.visit_expr(value)?;
+        let origin = self.origin(location);
+        self.emit(Op::Throw, origin);
+
+        Ok(())
+    }
+
+    fn visit_try_stmt(&mut self, try_data: &TryData) -> Result<(), RutoxError> {
+        let location = &try_data.location;
+        let origin = self.origin(location);
+        let finally = &try_data.finally;
+
+        let handler = self.emit(Op::PushHandler(0), origin);
+        self.state().blocks.push(Block::Try(finally.clone()));
+        self.block(&try_data.body, location)?;
+        self.state().blocks.pop();
+        self.emit(Op::PopHandler, origin);
+        if let Some(finally) = finally {
+            self.block(finally, location)?;
+        }
+        let mut end_jumps = vec![self.emit(Op::Jump(0), origin)];
+
+        // From here on, an error is pending
+        self.patch(handler);
+        if let Some(catch) = &try_data.catch {
+            // Errors in the `catch` clause still run the `finally`
+            let catch_handler = finally
+                .is_some()
+                .then(|| self.emit(Op::PushHandler(0), origin));
+            let uncaught = self.emit(Op::Catch(0), origin);
+
+            if catch_handler.is_some() {
+                self.state().blocks.push(Block::Try(finally.clone()));
+            }
+            self.begin_scope();
+            self.declare_local(&catch.name.lexeme);
+            for stmt in &catch.body {
+                self.visit_stmt(stmt)?;
+            }
+            self.end_scope(location);
+            if catch_handler.is_some() {
+                self.state().blocks.pop();
+                self.emit(Op::PopHandler, origin);
+            }
+            if let Some(finally) = finally {
+                self.block(finally, location)?;
+            }
+            end_jumps.push(self.emit(Op::Jump(0), origin));
+
+            self.patch(uncaught);
+            if let Some(catch_handler) = catch_handler {
+                self.emit(Op::PopHandler, origin);
+                self.patch(catch_handler);
+            }
+        }
+
+        if let Some(finally) = finally {
+            self.state().blocks.push(Block::Pending);
+            self.block(finally, location)?;
+            self.state().blocks.pop();
+        }
+        self.emit(Op::Rethrow, origin);
+
+        for jump in end_jumps {
+            self.patch(jump);
+        }
+
+        Ok(())
+    }
+
+    fn visit_import_stmt(&mut self, import: &ImportData) -> Result<(), RutoxError> {
+        let origin = self.origin(&import.location);
+        let imports = &mut self.chunk().imports;
+        imports.push(import.clone());
+        let index = (imports.len() - 1) as u32;
+
+        self.emit(Op::Import(index), origin);
+        self.define(&import.name, origin);
+
+        Ok(())
+    }
+
+    /// Tests only run under `rutox test`, which uses the tree-walking
+    /// interpreter.
+    fn visit_test_stmt(&mut self, _test: &TestData) -> Result<(), RutoxError> {
+        Ok(())
+    }
+}
+
+impl ExprVisitor<()> for Compiler {
+    fn visit_literal_expr(&mut self, literal: &LiteralData) -> Result<(), RutoxError> {
+        let origin = self.origin(&literal.location());
+        let constants = &mut self.chunk().constants;
+        constants.push(LoxObj::from(literal.clone()));
+        let index = (constants.len() - 1) as u32;
+
+        self.emit(Op::Constant(index), origin);
+
+        Ok(())
+    }
+
+    fn visit_unary_expr(&mut self, unary: &UnaryData) -> Result<(), RutoxError> {
+        self.visit_expr(&unary.expr)?;
+
+        match &unary.operator {
+            UnaryOp::Bang(location) => {
+                let origin = self.origin(location);
+                self.emit(Op::Not, origin);
+            }
+            UnaryOp::Minus(location) => {
+                let origin = self.chunk().add_origin(Origin {
+                    location: location.clone(),
+                    span: unary.span(),
+                    operands: vec![unary.expr.span()],
+                });
+                self.emit(Op::Negate, origin);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn visit_binary_expr(&mut self, binary: &BinaryData) -> Result<(), RutoxError> {
+        self.visit_expr(&binary.left)?;
+        self.visit_expr(&binary.right)?;
+
+        let origin = self.chunk().add_origin(Origin {
+            location: binary.location.clone(),
+            span: binary.span(),
+            operands: vec![binary.left.span(), binary.right.span()],
+        });
+        self.emit(Op::Binary((&binary.operator).into()), origin);
+
+        Ok(())
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &Expr) -> Result<(), RutoxError> {
+        self.visit_expr(expr)
+    }
+
+    fn visit_variable_expr(
+        &mut self,
+        name: &Token,
+        location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        let origin = self.origin(location);
+        let op = match self.resolve(&name.lexeme) {
+            Variable::Local(slot) => Op::GetLocal(slot),
+            Variable::Upvalue(index) => Op::GetUpvalue(index),
+            Variable::Global => Op::GetGlobal(self.reference(&name.lexeme)),
+        };
+        self.emit(op, origin);
+
+        Ok(())
+    }
+
+    fn visit_assign_expr(
+        &mut self,
+        name: &Token,
+        value: &Expr,
+        location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        self.visit_expr(value)?;
+
+        let origin = self.origin(location);
+        let op = match self.resolve(&name.lexeme) {
+            Variable::Local(slot) => Op::SetLocal(slot),
+            Variable::Upvalue(index) => Op::SetUpvalue(index),
+            Variable::Global => Op::SetGlobal(self.reference(&name.lexeme)),
+        };
+        self.emit(op, origin);
+
+        Ok(())
+    }
+
+    fn visit_logical_expr(
+        &mut self,
+        left: &Expr,
+        op: &LogicalOp,
+        right: &Expr,
+        location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        self.visit_expr(left)?;
+
+        // The left operand is the result if it decides it
+        let origin = self.origin(location);
+        let end_jump = match op {
+            LogicalOp::Or(_) => self.emit(Op::JumpIfTrue(0), origin),
+            LogicalOp::And(_) => self.emit(Op::JumpIfFalse(0), origin),
+        };
+        self.emit(Op::Pop, origin);
+        self.visit_expr(right)?;
+        self.patch(end_jump);
+
+        Ok(())
+    }
+
+    fn visit_call_expr(&mut self, call: &CallData) -> Result<(), RutoxError> {
+        self.visit_expr(&call.callee)?;
+        for arg in &call.args {
+            self.visit_expr(arg)?;
+        }
+
+        let origin = self.chunk().add_origin(Origin {
+            location: call.location.clone(),
+            span: call.callee.span(),
+            operands: vec![],
+        });
+        self.emit(Op::Call(call.args.len() as u32), origin);
+
+        Ok(())
+    }
+
+    fn visit_lambda_expr(&mut self, function: &Rc<FunctionData>) -> Result<(), RutoxError> {
+        let origin = self.origin(&function.location);
+
+        self.function(function, origin)
+    }
+
+    fn visit_get_expr(
+        &mut self,
+        object: &Expr,
+        name: &Token,
+        location: &SrcLocation,
+    ) -> Result<(), RutoxError> {
+        self.visit_expr(object)?;
+        let origin = self.origin(location);
+        let name = self.name(&name.lexeme);
+        self.emit(Op::GetMember(name), origin);
+
+        Ok(())
+    }
+}
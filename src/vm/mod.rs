@@ -0,0 +1,548 @@
+mod chunk;
+mod closure;
+mod compiler;
+use crate::error_code::ErrorCode;
+use crate::interpreter::{operators, Env, Import, Interpreter, LoxObj, Modules};
+use crate::parser::ast::Stmt;
+use crate::rutox_error::{RutoxError, StackFrame};
+use crate::scanner::{SourceMap, SrcLocation};
+pub use chunk::{Capture, Chunk, Function, Op, Origin, Reference};
+pub use closure::{Closure, Upvalue};
+pub use compiler::Compiler;
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::str::FromStr;
+
+/// What runs scripts, chosen with `--backend`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    /// The `Interpreter`, walking the AST.
+    Tree,
+    /// The `Vm`, running the AST compiled to bytecode.
+    Vm,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(backend: &str) -> Result<Self, Self::Err> {
+        match backend {
+            "tree" => Ok(Backend::Tree),
+            "vm" => Ok(Backend::Vm),
+            other => Err(format!(
+                "Unknown backend `{other}`, expected `tree` or `vm`"
+            )),
+        }
+    }
+}
+
+/// Runs programs compiled to bytecode on a stack of values. It gives the
+/// same results and errors as the `Interpreter`, without walking the AST or
+/// creating an environment for every block and call.
+pub struct Vm {
+    /// The values being worked on and the locals of every frame.
+    stack: Vec<LoxObj>,
+    /// The functions being run, outermost first. The one running is kept
+    /// out of it while it runs.
+    frames: Vec<Frame>,
+    /// The `try` statements being run, innermost last.
+    handlers: Vec<Handler>,
+    /// The errors `catch` and `finally` clauses are running for, innermost
+    /// last.
+    pending: Vec<RutoxError>,
+    /// Upvalues over slots still on the stack, to share between closures.
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    globals: Rc<RefCell<Env>>,
+    call_depth: usize,
+    modules: Modules,
+    /// Where imported files are registered, so their errors can be shown.
+    source_map: Rc<RefCell<SourceMap>>,
+}
+
+struct Frame {
+    closure: Rc<Closure>,
+    /// The next instruction to run.
+    ip: usize,
+    /// Where its slot 0 is on the stack.
+    base: usize,
+    kind: FrameKind,
+}
+
+enum FrameKind {
+    /// The script or REPL entry being run.
+    Script,
+    /// A function called at a location.
+    Call(SrcLocation),
+    /// A file being imported, which becomes a module once it's done.
+    Module {
+        path: PathBuf,
+        name: String,
+        location: SrcLocation,
+        previous_dir: PathBuf,
+    },
+}
+
+impl Frame {
+    fn chunk(&self) -> &Chunk {
+        &self.closure.function.chunk
+    }
+
+    /// Where the instruction being run comes from.
+    fn origin(&self) -> &Origin {
+        self.chunk().origin(self.ip - 1)
+    }
+}
+
+struct Handler {
+    /// The index its frame will have in `frames` when it's not running.
+    frame: usize,
+    target: usize,
+    /// How much of the stack and `pending` to keep when unwinding to it.
+    stack_len: usize,
+    pending_len: usize,
+}
+
+impl Vm {
+    pub fn new(source_map: Rc<RefCell<SourceMap>>) -> Self {
+        Vm {
+            stack: vec![],
+            frames: vec![],
+            handlers: vec![],
+            pending: vec![],
+            open_upvalues: vec![],
+            globals: Rc::new(RefCell::new(Env::default())),
+            call_depth: 0,
+            modules: Modules::default(),
+            source_map,
+        }
+    }
+
+    /// A VM for the script at `path`, which imports are resolved relative
+    /// to.
+    pub fn for_file(path: &str, source_map: Rc<RefCell<SourceMap>>) -> Self {
+        Vm {
+            modules: Modules::for_file(path),
+            ..Self::new(source_map)
+        }
+    }
+
+    pub fn interpret(&mut self, stmts: Vec<Stmt>) -> Result<(), RutoxError> {
+        let function = Compiler::compile(&stmts)?;
+        let frame = self.push_script(function, self.globals.clone(), FrameKind::Script);
+
+        self.run(frame)
+    }
+
+    /// Pushes a closure over the script `function`, returning its frame.
+    fn push_script(
+        &mut self,
+        function: Function,
+        globals: Rc<RefCell<Env>>,
+        kind: FrameKind,
+    ) -> Frame {
+        let closure = Rc::new(Closure {
+            function: Rc::new(function),
+            upvalues: vec![],
+            globals,
+        });
+        let location = closure.function.chunk.origin(0).location.clone();
+        self.stack.push(LoxObj::Closure(closure.clone(), location));
+
+        Frame {
+            closure,
+            ip: 0,
+            base: self.stack.len() - 1,
+            kind,
+        }
+    }
+
+    /// Runs `frame` until it returns, unwinding errors to the handlers that
+    /// catch them.
+    fn run(&mut self, mut frame: Frame) -> Result<(), RutoxError> {
+        loop {
+            let error = match self.execute(&mut frame) {
+                Ok(()) => return Ok(()),
+                Err(error) => error,
+            };
+
+            self.frames.push(frame);
+            frame = self.unwind(error)?;
+        }
+    }
+
+    fn execute(&mut self, frame: &mut Frame) -> Result<(), RutoxError> {
+        loop {
+            let op = frame.chunk().code[frame.ip];
+            frame.ip += 1;
+
+            match op {
+                Op::Constant(index) => {
+                    let value = frame.chunk().constants[index as usize].clone();
+                    self.stack.push(value);
+                }
+                Op::Nil => self
+                    .stack
+                    .push(LoxObj::Nil(frame.origin().location.clone())),
+                Op::Pop => {
+                    self.pop();
+                }
+                Op::GetLocal(slot) => {
+                    let value = self.stack[frame.base + slot as usize].clone();
+                    self.stack.push(value);
+                }
+                Op::SetLocal(slot) => {
+                    let value = self.peek().clone();
+                    self.stack[frame.base + slot as usize] = value;
+                }
+                Op::GetUpvalue(index) => {
+                    let value = match &*frame.closure.upvalues[index as usize].borrow() {
+                        Upvalue::Open(slot) => self.stack[*slot].clone(),
+                        Upvalue::Closed(value) => value.clone(),
+                    };
+                    self.stack.push(value);
+                }
+                Op::SetUpvalue(index) => {
+                    let value = self.peek().clone();
+                    match &mut *frame.closure.upvalues[index as usize].borrow_mut() {
+                        Upvalue::Open(slot) => self.stack[*slot] = value,
+                        Upvalue::Closed(closed) => *closed = value,
+                    }
+                }
+                Op::GetGlobal(index) => {
+                    let reference = &frame.chunk().references[index as usize];
+                    let value = frame.closure.globals.borrow().get(&reference.name);
+
+                    match value {
+                        Some(value) => self.stack.push(value),
+                        None => return Err(Self::undefined_variable(frame, reference)),
+                    }
+                }
+                Op::SetGlobal(index) => {
+                    let reference = &frame.chunk().references[index as usize];
+                    let value = self.peek().clone();
+                    let assigned = frame
+                        .closure
+                        .globals
+                        .borrow_mut()
+                        .assign(&reference.name, value);
+
+                    if !assigned {
+                        return Err(Self::undefined_variable(frame, reference));
+                    }
+                }
+                Op::DefineGlobal(index) => {
+                    let value = self.pop();
+                    let name = &frame.chunk().names[index as usize];
+                    frame.closure.globals.borrow_mut().define(name, value);
+                }
+                Op::GetMember(index) => {
+                    let object = self.pop();
+                    let name = &frame.chunk().names[index as usize];
+                    let value = Interpreter::get_member(object, name, &frame.origin().location)?;
+                    self.stack.push(value);
+                }
+                Op::Binary(operator) => {
+                    let b = self.pop();
+                    let a = self.pop();
+                    let origin = frame.origin();
+                    let value = operators::binary(operator, &a, &b, &origin.location).map_err(
+                        |invalid| {
+                            invalid.error(
+                                origin.span.clone(),
+                                &[
+                                    (&a, origin.operands[0].clone()),
+                                    (&b, origin.operands[1].clone()),
+                                ],
+                            )
+                        },
+                    )?;
+                    self.stack.push(value);
+                }
+                Op::Not => {
+                    let value = self.pop();
+                    let location = frame.origin().location.clone();
+                    self.stack
+                        .push(LoxObj::Bool(!operators::is_truthy(&value), location));
+                }
+                Op::Negate => {
+                    let value = self.pop();
+                    let origin = frame.origin();
+                    let value = operators::negate(&value, &origin.location).map_err(|invalid| {
+                        invalid.error(origin.span.clone(), &[(&value, origin.operands[0].clone())])
+                    })?;
+                    self.stack.push(value);
+                }
+                Op::Print => {
+                    let value = self.pop();
+                    println!("{value}");
+                }
+                Op::Jump(target) => frame.ip = target as usize,
+                Op::JumpIfFalse(target) => {
+                    if !operators::is_truthy(self.peek()) {
+                        frame.ip = target as usize;
+                    }
+                }
+                Op::JumpIfTrue(target) => {
+                    if operators::is_truthy(self.peek()) {
+                        frame.ip = target as usize;
+                    }
+                }
+                Op::Call(arg_count) => self.call(frame, arg_count as usize)?,
+                Op::Closure(index) => {
+                    let function = frame.chunk().functions[index as usize].clone();
+                    let upvalues = function
+                        .captures
+                        .iter()
+                        .map(|capture| match capture {
+                            Capture::Local(slot) => self.capture(frame.base + *slot as usize),
+                            Capture::Upvalue(index) => {
+                                frame.closure.upvalues[*index as usize].clone()
+                            }
+                        })
+                        .collect();
+                    let closure = Closure {
+                        function,
+                        upvalues,
+                        globals: frame.closure.globals.clone(),
+                    };
+                    let location = frame.origin().location.clone();
+                    self.stack.push(LoxObj::Closure(Rc::new(closure), location));
+                }
+                Op::EndScope(slot) => self.truncate(frame.base + slot as usize),
+                Op::Return => {
+                    let value = self.pop();
+                    self.truncate(frame.base);
+
+                    let Some(caller) = self.frames.pop() else {
+                        return Ok(());
+                    };
+                    let finished = std::mem::replace(frame, caller);
+                    let value = self.finish(finished, value);
+                    self.stack.push(value);
+                }
+                Op::Throw => {
+                    let value = self.pop();
+                    return Err(Interpreter::thrown(value, &frame.origin().location));
+                }
+                Op::PushHandler(target) => self.handlers.push(Handler {
+                    frame: self.frames.len(),
+                    target: target as usize,
+                    stack_len: self.stack.len(),
+                    pending_len: self.pending.len(),
+                }),
+                Op::PopHandler => {
+                    self.handlers.pop();
+                }
+                Op::Catch(target) => match Interpreter::caught(self.pending_error()) {
+                    Some(value) => {
+                        self.pending.pop();
+                        self.stack.push(value);
+                    }
+                    None => frame.ip = target as usize,
+                },
+                Op::Rethrow => {
+                    let error = self.pending.pop();
+                    return Err(error.expect("Errors are only raised again while pending"));
+                }
+                Op::Discard => {
+                    self.pending.pop();
+                }
+                Op::Import(index) => self.import(frame, index as usize)?,
+            }
+        }
+    }
+
+    fn pop(&mut self) -> LoxObj {
+        self.stack
+            .pop()
+            .expect("Instructions only pop what was pushed")
+    }
+
+    fn peek(&self) -> &LoxObj {
+        self.stack
+            .last()
+            .expect("Instructions only peek at what was pushed")
+    }
+
+    fn pending_error(&self) -> &RutoxError {
+        self.pending
+            .last()
+            .expect("`catch` clauses only run for a pending error")
+    }
+
+    /// Calls the value below the `arg_count` arguments on top of the stack.
+    fn call(&mut self, frame: &mut Frame, arg_count: usize) -> Result<(), RutoxError> {
+        let callee_slot = self.stack.len() - arg_count - 1;
+        let origin = frame.origin();
+
+        let closure = match &self.stack[callee_slot] {
+            LoxObj::Closure(closure, _) => closure.clone(),
+            LoxObj::Function(_, _) | LoxObj::Native(_, _) => {
+                return Err(RutoxError::Programmer(
+                    "Only the tree-walking interpreter can call this function".to_string(),
+                    origin.location.clone(),
+                ))
+            }
+            other => {
+                return Err(RutoxError::Runtime(
+                    ErrorCode::NotCallable,
+                    format!("Can only call functions, but got {:?}", other),
+                    origin.span.clone(),
+                ))
+            }
+        };
+
+        Interpreter::check_arity(closure.function.arity, arg_count, &origin.location)?;
+        Interpreter::check_call_depth(self.call_depth, &origin.location)?;
+        self.call_depth += 1;
+
+        let callee = Frame {
+            closure,
+            ip: 0,
+            base: callee_slot,
+            kind: FrameKind::Call(origin.location.clone()),
+        };
+        self.frames.push(std::mem::replace(frame, callee));
+
+        Ok(())
+    }
+
+    /// Pushes the module of the `index`th import of the running chunk, or
+    /// starts running its file if it wasn't imported before.
+    fn import(&mut self, frame: &mut Frame, index: usize) -> Result<(), RutoxError> {
+        let import = &frame.chunk().imports[index];
+
+        match self.modules.import(import, &self.source_map)? {
+            Import::Loaded(module) => {
+                self.stack
+                    .push(LoxObj::Module(module, import.location.clone()));
+            }
+            Import::Parsed(path, stmts) => {
+                let function = Compiler::compile(&stmts)?;
+                let kind = FrameKind::Module {
+                    previous_dir: self.modules.enter(&path),
+                    path,
+                    name: import.name.clone(),
+                    location: import.location.clone(),
+                };
+                let globals = Rc::new(RefCell::new(Env::default()));
+                let module_frame = self.push_script(function, globals, kind);
+
+                self.frames.push(std::mem::replace(frame, module_frame));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// What a frame that returned `value` gives its caller.
+    fn finish(&mut self, frame: Frame, value: LoxObj) -> LoxObj {
+        match frame.kind {
+            FrameKind::Script => value,
+            FrameKind::Call(_) => {
+                self.call_depth -= 1;
+                value
+            }
+            FrameKind::Module {
+                path,
+                name,
+                location,
+                previous_dir,
+            } => {
+                self.modules.leave(previous_dir);
+                let env = frame.closure.globals.clone();
+
+                LoxObj::Module(self.modules.finish(path, &name, env), location)
+            }
+        }
+    }
+
+    /// Unwinds the frames in `frames` to the innermost handler and returns
+    /// the frame to continue running from it, or fails with `error` if
+    /// there is none.
+    fn unwind(&mut self, mut error: RutoxError) -> Result<Frame, RutoxError> {
+        let handler = self.handlers.pop();
+        let frames_left = handler.as_ref().map_or(0, |handler| handler.frame + 1);
+
+        while self.frames.len() > frames_left {
+            let frame = self.frames.pop().expect("The loop checks there is one");
+            error = self.abandon(frame, error);
+        }
+
+        let Some(handler) = handler else {
+            self.truncate(0);
+            self.pending.clear();
+            return Err(error);
+        };
+
+        let mut frame = self
+            .frames
+            .pop()
+            .expect("A handler's frame is still running");
+        frame.ip = handler.target;
+        self.truncate(handler.stack_len);
+        self.pending.truncate(handler.pending_len);
+        self.pending.push(error);
+
+        Ok(frame)
+    }
+
+    /// Leaves `frame` because of `error`, which is traced through calls.
+    fn abandon(&mut self, frame: Frame, error: RutoxError) -> RutoxError {
+        match frame.kind {
+            FrameKind::Script => error,
+            FrameKind::Call(call_location) => {
+                self.call_depth -= 1;
+                error.traced(StackFrame {
+                    function_name: frame.closure.to_string(),
+                    call_location,
+                })
+            }
+            FrameKind::Module { previous_dir, .. } => {
+                self.modules.leave(previous_dir);
+                error
+            }
+        }
+    }
+
+    /// The open upvalue over `slot`, created if there is none yet.
+    fn capture(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        let open = self
+            .open_upvalues
+            .iter()
+            .find(|upvalue| matches!(*upvalue.borrow(), Upvalue::Open(s) if s == slot));
+        if let Some(upvalue) = open {
+            return upvalue.clone();
+        }
+
+        let upvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.push(upvalue.clone());
+
+        upvalue
+    }
+
+    /// Drops the stack from `len` up, first closing the upvalues over it.
+    fn truncate(&mut self, len: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|upvalue| {
+            let mut upvalue = upvalue.borrow_mut();
+            match *upvalue {
+                Upvalue::Open(slot) if slot >= len => {
+                    *upvalue = Upvalue::Closed(stack[slot].clone());
+                    false
+                }
+                _ => true,
+            }
+        });
+
+        self.stack.truncate(len);
+    }
+
+    fn undefined_variable(frame: &Frame, reference: &Reference) -> RutoxError {
+        let mut names = reference.locals.clone();
+        names.extend(frame.closure.globals.borrow().names());
+
+        Interpreter::undefined_variable(&reference.name, &frame.origin().location, &names)
+    }
+}
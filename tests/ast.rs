@@ -0,0 +1,95 @@
+//! Compares `rutox ast` on `tests/ast/every_node.lox`, which has every kind
+//! of statement and expression, with the snapshots next to it.
+
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn ast(format: &str) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_rutox"))
+        .args(["ast", &format!("--format={format}"), "every_node.lox"])
+        .current_dir(fixtures())
+        .output()
+        .expect("Could not run rutox");
+    assert!(output.status.success(), "{output:?}");
+
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+fn fixtures() -> &'static Path {
+    Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/ast"))
+}
+
+fn snapshot(name: &str) -> String {
+    fs::read_to_string(fixtures().join(name))
+        .unwrap_or_else(|err| panic!("Could not read tests/ast/{name}: {err}"))
+}
+
+/// The `type` of every node under `value`, checking each has a location.
+fn node_types(value: &Value, types: &mut BTreeSet<String>) {
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::String(node_type)) = object.get("type") {
+                assert!(
+                    object["location"]["start"]["line"].is_u64(),
+                    "{node_type} node without a location: {value}"
+                );
+                types.insert(node_type.clone());
+            }
+            object.values().for_each(|value| node_types(value, types));
+        }
+        Value::Array(values) => values.iter().for_each(|value| node_types(value, types)),
+        _ => {}
+    }
+}
+
+#[test]
+fn sexpr_matches_snapshot() {
+    assert_eq!(ast("sexpr"), snapshot("every_node.sexpr"));
+}
+
+#[test]
+fn json_matches_snapshot() {
+    let actual: Value = serde_json::from_str(&ast("json")).expect("`rutox ast` printed JSON");
+    let expected: Value =
+        serde_json::from_str(&snapshot("every_node.json")).expect("The snapshot is JSON");
+
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn json_has_every_node_with_its_location() {
+    let tree: Value = serde_json::from_str(&ast("json")).expect("`rutox ast` printed JSON");
+    let mut types = BTreeSet::new();
+    node_types(&tree, &mut types);
+
+    // Function declarations and lambdas are both `Function` nodes
+    let every_type = [
+        "Assign",
+        "Binary",
+        "Block",
+        "Break",
+        "Call",
+        "Continue",
+        "Expression",
+        "Function",
+        "Get",
+        "Grouping",
+        "If",
+        "Import",
+        "Literal",
+        "Logical",
+        "Print",
+        "Return",
+        "Test",
+        "Throw",
+        "Try",
+        "Unary",
+        "Var",
+        "Variable",
+        "While",
+    ];
+    assert_eq!(types, every_type.map(String::from).into());
+}
@@ -0,0 +1,58 @@
+//! Framing shared by the tests that talk to `rutox lsp` and
+//! `rutox debug --dap`, which both send JSON messages after a
+//! `Content-Length` header.
+
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+
+/// Runs rutox with `args`, sends it `messages`, then closes its stdin and
+/// returns what it replied and how it exited.
+pub fn session(args: &[&str], messages: &[Value]) -> (Vec<Value>, i32) {
+    let mut server = Command::new(env!("CARGO_BIN_EXE_rutox"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Could not run rutox");
+
+    let mut stdin = server.stdin.take().expect("stdin is piped");
+    for message in messages {
+        let content = message.to_string();
+        write!(stdin, "Content-Length: {}\r\n\r\n{content}", content.len())
+            .expect("Could not write to rutox");
+    }
+    drop(stdin);
+
+    let mut stdout = BufReader::new(server.stdout.take().expect("stdout is piped"));
+    let mut replies = vec![];
+    while let Some(reply) = read_message(&mut stdout) {
+        replies.push(reply);
+    }
+    let status = server.wait().expect("Could not wait for rutox");
+
+    (replies, status.code().unwrap_or(-1))
+}
+
+fn read_message(input: &mut impl BufRead) -> Option<Value> {
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header).ok()? == 0 {
+            return None;
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(length) = header.strip_prefix("Content-Length:") {
+            content_length = length.trim().parse::<usize>().ok();
+        }
+    }
+
+    let mut content = vec![0; content_length.expect("Missing Content-Length header")];
+    input.read_exact(&mut content).ok()?;
+
+    Some(serde_json::from_slice(&content).expect("rutox sent invalid JSON"))
+}
@@ -0,0 +1,267 @@
+//! Runs every `.lox` file under `tests/lox` and checks what it prints, the
+//! errors it reports and how it exits against the annotations in it. They
+//! follow those of the Crafting Interpreters test suite:
+//!
+//! - `// expect: <output>` is a line the script prints, in order.
+//! - `// expect runtime error: <message>` is the runtime error the script
+//!   stops with, on the line of the comment.
+//! - `// [line N] Error<...>: <message>` is a syntax error on line `N`, and
+//!   `// Error<...>: <message>` one on the line of the comment. Whatever is
+//!   between `Error` and the colon, e.g. ` at ';'`, is not checked.
+//!
+//! Files in `lib` directories are helpers that other scripts import, and are
+//! not run on their own. Every other file under `tests/lox` needs at least one
+//! annotation, so one can't pass by checking nothing.
+//!
+//! A trailing `.` is ignored when comparing messages, since the reference
+//! implementations end theirs with one. Every file is run on both backends,
+//! which must agree with the annotations alike.
+//!
+//! Set `RUTOX_CONFORMANCE_DIR` to run another directory instead, e.g. the
+//! `test` directory of the Crafting Interpreters repository. Failures are
+//! then only reported, to track how far rutox conforms:
+//!
+//! ```text
+//! RUTOX_CONFORMANCE_DIR=../craftinginterpreters/test cargo test --test conformance -- --nocapture
+//! ```
+
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const BACKENDS: [&str; 2] = ["tree", "vm"];
+
+/// What rutox exits with after a syntax or runtime error. Unlike the
+/// reference implementations, it uses the same code for both.
+const ERROR_EXIT_CODE: i32 = 74;
+
+#[derive(Debug, Default, PartialEq)]
+struct Outcome {
+    output: Vec<String>,
+    /// `(line, message)` of each syntax error, sorted.
+    syntax_errors: Vec<(usize, String)>,
+    runtime_error: Option<(usize, String)>,
+    exit_code: i32,
+}
+
+#[test]
+fn conformance() {
+    let custom_dir = env::var_os("RUTOX_CONFORMANCE_DIR").map(PathBuf::from);
+    let dir = custom_dir
+        .clone()
+        .unwrap_or_else(|| Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/lox"));
+
+    let mut files = vec![];
+    lox_files(&dir, &mut files);
+    assert!(!files.is_empty(), "No `.lox` files in {}", dir.display());
+
+    // Other suites may have scripts that do nothing on purpose
+    if custom_dir.is_none() {
+        let unannotated: Vec<String> = files
+            .iter()
+            .filter(|path| !has_annotations(path))
+            .map(|path| path.display().to_string())
+            .collect();
+        assert!(
+            unannotated.is_empty(),
+            "These conformance tests have no annotations, so they check nothing:\n\n{}",
+            unannotated.join("\n")
+        );
+    }
+
+    let failures: Vec<String> = BACKENDS
+        .iter()
+        .flat_map(|backend| files.iter().map(move |path| (path, backend)))
+        .filter_map(|(path, backend)| check(path, backend).err())
+        .collect();
+    let total = files.len() * BACKENDS.len();
+    let passed = total - failures.len();
+
+    if custom_dir.is_some() {
+        for failure in &failures {
+            println!("{failure}\n");
+        }
+        println!("{passed}/{total} conformance tests passed");
+    } else {
+        assert!(
+            failures.is_empty(),
+            "{} of {} conformance tests failed:\n\n{}",
+            failures.len(),
+            total,
+            failures.join("\n\n")
+        );
+    }
+}
+
+/// The `.lox` files under `dir`, sorted, skipping helpers in `lib`
+/// directories.
+fn lox_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap_or_else(|err| panic!("Could not read {}: {err}", dir.display()))
+        .map(|entry| entry.expect("Could not read directory entry").path())
+        .collect();
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() {
+            if entry.file_name().is_some_and(|name| name != "lib") {
+                lox_files(&entry, files);
+            }
+        } else if entry
+            .extension()
+            .is_some_and(|extension| extension == "lox")
+        {
+            files.push(entry);
+        }
+    }
+}
+
+fn has_annotations(path: &Path) -> bool {
+    let source = fs::read_to_string(path).unwrap_or_default();
+    let expected = expectations(&source);
+
+    !expected.output.is_empty()
+        || !expected.syntax_errors.is_empty()
+        || expected.runtime_error.is_some()
+}
+
+/// Runs the script at `path` on `backend`, describing how it went wrong if it
+/// did.
+fn check(path: &Path, backend: &str) -> Result<(), String> {
+    let source = fs::read_to_string(path)
+        .map_err(|err| format!("{}: could not be read: {err}", path.display()))?;
+    let expected = expectations(&source);
+    let actual = run(path, backend)?;
+
+    if actual == expected {
+        return Ok(());
+    }
+
+    let mut report = format!("{} (--backend={backend}):", path.display());
+    if actual.output != expected.output {
+        report.push_str(&format!(
+            "\n  expected output: {:?}\n  actual output:   {:?}",
+            expected.output, actual.output
+        ));
+    }
+    if actual.syntax_errors != expected.syntax_errors {
+        report.push_str(&format!(
+            "\n  expected syntax errors: {:?}\n  actual syntax errors:   {:?}",
+            expected.syntax_errors, actual.syntax_errors
+        ));
+    }
+    if actual.runtime_error != expected.runtime_error {
+        report.push_str(&format!(
+            "\n  expected runtime error: {:?}\n  actual runtime error:   {:?}",
+            expected.runtime_error, actual.runtime_error
+        ));
+    }
+    if actual.exit_code != expected.exit_code {
+        report.push_str(&format!(
+            "\n  expected exit code: {}\n  actual exit code:   {}",
+            expected.exit_code, actual.exit_code
+        ));
+    }
+
+    Err(report)
+}
+
+/// What the annotations in `source` say running it does.
+fn expectations(source: &str) -> Outcome {
+    let mut expected = Outcome::default();
+
+    for (i, line) in source.lines().enumerate() {
+        let line_number = i + 1;
+        let Some((_, comment)) = line.split_once("// ") else {
+            continue;
+        };
+
+        if let Some(output) = comment.strip_prefix("expect: ") {
+            expected.output.push(output.to_string());
+        } else if let Some(message) = comment.strip_prefix("expect runtime error: ") {
+            expected.runtime_error = Some((line_number, normalize(message)));
+        } else if let Some((line, message)) = syntax_error(comment) {
+            expected
+                .syntax_errors
+                .push((line.unwrap_or(line_number), message));
+        }
+    }
+
+    expected.syntax_errors.sort();
+    expected.exit_code = match expected.syntax_errors.is_empty() && expected.runtime_error.is_none()
+    {
+        true => 0,
+        false => ERROR_EXIT_CODE,
+    };
+
+    expected
+}
+
+/// Parses `[line N] Error...: message` or `Error...: message`, with the line
+/// if it is given. `[java line N]` is what jlox, the tree-walking reference
+/// implementation, reports when the two differ. `[c line N]` is skipped.
+fn syntax_error(comment: &str) -> Option<(Option<usize>, String)> {
+    let (line, rest) = match comment.strip_prefix('[') {
+        Some(rest) => {
+            let (location, rest) = rest.split_once("] ")?;
+            let line = location
+                .strip_prefix("line ")
+                .or_else(|| location.strip_prefix("java line "))?;
+
+            (Some(line.parse().ok()?), rest)
+        }
+        None => (None, comment),
+    };
+
+    let (_, message) = rest.strip_prefix("Error")?.split_once(": ")?;
+
+    Some((line, normalize(message)))
+}
+
+fn normalize(message: &str) -> String {
+    message.trim().trim_end_matches('.').to_string()
+}
+
+/// Runs the script at `path` with errors reported as JSON, so they can be
+/// told apart from what it prints.
+fn run(path: &Path, backend: &str) -> Result<Outcome, String> {
+    let output = Command::new(env!("CARGO_BIN_EXE_rutox"))
+        .args(["--error-format=json", "--color=never"])
+        .arg(format!("--backend={backend}"))
+        .arg(path)
+        .output()
+        .map_err(|err| format!("{}: could not run rutox: {err}", path.display()))?;
+
+    let mut outcome = Outcome {
+        output: String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect(),
+        exit_code: output.status.code().unwrap_or(-1),
+        ..Outcome::default()
+    };
+
+    for line in String::from_utf8_lossy(&output.stderr).lines() {
+        let error: Value = serde_json::from_str(line).map_err(|_| {
+            format!(
+                "{}: rutox wrote something other than an error to stderr: {line}",
+                path.display()
+            )
+        })?;
+        if error["severity"] != "error" {
+            continue;
+        }
+
+        let line = error["line"].as_u64().unwrap_or_default() as usize;
+        let message = normalize(error["message"].as_str().unwrap_or_default());
+        match error["kind"].as_str() {
+            Some("RuntimeError") => outcome.runtime_error = Some((line, message)),
+            _ => outcome.syntax_errors.push((line, message)),
+        }
+    }
+    outcome.syntax_errors.sort();
+
+    Ok(outcome)
+}
@@ -0,0 +1,40 @@
+//! Runs `rutox run --coverage` on `tests/coverage/script.lox` and compares
+//! the LCOV tracefile it writes with `tests/coverage/script.info`, since
+//! CI tools read the exact records.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn lcov_tracefile_matches_golden_file() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/coverage");
+    let tracefile = env::temp_dir().join(format!("rutox-coverage-{}.info", std::process::id()));
+
+    // Run from the fixtures so `SF` records hold relative paths
+    let output = Command::new(env!("CARGO_BIN_EXE_rutox"))
+        .args(["run", "--color=never"])
+        .arg(format!("--coverage={}", tracefile.display()))
+        .arg("script.lox")
+        .current_dir(&dir)
+        .output()
+        .expect("Could not run rutox");
+    let lcov = fs::read_to_string(&tracefile);
+    let _ = fs::remove_file(&tracefile);
+
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "positive\nother\n9\n"
+    );
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .starts_with("Lines: 13/16 (81.2%), branches: 3/8 (37.5%), functions: 2/4 (50.0%)\n"));
+
+    let expected = fs::read_to_string(dir.join("script.info")).expect("Could not read golden file");
+    assert_eq!(
+        lcov.expect("No tracefile was written"),
+        expected,
+        "the tracefile differs from tests/coverage/script.info"
+    );
+}
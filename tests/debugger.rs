@@ -0,0 +1,202 @@
+//! Drives `rutox debug` over the fixtures in `tests/debugger`, with scripted
+//! commands on stdin or as a Debug Adapter Protocol client, and checks where
+//! it pauses.
+
+mod common;
+
+use serde_json::{json, Value};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+const LOOP: &str = "tests/debugger/loop.lox";
+
+/// Debugs `script` with `commands`, one per line, and returns its output.
+fn debug(script: &str, commands: &[&str]) -> String {
+    let mut debugger = Command::new(env!("CARGO_BIN_EXE_rutox"))
+        .args(["debug", script])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Could not run rutox debug");
+
+    let mut stdin = debugger.stdin.take().expect("stdin is piped");
+    for command in commands {
+        writeln!(stdin, "{command}").expect("Could not write to the debugger");
+    }
+    drop(stdin);
+
+    let output = debugger
+        .wait_with_output()
+        .expect("Could not wait for the debugger");
+    assert!(output.status.success(), "{output:?}");
+
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+/// The lines the debugger paused on, in order.
+fn pauses(output: &str, script: &str) -> Vec<usize> {
+    let prefix = format!("{}:", Path::new(script).display());
+
+    output
+        .split("(rutox) ")
+        .flat_map(str::lines)
+        .filter_map(|line| line.strip_prefix(&prefix)?.parse().ok())
+        .collect()
+}
+
+#[test]
+fn breakpoint_in_loop_pauses_every_iteration() {
+    let output = debug(LOOP, &["b 3", "c", "p i", "c", "p i", "c", "p i", "c"]);
+
+    assert_eq!(pauses(&output, LOOP), [1, 3, 3, 3]);
+    let printed: Vec<&str> = output
+        .split("(rutox) ")
+        .filter_map(|reply| reply.lines().next())
+        .filter(|line| line.parse::<f64>().is_ok())
+        .collect();
+    assert_eq!(printed, ["0", "1", "2", "3"]);
+}
+
+#[test]
+fn stepping_from_loop_body_runs_each_iteration() {
+    let output = debug(LOOP, &["b 3", "c", "s", "s", "s", "s"]);
+
+    assert_eq!(pauses(&output, LOOP), [1, 3, 3, 3, 5]);
+    assert!(output.ends_with("3\n"), "{output}");
+}
+
+#[test]
+fn statements_on_one_line_pause_once() {
+    let output = debug("tests/debugger/one_line.lox", &["s", "s", "s", "s"]);
+
+    // The loop's second iteration runs the same statement on line 1 again
+    assert_eq!(pauses(&output, "tests/debugger/one_line.lox"), [1, 1, 2]);
+}
+
+fn request(seq: u64, command: &str, arguments: Value) -> Value {
+    json!({ "seq": seq, "type": "request", "command": command, "arguments": arguments })
+}
+
+fn response(replies: &[Value], seq: u64) -> &Value {
+    replies
+        .iter()
+        .find(|reply| reply["type"] == "response" && reply["request_seq"] == seq)
+        .unwrap_or_else(|| panic!("No response to request {seq} in {replies:#?}"))
+}
+
+fn events<'a>(replies: &'a [Value], event: &'a str) -> impl Iterator<Item = &'a Value> {
+    replies
+        .iter()
+        .filter(move |reply| reply["type"] == "event" && reply["event"] == event)
+}
+
+#[test]
+fn dap_session_pauses_at_breakpoints_and_inspects_frames() {
+    let program = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/debugger/calls.lox");
+    let program = program.to_str().expect("The path is UTF-8");
+
+    let (replies, exit_code) = common::session(
+        &["debug", "--dap"],
+        &[
+            request(1, "initialize", json!({ "adapterID": "rutox" })),
+            request(2, "launch", json!({ "program": program })),
+            request(
+                3,
+                "setBreakpoints",
+                json!({ "source": { "path": program }, "breakpoints": [{ "line": 3 }] }),
+            ),
+            request(4, "configurationDone", json!({})),
+            request(5, "threads", json!({})),
+            request(6, "stackTrace", json!({ "threadId": 1 })),
+            request(7, "scopes", json!({ "frameId": 0 })),
+            request(8, "variables", json!({ "variablesReference": 1 })),
+            request(9, "variables", json!({ "variablesReference": 2 })),
+            request(
+                10,
+                "evaluate",
+                json!({ "expression": "sum * 10", "frameId": 0 }),
+            ),
+            request(
+                11,
+                "evaluate",
+                json!({ "expression": "nope", "frameId": 0 }),
+            ),
+            request(12, "next", json!({ "threadId": 1 })),
+            request(13, "continue", json!({ "threadId": 1 })),
+            request(14, "disconnect", json!({})),
+        ],
+    );
+
+    assert_eq!(exit_code, 0);
+    assert!(
+        replies
+            .iter()
+            .filter(|reply| reply["type"] == "response")
+            .all(|reply| reply["success"] == true || reply["request_seq"] == 11),
+        "{replies:#?}"
+    );
+    assert_eq!(events(&replies, "initialized").count(), 1);
+
+    let stops: Vec<&Value> = events(&replies, "stopped")
+        .map(|stopped| &stopped["body"]["reason"])
+        .collect();
+    assert_eq!(stops, ["breakpoint", "step"]);
+
+    assert_eq!(
+        response(&replies, 5)["body"]["threads"],
+        json!([{ "id": 1, "name": "main" }])
+    );
+
+    let frames = response(&replies, 6)["body"]["stackFrames"]
+        .as_array()
+        .expect("stackFrames is an array");
+    let frames: Vec<(&str, u64)> = frames
+        .iter()
+        .map(|frame| {
+            (
+                frame["name"].as_str().unwrap_or_default(),
+                frame["line"].as_u64().unwrap_or_default(),
+            )
+        })
+        .collect();
+    assert_eq!(frames, [("<fn add>", 3), ("<script>", 5)]);
+
+    let variables = |seq| -> Vec<(String, String)> {
+        response(&replies, seq)["body"]["variables"]
+            .as_array()
+            .expect("variables is an array")
+            .iter()
+            .map(|variable| {
+                (
+                    variable["name"].as_str().unwrap_or_default().to_string(),
+                    variable["value"].as_str().unwrap_or_default().to_string(),
+                )
+            })
+            .collect()
+    };
+    let locals = variables(8);
+    for local in [("sum", "3"), ("a", "1"), ("b", "2")] {
+        assert!(
+            locals.contains(&(local.0.to_string(), local.1.to_string())),
+            "{locals:?}"
+        );
+    }
+    assert_eq!(variables(9), [("add".to_string(), "<fn add>".to_string())]);
+
+    assert_eq!(response(&replies, 10)["body"]["result"], "30");
+    assert_eq!(response(&replies, 11)["success"], false);
+
+    let output: Vec<&Value> = events(&replies, "output")
+        .map(|output| &output["body"]["output"])
+        .collect();
+    assert_eq!(output, ["3\n"]);
+    assert_eq!(
+        events(&replies, "exited")
+            .next()
+            .map(|exited| &exited["body"]["exitCode"]),
+        Some(&json!(0))
+    );
+    assert_eq!(events(&replies, "terminated").count(), 1);
+}
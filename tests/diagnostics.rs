@@ -0,0 +1,160 @@
+//! Checks the diagnostics rutox reports beyond plain errors: the resolver's
+//! warnings, syntax errors recovered from inside blocks and did-you-mean
+//! help, reported as JSON for the fixtures in `tests/diagnostics`.
+
+use serde_json::{json, Value};
+use std::process::Command;
+
+struct Run {
+    /// `(code, line, severity)` of each diagnostic.
+    diagnostics: Vec<(String, u64, String)>,
+    records: Vec<Value>,
+    stdout: String,
+    exit_code: i32,
+}
+
+fn rutox(args: &[&str]) -> Run {
+    let output = Command::new(env!("CARGO_BIN_EXE_rutox"))
+        .args(["--error-format=json", "--color=never"])
+        .args(args)
+        .current_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/diagnostics"))
+        .output()
+        .expect("Could not run rutox");
+
+    let records: Vec<Value> = String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("Each line is a JSON record"))
+        .collect();
+    let diagnostics = records
+        .iter()
+        .map(|record| {
+            (
+                record["code"].as_str().unwrap_or_default().to_string(),
+                record["line"].as_u64().unwrap_or_default(),
+                record["severity"].as_str().unwrap_or_default().to_string(),
+            )
+        })
+        .collect();
+
+    Run {
+        diagnostics,
+        records,
+        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+        exit_code: output.status.code().unwrap_or(-1),
+    }
+}
+
+fn diagnostic(code: &str, line: u64, severity: &str) -> (String, u64, String) {
+    (code.to_string(), line, severity.to_string())
+}
+
+#[test]
+fn resolver_warns_but_runs_the_script() {
+    let run = rutox(&["warnings.lox"]);
+
+    assert_eq!(
+        run.diagnostics,
+        [
+            diagnostic("W0001", 2, "warning"),
+            diagnostic("W0003", 4, "warning"),
+            diagnostic("W0002", 9, "warning"),
+            diagnostic("W0004", 14, "warning"),
+            diagnostic("W0005", 15, "warning"),
+        ]
+    );
+    let messages: Vec<&str> = run
+        .records
+        .iter()
+        .map(|record| record["message"].as_str().unwrap_or_default())
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "Unused variable `unused`",
+            "Unreachable code",
+            "`total` shadows a variable from an outer scope",
+            "Comparison with `nil` using `==`",
+            "This condition is always true",
+        ]
+    );
+    assert_eq!(run.stdout, "2\nhi\n");
+    assert_eq!(run.exit_code, 0);
+}
+
+#[test]
+fn warnings_as_errors_stop_the_script() {
+    let run = rutox(&["-W", "error", "warnings.lox"]);
+
+    assert_eq!(run.diagnostics.len(), 5);
+    assert!(run
+        .diagnostics
+        .iter()
+        .all(|(_, _, severity)| severity == "error"));
+    assert_eq!(run.stdout, "");
+    assert_eq!(run.exit_code, 74);
+}
+
+#[test]
+fn syntax_errors_inside_a_block_are_all_reported() {
+    let run = rutox(&["unclosed_block.lox"]);
+
+    assert_eq!(
+        run.diagnostics,
+        [
+            diagnostic("E0102", 2, "error"),
+            diagnostic("E0100", 3, "error"),
+            diagnostic("E0101", 8, "error"),
+            diagnostic("E0101", 8, "error"),
+        ]
+    );
+    assert_eq!(run.stdout, "");
+    assert_eq!(run.exit_code, 74);
+}
+
+#[test]
+fn unclosed_block_points_at_its_opening_brace() {
+    let run = rutox(&["unclosed_block.lox"]);
+    let unclosed = run.records.last().expect("The unclosed block is reported");
+
+    assert_eq!(unclosed["message"], "Expect `}` after block");
+    assert_eq!(unclosed["labels"].as_array().map(Vec::len), Some(1));
+    assert_eq!(unclosed["labels"][0]["message"], "this `{` is never closed");
+    assert_eq!(unclosed["labels"][0]["span"]["start"]["line"], 1);
+    assert_eq!(unclosed["labels"][0]["span"]["start"]["column"], 14);
+}
+
+#[test]
+fn undefined_variable_suggests_a_similar_name() {
+    for backend in ["--backend=tree", "--backend=vm"] {
+        let run = rutox(&[backend, "undefined.lox"]);
+
+        assert_eq!(run.diagnostics, [diagnostic("E0200", 2, "error")]);
+        assert_eq!(run.records[0]["message"], "Undefined variable `totl`");
+        assert_eq!(run.records[0]["help"], json!(["did you mean `total`?"]));
+    }
+}
+
+#[test]
+fn undefined_variable_suggests_a_local_name() {
+    let run = rutox(&["-e", "fun f(count) { print cont; } f(1);"]);
+
+    assert_eq!(run.records[0]["help"], json!(["did you mean `count`?"]));
+}
+
+#[test]
+fn undefined_variable_without_a_similar_name_has_no_help() {
+    let run = rutox(&["-e", "var total = 1; print zzzzzz;"]);
+
+    assert_eq!(run.records[0]["help"], json!([]));
+}
+
+#[test]
+fn misspelled_keyword_suggests_the_keyword() {
+    let run = rutox(&["retrun.lox"]);
+
+    assert_eq!(run.diagnostics, [diagnostic("E0101", 2, "error")]);
+    assert_eq!(
+        run.records[0]["help"],
+        json!(["did you mean the keyword `return`?"])
+    );
+}
@@ -0,0 +1,180 @@
+//! Checks the machine-readable error formats: JSON records, one per line,
+//! and a SARIF 2.1.0 log, for the fixtures in `tests/error_format`.
+
+use serde_json::{json, Value};
+use std::process::Command;
+
+/// Runs the fixture `name` with `--error-format=<format>`, returning what
+/// rutox wrote to stderr.
+fn errors(name: &str, format: &str) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_rutox"))
+        .args([&format!("--error-format={format}"), "--color=never"])
+        .arg(format!("tests/error_format/{name}"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("Could not run rutox");
+    assert_eq!(output.status.code(), Some(74));
+
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
+fn json_records(name: &str) -> Vec<Value> {
+    errors(name, "json")
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("Each line is a JSON record"))
+        .collect()
+}
+
+fn sarif_log(name: &str) -> Value {
+    serde_json::from_str(&errors(name, "sarif")).expect("The SARIF log is JSON")
+}
+
+fn span(line: u64, start: u64, end: u64) -> Value {
+    json!({ "start": { "line": line, "column": start }, "end": { "line": line, "column": end } })
+}
+
+fn region(line: u64, start: u64, end: u64) -> Value {
+    json!({ "startLine": line, "startColumn": start, "endLine": line, "endColumn": end })
+}
+
+#[test]
+fn json_runtime_error_with_labels() {
+    let records = json_records("runtime.lox");
+
+    assert_eq!(
+        records,
+        [json!({
+            "kind": "RuntimeError",
+            "severity": "error",
+            "code": "E0201",
+            "message": "Cannot add string \"a\" and number 1",
+            "file": "tests/error_format/runtime.lox",
+            "line": 2,
+            "column": 10,
+            "span": span(2, 10, 14),
+            "labels": [
+                {
+                    "message": "left operand is a string here",
+                    "file": "tests/error_format/runtime.lox",
+                    "span": span(2, 10, 10),
+                },
+                {
+                    "message": "right operand is a number here",
+                    "file": "tests/error_format/runtime.lox",
+                    "span": span(2, 14, 14),
+                },
+            ],
+            "help": [],
+            "traceback": [{
+                "function": "<fn f>",
+                "file": "tests/error_format/runtime.lox",
+                "line": 4,
+                "column": 8,
+            }],
+        })]
+    );
+}
+
+#[test]
+fn json_multiple_syntax_errors_are_one_record_each() {
+    let records = json_records("syntax.lox");
+    let summary: Vec<(&str, u64, u64, &str)> = records
+        .iter()
+        .map(|record| {
+            (
+                record["code"].as_str().unwrap_or_default(),
+                record["line"].as_u64().unwrap_or_default(),
+                record["column"].as_u64().unwrap_or_default(),
+                record["kind"].as_str().unwrap_or_default(),
+            )
+        })
+        .collect();
+
+    assert_eq!(
+        summary,
+        [
+            ("E0102", 1, 5, "SyntaxError"),
+            ("E0100", 2, 7, "SyntaxError"),
+            ("E0101", 3, 8, "SyntaxError"),
+        ]
+    );
+    assert_eq!(records[1]["message"], "Expect expression, got `;`");
+    assert_eq!(records[1]["span"], span(2, 7, 7));
+    assert_eq!(
+        records[2]["help"],
+        json!(["did you mean the keyword `return`?"])
+    );
+}
+
+#[test]
+fn sarif_log_describes_the_run() {
+    let log = sarif_log("runtime.lox");
+
+    assert_eq!(log["version"], "2.1.0");
+    assert_eq!(
+        log["$schema"],
+        "https://json.schemastore.org/sarif-2.1.0.json"
+    );
+    assert_eq!(log["runs"].as_array().map(Vec::len), Some(1));
+
+    let driver = &log["runs"][0]["tool"]["driver"];
+    assert_eq!(driver["name"], "rutox");
+    let rule = driver["rules"]
+        .as_array()
+        .and_then(|rules| rules.iter().find(|rule| rule["id"] == "E0201"))
+        .expect("Every error code is a rule");
+    assert_eq!(rule["shortDescription"]["text"], "Invalid operand types");
+}
+
+#[test]
+fn sarif_runtime_error_with_related_locations() {
+    let log = sarif_log("runtime.lox");
+    let uri = json!({ "uri": "tests/error_format/runtime.lox" });
+
+    assert_eq!(
+        log["runs"][0]["results"],
+        json!([{
+            "ruleId": "E0201",
+            "level": "error",
+            "message": { "text": "Cannot add string \"a\" and number 1" },
+            // End columns are exclusive in SARIF
+            "locations": [{
+                "physicalLocation": { "artifactLocation": uri, "region": region(2, 10, 15) },
+            }],
+            "relatedLocations": [
+                {
+                    "message": { "text": "left operand is a string here" },
+                    "physicalLocation": { "artifactLocation": uri, "region": region(2, 10, 11) },
+                },
+                {
+                    "message": { "text": "right operand is a number here" },
+                    "physicalLocation": { "artifactLocation": uri, "region": region(2, 14, 15) },
+                },
+            ],
+        }])
+    );
+}
+
+#[test]
+fn sarif_multiple_syntax_errors_are_one_result_each() {
+    let log = sarif_log("syntax.lox");
+    let results = log["runs"][0]["results"]
+        .as_array()
+        .expect("results are an array");
+
+    let rules: Vec<&str> = results
+        .iter()
+        .map(|result| result["ruleId"].as_str().unwrap_or_default())
+        .collect();
+    assert_eq!(rules, ["E0102", "E0100", "E0101"]);
+    assert_eq!(
+        results[1]["locations"][0]["physicalLocation"]["region"],
+        region(2, 7, 8)
+    );
+    assert_eq!(results[1]["relatedLocations"], json!([]));
+    // SARIF has no field for help, so it follows the message
+    assert_eq!(
+        results[2]["message"]["text"],
+        "Expect `;` after expression\nhelp: did you mean the keyword `return`?"
+    );
+}
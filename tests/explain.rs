@@ -0,0 +1,52 @@
+//! Checks `rutox explain <code>` for known and unknown codes.
+
+use rutox::error_code::ErrorCode;
+use std::process::{Command, Output};
+
+fn explain(code: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rutox"))
+        .args(["explain", code])
+        .output()
+        .expect("Could not run rutox")
+}
+
+#[test]
+fn known_code_is_explained() {
+    let output = explain("E0201");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    assert!(
+        stdout.starts_with("E0201: Invalid operand types\n\nAn operator was applied"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("Erroneous code example:\n\n    print \"10\" - 1;"));
+}
+
+#[test]
+fn codes_are_case_insensitive() {
+    let output = explain("w0104");
+
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("W0104: Empty block\n"));
+}
+
+#[test]
+fn unknown_code_is_a_usage_error() {
+    let output = explain("E9999");
+
+    assert_eq!(output.status.code(), Some(64));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "Unknown error code `E9999`\n"
+    );
+}
+
+#[test]
+fn every_code_has_an_explanation() {
+    for code in ErrorCode::ALL {
+        assert_eq!(ErrorCode::from_code(code.code()), Some(code));
+        assert!(!code.title().is_empty(), "{code} has no title");
+        assert!(!code.explanation().is_empty(), "{code} has no explanation");
+    }
+}
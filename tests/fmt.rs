@@ -0,0 +1,68 @@
+//! Checks that `rutox fmt` keeps comments in the scope they were written
+//! in, and that formatting its own output changes nothing.
+
+use rutox::formatter;
+use rutox::scanner::SourceMap;
+
+fn format(source: &str) -> String {
+    let file = SourceMap::new().add("test.lox", source);
+
+    formatter::format(source, file).unwrap_or_else(|_| panic!("Could not format {source:?}"))
+}
+
+/// Formats `source`, expecting `expected`, and checks that formatting that
+/// again keeps it as is.
+fn assert_formats(source: &str, expected: &str) {
+    let formatted = format(source);
+    assert_eq!(formatted, expected, "formatting {source:?}");
+    assert_eq!(
+        format(&formatted),
+        formatted,
+        "formatting {formatted:?} again"
+    );
+}
+
+#[test]
+fn comment_at_end_of_block_stays_inside() {
+    assert_formats(
+        "{\n  print 1;\n  // end\n}\n",
+        "{\n  print 1;\n  // end\n}\n",
+    );
+}
+
+#[test]
+fn comment_in_empty_block_stays_inside() {
+    assert_formats("{\n  // only\n}\n", "{\n  // only\n}\n");
+}
+
+#[test]
+fn comments_in_nested_blocks_stay_in_their_scope() {
+    assert_formats(
+        "// before\n{\n{ print 2; // trailing\n}\n// tail\n}\nprint 3;\n",
+        "// before\n{\n  {\n    print 2; // trailing\n  }\n  // tail\n}\nprint 3;\n",
+    );
+}
+
+#[test]
+fn comments_in_function_bodies_stay_inside() {
+    assert_formats(
+        "fun f() {\n  // first\n  return 1;\n  // last\n}\n",
+        "fun f() {\n  // first\n  return 1;\n  // last\n}\n",
+    );
+}
+
+#[test]
+fn comments_between_statements_keep_their_blank_lines() {
+    assert_formats(
+        "var a=1;\n\n// about b\nvar b =2; // trailing\nprint a+b;\n",
+        "var a = 1;\n\n// about b\nvar b = 2; // trailing\nprint a + b;\n",
+    );
+}
+
+#[test]
+fn comments_in_loop_and_branch_bodies_stay_inside() {
+    assert_formats(
+        "while (true) {\n  // body\n  break;\n}\nif (true) {\n  print 1;\n  // then\n} else {\n  // else\n}\n",
+        "while (true) {\n  // body\n  break;\n}\nif (true) {\n  print 1;\n  // then\n} else {\n  // else\n}\n",
+    );
+}
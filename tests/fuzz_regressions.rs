@@ -0,0 +1,160 @@
+//! Inputs that made rutox panic or overflow its stack, most found by the fuzz
+//! targets in `fuzz/`, which must now be reported as errors or run normally.
+//! The fuzz targets keep programs shallow, so deep ones are covered here.
+
+use rutox::error_code::ErrorCode;
+use rutox::parser::{ast::BinaryOp, Parser};
+use rutox::scanner::{
+    token::{Token, TokenKind},
+    FileId, Scanner, SrcLocation,
+};
+use std::process::{Command, Output};
+
+const BACKENDS: [&str; 2] = ["tree", "vm"];
+
+fn rutox(backend: &str, code: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rutox"))
+        .args([&format!("--backend={backend}"), "--color=never", "-e", code])
+        .output()
+        .expect("Could not run rutox")
+}
+
+/// `print 1 <operator> 1 ... <operator> 1;` with `terms` terms.
+fn chain(operator: &str, terms: usize) -> String {
+    let chain = vec!["1"; terms].join(&format!(" {operator} "));
+
+    format!("print {chain};")
+}
+
+#[test]
+fn long_operator_chains_are_too_deeply_nested() {
+    for operator in ["+", "*", "==", "<", "and", "or"] {
+        for backend in BACKENDS {
+            let output = rutox(backend, &chain(operator, 5000));
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let stdout = String::from_utf8_lossy(&output.stdout);
+
+            assert_eq!(
+                output.status.code(),
+                Some(74),
+                "`{operator}` chain on {backend}: {stdout}{stderr}"
+            );
+            assert!(
+                stdout.contains("SyntaxError[E0108]"),
+                "`{operator}` chain on {backend}: {stdout}{stderr}"
+            );
+        }
+    }
+}
+
+#[test]
+fn long_call_and_property_chains_are_too_deeply_nested() {
+    let calls = format!("fun f() {{ return f; }} f{};", "()".repeat(5000));
+    let properties = format!("var a; a{};", ".b".repeat(5000));
+
+    for code in [calls, properties] {
+        for backend in BACKENDS {
+            let output = rutox(backend, &code);
+
+            assert_eq!(output.status.code(), Some(74));
+            assert!(String::from_utf8_lossy(&output.stdout).contains("SyntaxError[E0108]"));
+        }
+    }
+}
+
+#[test]
+fn shorter_operator_chains_run() {
+    for backend in BACKENDS {
+        let output = rutox(backend, &chain("+", 150));
+
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "150\n");
+    }
+}
+
+#[test]
+fn deep_recursion_through_deeply_nested_code_runs() {
+    // Close to the deepest nesting and calls allowed, so each call takes as
+    // much of the interpreter's stack as it can
+    let blocks = format!(
+        "fun f(n) {{ {} if (n > 0) f(n - 1); {} }} f(250); print \"done\";",
+        "{".repeat(190),
+        "}".repeat(190)
+    );
+    let negations = format!(
+        "fun f(n) {{ if (n > 0) return {}f(n - 1); return 0; }} f(250); print \"done\";",
+        "-".repeat(190)
+    );
+
+    for code in [blocks, negations] {
+        for backend in BACKENDS {
+            let output = rutox(backend, &code);
+
+            assert!(output.status.success(), "{backend}: {output:?}");
+            assert_eq!(String::from_utf8_lossy(&output.stdout), "done\n");
+        }
+    }
+}
+
+#[test]
+fn non_ascii_strings_are_scanned_by_character() {
+    let tokens = Scanner::new("\"héllo 😀\" + x".to_string(), FileId::default())
+        .scan_tokens()
+        .unwrap_or_else(|error| panic!("Could not scan: {error}"));
+
+    assert!(tokens[0].kind == TokenKind::String("héllo 😀".to_string()));
+    // Columns count characters, not bytes
+    assert_eq!(tokens[1].location.col, 11);
+    assert_eq!(tokens[2].location.col, 13);
+
+    for backend in BACKENDS {
+        let output = rutox(backend, "print \"héllo \" + \"😀\";");
+
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "héllo 😀\n");
+    }
+}
+
+#[test]
+fn unterminated_non_ascii_string_is_an_error() {
+    let Err(error) = Scanner::new("\"ü".to_string(), FileId::default()).scan_tokens() else {
+        panic!("The string is never closed");
+    };
+
+    assert_eq!(error.code(), ErrorCode::UnterminatedString);
+}
+
+#[test]
+fn binary_op_from_other_tokens_is_an_error() {
+    let token = Token {
+        kind: TokenKind::Bang,
+        lexeme: "!".to_string(),
+        location: SrcLocation::new(1, 1, FileId::default()),
+    };
+
+    assert!(BinaryOp::try_from(token).is_err());
+}
+
+#[test]
+fn empty_token_stream_parses_to_nothing() {
+    let stmts = Parser::new(vec![])
+        .parse()
+        .unwrap_or_else(|error| panic!("Could not parse: {error}"));
+
+    assert!(stmts.is_empty());
+}
+
+#[test]
+fn token_stream_without_eof_is_parsed() {
+    let tokens = Scanner::new("print 1".to_string(), FileId::default())
+        .scan_tokens()
+        .unwrap_or_else(|error| panic!("Could not scan: {error}"));
+    let tokens = tokens
+        .into_iter()
+        .filter(|token| token.kind != TokenKind::Eof)
+        .collect();
+
+    let Err(error) = Parser::new(tokens).parse() else {
+        panic!("`print 1` is missing its `;`");
+    };
+    assert_eq!(error.code(), ErrorCode::MissingToken);
+}
@@ -0,0 +1,176 @@
+//! Runs `rutox lint` over the fixtures in `tests/lint` and checks the codes,
+//! lines and severities it reports, and how it exits.
+
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// What rutox exits with when a lint is configured as an error.
+const ERROR_EXIT_CODE: i32 = 74;
+
+/// `(code, line, severity)` of a reported lint.
+type Lint = (String, u64, String);
+
+fn fixtures() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/lint")
+}
+
+/// Lints `file` from `dir`, where a `.rutoxlint.json` is picked up.
+fn lint(dir: &Path, file: &str, args: &[&str]) -> (Vec<Lint>, i32) {
+    let (reports, exit_code) = reports(dir, file, args);
+    let lints = reports
+        .iter()
+        .map(|lint| {
+            (
+                lint["code"].as_str().unwrap_or_default().to_string(),
+                lint["line"].as_u64().unwrap_or_default(),
+                lint["severity"].as_str().unwrap_or_default().to_string(),
+            )
+        })
+        .collect();
+
+    (lints, exit_code)
+}
+
+/// The lints reported for `file` as JSON, and how rutox exited.
+fn reports(dir: &Path, file: &str, args: &[&str]) -> (Vec<Value>, i32) {
+    let output = Command::new(env!("CARGO_BIN_EXE_rutox"))
+        .args(["lint", "--error-format=json"])
+        .args(args)
+        .arg(file)
+        .current_dir(dir)
+        .output()
+        .expect("Could not run rutox");
+
+    let reports = String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .map(|line| {
+            serde_json::from_str(line)
+                .unwrap_or_else(|_| panic!("rutox wrote something other than JSON: {line}"))
+        })
+        .collect();
+
+    (reports, output.status.code().unwrap_or(-1))
+}
+
+fn messages(reports: &[Value]) -> Vec<&str> {
+    reports
+        .iter()
+        .map(|report| report["message"].as_str().unwrap_or_default())
+        .collect()
+}
+
+fn warning(code: &str, line: u64) -> Lint {
+    (code.to_string(), line, "warning".to_string())
+}
+
+fn error(code: &str, line: u64) -> Lint {
+    (code.to_string(), line, "error".to_string())
+}
+
+#[test]
+fn every_rule_is_reported() {
+    let (lints, exit_code) = lint(&fixtures(), "rules.lox", &[]);
+
+    assert_eq!(
+        lints,
+        vec![
+            warning("W0101", 2),
+            warning("W0102", 3),
+            warning("W0103", 4),
+            warning("W0104", 5),
+            warning("W0105", 6),
+            warning("W0106", 7),
+            warning("W0106", 8),
+        ]
+    );
+    assert_eq!(exit_code, 0);
+}
+
+#[test]
+fn rutox_ignore_silences_its_line_or_the_next() {
+    let (lints, exit_code) = lint(&fixtures(), "ignore.lox", &[]);
+
+    assert_eq!(
+        lints,
+        vec![
+            warning("W0102", 5),
+            warning("W0104", 9),
+            warning("W0101", 10),
+            warning("W0107", 10),
+            warning("W0107", 11),
+        ]
+    );
+    assert_eq!(exit_code, 0);
+}
+
+#[test]
+fn misspelled_rules_in_rutox_ignore_are_reported() {
+    let (reports, _) = reports(&fixtures(), "ignore.lox", &[]);
+    let unknown: Vec<&Value> = reports
+        .iter()
+        .filter(|report| report["code"] == "W0107")
+        .collect();
+
+    assert_eq!(
+        messages(&reports[3..]),
+        [
+            "Unknown rule `self-asignment` in `rutox-ignore`",
+            "Unknown rule `self-asignment` in `rutox-ignore`",
+        ]
+    );
+    assert_eq!(unknown[0]["help"][0], "did you mean `self-assignment`?");
+}
+
+#[test]
+fn empty_bodies_are_reported() {
+    let (reports, exit_code) = reports(&fixtures(), "empty_bodies.lox", &[]);
+    let lines: Vec<u64> = reports
+        .iter()
+        .map(|report| report["line"].as_u64().unwrap_or_default())
+        .collect();
+
+    assert!(reports.iter().all(|report| report["code"] == "W0104"));
+    assert_eq!(lines, [1, 1, 2, 3, 4, 5, 6]);
+    assert_eq!(
+        messages(&reports),
+        [
+            "Empty `try` block",
+            "Empty `catch` block",
+            "Empty `finally` block",
+            "Empty function body",
+            "Empty function body",
+            "Empty test body",
+            "Empty block",
+        ]
+    );
+    assert_eq!(exit_code, 0);
+}
+
+#[test]
+fn config_in_current_directory_toggles_rules() {
+    let (lints, exit_code) = lint(&fixtures().join("config"), "config.lox", &[]);
+
+    assert_eq!(lints, vec![error("W0102", 3), warning("W0101", 4)]);
+    assert_eq!(exit_code, ERROR_EXIT_CODE);
+}
+
+#[test]
+fn config_flag_toggles_rules() {
+    let config = format!(
+        "--config={}",
+        fixtures().join("config/.rutoxlint.json").display()
+    );
+    let (lints, exit_code) = lint(&fixtures(), "config/config.lox", &[&config]);
+
+    assert_eq!(lints, vec![error("W0102", 3), warning("W0101", 4)]);
+    assert_eq!(exit_code, ERROR_EXIT_CODE);
+}
+
+#[test]
+fn warnings_as_errors_fail_the_run() {
+    let (lints, exit_code) = lint(&fixtures(), "rules.lox", &["-W", "error"]);
+
+    assert!(lints.iter().all(|(_, _, severity)| severity == "error"));
+    assert_eq!(exit_code, ERROR_EXIT_CODE);
+}
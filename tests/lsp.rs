@@ -0,0 +1,163 @@
+//! Talks to `rutox lsp` the way an editor would: framed JSON-RPC messages on
+//! its stdin, with the replies read back from its stdout.
+
+mod common;
+
+use serde_json::{json, Value};
+
+const URI: &str = "file:///test.lox";
+
+/// `count` is declared after an emoji, which is two UTF-16 code units but a
+/// single character.
+const DOCUMENT: &str = "var s = \"😀\"; var count = 1;\nprint count+count;\ncount = count;\n";
+
+/// Sends `messages` to a server, returning what it replied and how it
+/// exited.
+fn session(messages: &[Value]) -> (Vec<Value>, i32) {
+    common::session(&["lsp"], messages)
+}
+
+fn request(id: u64, method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+fn at(line: u64, character: u64) -> Value {
+    json!({ "textDocument": { "uri": URI }, "position": { "line": line, "character": character } })
+}
+
+/// A whole editing session, with positions in `encodings` if given.
+fn messages(encodings: Option<&[&str]>, declaration_character: u64) -> Vec<Value> {
+    let capabilities = match encodings {
+        Some(encodings) => json!({ "general": { "positionEncodings": encodings } }),
+        None => json!({}),
+    };
+
+    vec![
+        request(1, "initialize", json!({ "capabilities": capabilities })),
+        notification("initialized", json!({})),
+        notification(
+            "textDocument/didOpen",
+            json!({ "textDocument": { "uri": URI, "languageId": "lox", "version": 1, "text": DOCUMENT } }),
+        ),
+        request(2, "textDocument/definition", at(1, 7)),
+        request(3, "textDocument/references", {
+            let mut params = at(0, declaration_character);
+            params["context"] = json!({ "includeDeclaration": true });
+            params
+        }),
+        request(4, "textDocument/hover", at(0, declaration_character)),
+        request(
+            5,
+            "textDocument/formatting",
+            json!({ "textDocument": { "uri": URI }, "options": { "tabSize": 2, "insertSpaces": true } }),
+        ),
+        request(6, "shutdown", Value::Null),
+        notification("exit", Value::Null),
+    ]
+}
+
+fn response(replies: &[Value], id: u64) -> &Value {
+    let reply = replies
+        .iter()
+        .find(|reply| reply["id"] == id)
+        .unwrap_or_else(|| panic!("No response to request {id} in {replies:#?}"));
+
+    &reply["result"]
+}
+
+fn range(line: u64, start: u64, end: u64) -> Value {
+    json!({
+        "start": { "line": line, "character": start },
+        "end": { "line": line, "character": end },
+    })
+}
+
+#[test]
+fn utf16_positions_by_default() {
+    let (replies, exit_code) = session(&messages(None, 18));
+
+    assert_eq!(exit_code, 0);
+    assert_eq!(
+        response(&replies, 1)["capabilities"]["positionEncoding"],
+        "utf-16"
+    );
+
+    let diagnostics = replies
+        .iter()
+        .find(|reply| reply["method"] == "textDocument/publishDiagnostics")
+        .expect("No diagnostics were published");
+    assert_eq!(diagnostics["params"]["uri"], URI);
+    assert_eq!(diagnostics["params"]["diagnostics"][0]["code"], "W0101");
+    assert_eq!(
+        diagnostics["params"]["diagnostics"][0]["range"],
+        range(2, 0, 13)
+    );
+
+    assert_eq!(
+        *response(&replies, 2),
+        json!({ "uri": URI, "range": range(0, 18, 23) })
+    );
+
+    let references = response(&replies, 3)
+        .as_array()
+        .expect("references are an array");
+    let ranges: Vec<&Value> = references
+        .iter()
+        .map(|reference| &reference["range"])
+        .collect();
+    assert_eq!(
+        ranges,
+        [
+            &range(0, 18, 23),
+            &range(1, 6, 11),
+            &range(1, 12, 17),
+            &range(2, 0, 5),
+            &range(2, 8, 13),
+        ]
+    );
+
+    let hover = response(&replies, 4)["contents"]["value"]
+        .as_str()
+        .expect("hover has markdown contents");
+    assert!(hover.starts_with("variable `count`"), "{hover}");
+
+    assert_eq!(
+        response(&replies, 5)[0]["newText"],
+        "var s = \"😀\";\nvar count = 1;\nprint count + count;\ncount = count;\n"
+    );
+
+    assert_eq!(*response(&replies, 6), Value::Null);
+}
+
+#[test]
+fn utf32_positions_when_offered() {
+    let (replies, exit_code) = session(&messages(Some(&["utf-32", "utf-16"]), 17));
+
+    assert_eq!(exit_code, 0);
+    assert_eq!(
+        response(&replies, 1)["capabilities"]["positionEncoding"],
+        "utf-32"
+    );
+    assert_eq!(
+        *response(&replies, 2),
+        json!({ "uri": URI, "range": range(0, 17, 22) })
+    );
+    assert_eq!(response(&replies, 3).as_array().map(Vec::len), Some(5));
+    assert!(response(&replies, 4)["contents"]["value"]
+        .as_str()
+        .is_some_and(|hover| hover.starts_with("variable `count`")));
+}
+
+#[test]
+fn exit_without_shutdown_fails() {
+    let (_, exit_code) = session(&[
+        request(1, "initialize", json!({ "capabilities": {} })),
+        notification("exit", Value::Null),
+    ]);
+
+    assert_eq!(exit_code, 1);
+}
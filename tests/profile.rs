@@ -0,0 +1,98 @@
+//! Runs `rutox profile` on `tests/profile/fib.lox` and checks how often it
+//! reports each line ran, and the call stacks it folds for flame graphs.
+//! Timings vary from run to run, so only their format is checked.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::process::Command;
+
+struct Profile {
+    /// How often each line ran, from the per-line table.
+    counts: BTreeMap<String, u64>,
+    /// Each folded stack, without its time.
+    stacks: Vec<String>,
+}
+
+/// Profiles `fib.lox`, writing the folded stacks to a file named after
+/// `test`, since tests run at the same time.
+fn profile(test: &str) -> Profile {
+    let folded = env::temp_dir().join(format!(
+        "rutox-profile-{test}-{}.folded",
+        std::process::id()
+    ));
+    let output = Command::new(env!("CARGO_BIN_EXE_rutox"))
+        .args(["profile", "--color=never"])
+        .arg(format!("--folded={}", folded.display()))
+        .arg("fib.lox")
+        .current_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/profile"))
+        .output()
+        .expect("Could not run rutox");
+    let stacks = fs::read_to_string(&folded);
+    let _ = fs::remove_file(&folded);
+
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "2\n");
+
+    // The per-line table runs from its header to the first blank line
+    let report = String::from_utf8_lossy(&output.stderr).to_string();
+    let counts = report
+        .lines()
+        .skip_while(|line| !line.starts_with("line "))
+        .skip(1)
+        .take_while(|line| !line.is_empty())
+        .map(|row| {
+            let columns: Vec<&str> = row.split_whitespace().collect();
+            let count = columns[1].parse().expect("The count is a whole number");
+
+            (columns[0].to_string(), count)
+        })
+        .collect();
+
+    let stacks = stacks
+        .expect("No folded stacks were written")
+        .lines()
+        .map(|line| {
+            let (stack, time) = line.rsplit_once(' ').expect("A stack ends with its time");
+            assert!(time.parse::<u64>().is_ok(), "Bad time in {line}");
+
+            stack.to_string()
+        })
+        .collect();
+
+    Profile { counts, stacks }
+}
+
+#[test]
+fn counts_how_often_each_line_ran() {
+    let counts: Vec<(&str, u64)> = vec![
+        ("fib.lox:1", 1),
+        ("fib.lox:2", 5),
+        ("fib.lox:3", 2),
+        ("fib.lox:6", 1),
+    ];
+
+    assert_eq!(
+        profile("counts").counts,
+        counts
+            .into_iter()
+            .map(|(line, count)| (line.to_string(), count))
+            .collect()
+    );
+}
+
+#[test]
+fn folds_stacks_by_function_and_line() {
+    assert_eq!(
+        profile("stacks").stacks,
+        [
+            "<script>;fib.lox:1",
+            "<script>;fib.lox:6",
+            "<script>;fib;fib.lox:2",
+            "<script>;fib;fib.lox:3",
+            "<script>;fib;fib;fib.lox:2",
+            "<script>;fib;fib;fib.lox:3",
+            "<script>;fib;fib;fib;fib.lox:2",
+        ]
+    );
+}
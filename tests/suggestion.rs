@@ -0,0 +1,45 @@
+//! Checks the did-you-mean suggestions for names and keywords.
+
+use rutox::scanner::KEYWORDS;
+use rutox::suggestion::{did_you_mean, keyword_for};
+
+#[test]
+fn suggests_the_closest_candidate() {
+    assert_eq!(did_you_mean("totl", ["total", "count"]), Some("total"));
+    assert_eq!(did_you_mean("conut", ["total", "count"]), Some("count"));
+}
+
+#[test]
+fn suggests_nothing_when_no_candidate_is_close() {
+    assert_eq!(did_you_mean("zzzzzz", ["total", "count"]), None);
+    assert_eq!(did_you_mean("total", ["total"]), None);
+}
+
+#[test]
+fn suggests_keywords_for_typos() {
+    assert_eq!(keyword_for("retrun"), Some("return"));
+    assert_eq!(keyword_for("whlie"), Some("while"));
+    assert_eq!(keyword_for("improt"), Some("import"));
+    assert_eq!(keyword_for("total"), None);
+}
+
+#[test]
+fn suggests_keywords_for_other_languages_words() {
+    assert_eq!(keyword_for("function"), Some("fun"));
+    assert_eq!(keyword_for("let"), Some("var"));
+    assert_eq!(keyword_for("null"), Some("nil"));
+    assert_eq!(keyword_for("elif"), Some("else if"));
+    assert_eq!(keyword_for("funtion"), Some("fun"));
+}
+
+#[test]
+fn suggests_every_keyword_the_scanner_knows() {
+    for (keyword, _) in KEYWORDS.iter().filter(|(keyword, _)| keyword.len() >= 4) {
+        // Swapping the first two characters is a one-letter typo
+        let mut typo: Vec<char> = keyword.chars().collect();
+        typo.swap(0, 1);
+        let typo: String = typo.into_iter().collect();
+
+        assert_eq!(keyword_for(&typo), Some(*keyword), "for `{typo}`");
+    }
+}
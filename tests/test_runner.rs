@@ -0,0 +1,141 @@
+//! Runs `rutox test` over the fixtures in `tests/test_runner` and checks the
+//! line it prints for each test, the failures it reports, and how it exits.
+
+use serde_json::Value;
+use std::process::Command;
+
+/// What rutox exits with when a test fails.
+const FAILURE_EXIT_CODE: i32 = 74;
+
+struct Run {
+    stdout: Vec<String>,
+    stderr: String,
+    exit_code: i32,
+}
+
+/// Runs `rutox test` on `path`, relative to the crate root.
+fn rutox_test(path: &str, args: &[&str]) -> Run {
+    let output = Command::new(env!("CARGO_BIN_EXE_rutox"))
+        .args(["test", "--color=never"])
+        .args(args)
+        .arg(path)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("Could not run rutox");
+
+    Run {
+        stdout: String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect(),
+        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        exit_code: output.status.code().unwrap_or(-1),
+    }
+}
+
+/// The `ok` and `FAILED` lines printed as tests finish, in order.
+fn results(run: &Run) -> Vec<&str> {
+    run.stdout
+        .iter()
+        .map(String::as_str)
+        .filter(|line| line.starts_with("ok ") || line.starts_with("FAILED "))
+        .collect()
+}
+
+fn last_line(run: &Run) -> &str {
+    run.stdout.last().map(String::as_str).unwrap_or_default()
+}
+
+#[test]
+fn passing_tests_exit_successfully() {
+    let run = rutox_test("tests/test_runner/math_test.lox", &[]);
+
+    assert_eq!(
+        results(&run),
+        [
+            "ok     tests/test_runner/math_test.lox: squares numbers",
+            "ok     tests/test_runner/math_test.lox: squares negatives",
+        ]
+    );
+    assert_eq!(last_line(&run), "Test result: ok. 2 passed; 0 failed");
+    assert_eq!(run.exit_code, 0);
+}
+
+#[test]
+fn directories_are_searched_for_test_files() {
+    let run = rutox_test("tests/test_runner", &[]);
+
+    // `helper.lox` fails if run, but isn't a test file
+    assert_eq!(
+        results(&run),
+        [
+            "ok     tests/test_runner/failing/failing_test.lox: passes",
+            "FAILED tests/test_runner/failing/failing_test.lox: compares",
+            "FAILED tests/test_runner/failing/failing_test.lox: asserts",
+            "ok     tests/test_runner/math_test.lox: squares numbers",
+            "ok     tests/test_runner/math_test.lox: squares negatives",
+            "ok     tests/test_runner/script_test.lox",
+        ]
+    );
+    assert_eq!(last_line(&run), "Test result: FAILED. 4 passed; 2 failed");
+    assert_eq!(run.exit_code, FAILURE_EXIT_CODE);
+}
+
+#[test]
+fn failures_are_reported_with_their_assertion() {
+    let run = rutox_test("tests/test_runner/failing", &["--error-format=json"]);
+
+    let failures: Vec<Value> = run
+        .stderr
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("Failures are reported as JSON"))
+        .collect();
+    let failures: Vec<(&str, u64, &str)> = failures
+        .iter()
+        .map(|failure| {
+            (
+                failure["code"].as_str().unwrap_or_default(),
+                failure["line"].as_u64().unwrap_or_default(),
+                failure["message"].as_str().unwrap_or_default(),
+            )
+        })
+        .collect();
+
+    assert_eq!(
+        failures,
+        [
+            (
+                "E0210",
+                6,
+                "Assertion failed: expected number 3, got number 2"
+            ),
+            (
+                "E0210",
+                10,
+                "Assertion failed: expected a truthy value, got nil"
+            ),
+        ]
+    );
+    assert_eq!(last_line(&run), "Test result: FAILED. 1 passed; 2 failed");
+    assert_eq!(run.exit_code, FAILURE_EXIT_CODE);
+}
+
+#[test]
+fn failures_name_their_test_in_the_summary() {
+    let run = rutox_test("tests/test_runner/failing", &[]);
+    let failures = run
+        .stdout
+        .iter()
+        .skip_while(|line| *line != "Failures:")
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let compares = failures
+        .find("tests/test_runner/failing/failing_test.lox: compares\nRuntimeError[E0210]")
+        .expect("The failing `assertEqual` is reported");
+    let asserts = failures
+        .find("tests/test_runner/failing/failing_test.lox: asserts\nRuntimeError[E0210]")
+        .expect("The failing `assert` is reported");
+    assert!(compares < asserts);
+}
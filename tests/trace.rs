@@ -0,0 +1,42 @@
+//! Checks what `rutox --trace` writes to stderr: each statement and
+//! evaluated expression with its location and value, indented by call depth.
+
+use std::process::Command;
+
+#[test]
+fn trace_shows_every_step_indented_by_call_depth() {
+    let output = Command::new(env!("CARGO_BIN_EXE_rutox"))
+        .args(["--trace", "--color=never", "add.lox"])
+        .current_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/trace"))
+        .output()
+        .expect("Could not run rutox");
+
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "7\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "\
+1:5 Fun(add)
+4:1 Print
+4:7 Variable(add) -> function <fn add>
+4:11 Literal(number) -> number 3
+4:14 Literal(number) -> number 4
+  2:3 Return
+  2:10 Variable(a) -> number 3
+  2:14 Variable(b) -> number 4
+  2:10 Binary(+) -> number 7
+4:7 Call -> number 7
+5:11 Var(total)
+5:13 Variable(add) -> function <fn add>
+5:17 Literal(number) -> number 1
+5:20 Literal(number) -> number 2
+  2:3 Return
+  2:10 Variable(a) -> number 1
+  2:14 Variable(b) -> number 2
+  2:10 Binary(+) -> number 3
+5:13 Call -> number 3
+5:25 Literal(number) -> number 4
+5:13 Binary(+) -> number 7
+"
+    );
+}